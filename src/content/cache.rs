@@ -0,0 +1,96 @@
+use std::{fmt, sync::{Arc, Mutex}};
+
+use lru::LruCache;
+
+/// A [ContentCache] shared by all [super::Content] in a [super::sitemap::SiteMap]
+pub type SharedContentCache = Arc<Mutex<ContentCache>>;
+
+/// Bounded in-memory store of content bodies, keyed by disk path
+///
+/// - Bodies are evicted least recently used first once
+///   [ContentCache::resident_bytes] would exceed [ContentCache::budget_bytes]
+/// - A body larger than the whole budget is never resident
+/// - Evicted bodies are re-read from disk by [super::Content] when next served
+pub struct ContentCache
+{
+    bodies: LruCache<String, Arc<Vec<u8>>>,
+    budget_bytes: u64,
+    resident_bytes: u64
+}
+
+impl fmt::Debug for ContentCache
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ContentCache {{ budget_bytes: {}, resident_bytes: {}, resident: {} }}", self.budget_bytes, self.resident_bytes, self.bodies.len())
+    }
+}
+
+impl ContentCache
+{
+    pub fn new(budget_bytes: u64) -> ContentCache
+    {
+        ContentCache { bodies: LruCache::unbounded(), budget_bytes, resident_bytes: 0 }
+    }
+
+    pub fn shared(budget_bytes: u64) -> SharedContentCache
+    {
+        Arc::new(Mutex::new(ContentCache::new(budget_bytes)))
+    }
+
+    pub fn budget_bytes(&self) -> u64 { self.budget_bytes }
+
+    pub fn resident_bytes(&self) -> u64 { self.resident_bytes }
+
+    /// Get a body, marking it as most recently used
+    pub fn get(&mut self, path: &str) -> Option<Arc<Vec<u8>>>
+    {
+        self.bodies.get(path).cloned()
+    }
+
+    /// Insert (or replace) a body, evicting least recently used bodies
+    ///   to stay within [ContentCache::budget_bytes]
+    pub fn insert(&mut self, path: &str, body: Vec<u8>) -> Arc<Vec<u8>>
+    {
+        self.remove(path);
+        let body = Arc::new(body);
+        let size = body.len() as u64;
+
+        if size > self.budget_bytes
+        {
+            crate::debug(format!("{} ({} bytes) exceeds the content cache budget, not resident", path, size), Some("CACHE"));
+            return body
+        }
+
+        while self.resident_bytes + size > self.budget_bytes
+        {
+            match self.bodies.pop_lru()
+            {
+                Some((evicted, evicted_body)) =>
+                {
+                    self.resident_bytes -= evicted_body.len() as u64;
+                    crate::debug(format!("Evicted {} ({} bytes)", evicted, evicted_body.len()), Some("CACHE"));
+                },
+                None => break
+            }
+        }
+
+        self.bodies.push(path.to_string(), body.clone());
+        self.resident_bytes += size;
+        crate::debug(format!("Resident {} ({} bytes), using {}/{} bytes", path, size, self.resident_bytes, self.budget_bytes), Some("CACHE"));
+        body
+    }
+
+    pub fn remove(&mut self, path: &str)
+    {
+        if let Some(body) = self.bodies.pop(path)
+        {
+            self.resident_bytes -= body.len() as u64;
+        }
+    }
+
+    /// Disk paths of resident bodies, most recently used first
+    pub fn resident(&self) -> Vec<String>
+    {
+        self.bodies.iter().map(|(path, _)| path.clone()).collect()
+    }
+}
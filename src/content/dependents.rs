@@ -0,0 +1,57 @@
+use std::{collections::{BTreeSet, HashMap}, path::{Path, PathBuf}, sync::{Arc, Mutex}};
+
+/// [Dependents] shared by all [super::Content] in a [super::sitemap::SiteMap]
+pub type SharedDependents = Arc<Mutex<Dependents>>;
+
+/// Uris of content by the files their rendered bodies depend on, e.g. partials and
+///   layouts, see [super::Content::dependencies]. Updated as content is loaded, so a
+///   changed file refreshes only the content using it
+#[derive(Debug, Default)]
+pub struct Dependents
+{
+    uris: HashMap<PathBuf, BTreeSet<String>>
+}
+
+impl Dependents
+{
+    pub fn shared() -> SharedDependents
+    {
+        Arc::new(Mutex::new(Dependents::default()))
+    }
+
+    /// Replace the dependencies of the content at uri
+    pub fn update(&mut self, uri: &str, old: &[String], new: &[String])
+    {
+        for path in old
+        {
+            let key = key(path);
+            if let Some(uris) = self.uris.get_mut(&key)
+            {
+                uris.remove(uri);
+                if uris.is_empty() { self.uris.remove(&key); }
+            }
+        }
+        for path in new
+        {
+            self.uris.entry(key(path)).or_default().insert(uri.to_string());
+        }
+    }
+
+    /// Uris of content depending on the file at path
+    pub fn of(&self, path: &str) -> Vec<String>
+    {
+        self.uris.get(&key(path)).map_or(vec![], |uris| uris.iter().cloned().collect())
+    }
+}
+
+/// The canonical path, resolved through its directory if the file was removed
+fn key(path: &str) -> PathBuf
+{
+    let path = Path::new(path);
+    if let Ok(canonical) = std::fs::canonicalize(path) { return canonical }
+    match (path.parent().and_then(|p| std::fs::canonicalize(p).ok()), path.file_name())
+    {
+        (Some(parent), Some(name)) => parent.join(name),
+        _ => path.to_path_buf()
+    }
+}
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+
+/// Content-Encodings of precompressed sidecar files, e.g. app.js.br
+///   next to app.js, in order of preference
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Encoding
+{
+    Brotli,
+    Zstd,
+    Gzip
+}
+
+const ENCODINGS: [Encoding; 3] = [Encoding::Brotli, Encoding::Zstd, Encoding::Gzip];
+
+impl Encoding
+{
+    /// The sidecar file extension
+    pub fn extension(&self) -> &'static str
+    {
+        match self
+        {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zst",
+            Encoding::Gzip => "gz"
+        }
+    }
+
+    /// The Content-Encoding (and Accept-Encoding) token
+    pub fn as_str(&self) -> &'static str
+    {
+        match self
+        {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip"
+        }
+    }
+}
+
+/// If path is a precompressed sidecar, the path of the original and its [Encoding],
+///   e.g. app.js.br is app.js with [Encoding::Brotli]
+pub fn sidecar_of(path: &str) -> Option<(String, Encoding)>
+{
+    for encoding in ENCODINGS
+    {
+        if let Some(original) = path.strip_suffix(&format!(".{}", encoding.extension()))
+        {
+            if !original.is_empty() && !original.ends_with('/')
+            {
+                return Some((original.to_string(), encoding))
+            }
+        }
+    }
+    None
+}
+
+/// Sidecars of the file at path present on disk
+pub fn find_sidecars(path: &str) -> Vec<(Encoding, String)>
+{
+    ENCODINGS.into_iter()
+        .map(|e| (e, format!("{}.{}", path, e.extension())))
+        .filter(|(_, sidecar)| std::path::Path::new(sidecar).is_file())
+        .collect()
+}
+
+/// The most preferred available [Encoding] acceptable by an Accept-Encoding header,
+///   highest quality first then [Encoding] order. None if only identity is acceptable
+pub fn preferred_encoding(accept_encoding: &str, available: &[Encoding]) -> Option<Encoding>
+{
+    let mut wildcard: Option<f32> = None;
+    let mut qualities: Vec<(String, f32)> = vec![];
+    for token in accept_encoding.split(',')
+    {
+        let mut parts = token.split(';');
+        let name = parts.next().unwrap_or_default().trim().to_lowercase();
+        if name.is_empty() { continue }
+        let q = parts
+            .filter_map(|p| p.trim().strip_prefix("q=").and_then(|q| q.trim().parse::<f32>().ok()))
+            .next()
+            .unwrap_or(1.0);
+        if name == "*" { wildcard = Some(q) } else { qualities.push((name, q)) }
+    }
+
+    let mut best: Option<(Encoding, f32)> = None;
+    for encoding in ENCODINGS.into_iter().filter(|e| available.contains(e))
+    {
+        let q = match qualities.iter().find(|(name, _)| name == encoding.as_str())
+        {
+            Some((_, q)) => *q,
+            None => wildcard.unwrap_or(0.0)
+        };
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q)
+        {
+            best = Some((encoding, q));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
@@ -0,0 +1,226 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use quick_xml::{escape::unescape, events::{BytesDecl, BytesText, Event}, Error, Writer};
+use regex::Regex;
+
+use crate::config::{FeedConfig, FeedFormat};
+
+use super::{markdown::{is_markdown, split_front_matter}, sitemap::LastModified};
+
+/// Entries in a feed unless [FeedConfig::max_entries] is set
+pub const DEFAULT_MAX_ENTRIES: usize = 20;
+
+/// A page in a feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedEntry
+{
+    pub uri: String,
+    pub title: String,
+    pub summary: Option<String>,
+    pub date: DateTime<Utc>
+}
+
+/// Page metadata from html ```<title>```, ```<meta>``` and ```<time>``` tags
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageMeta
+{
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    pub date: Option<String>
+}
+
+/// Read title, summary (description) and date (published time) from html
+pub fn html_meta(html: &str) -> PageMeta
+{
+    let mut meta: BTreeMap<String, String> = BTreeMap::new();
+    let attribute = Regex::new(r#"(?i)\b(name|property|content|itemprop)\s*=\s*("([^"]*)"|'([^']*)')"#).unwrap();
+    for tag in Regex::new(r"(?i)<meta\s[^>]*>").unwrap().find_iter(html)
+    {
+        let (mut key, mut value) = (None, None);
+        for caps in attribute.captures_iter(tag.as_str())
+        {
+            let v = caps.get(3).or(caps.get(4)).map_or("", |m| m.as_str()).to_string();
+            if caps[1].eq_ignore_ascii_case("content") { value = Some(v) } else { key = Some(v.to_lowercase()) }
+        }
+        if let (Some(key), Some(value)) = (key, value)
+        {
+            meta.entry(key).or_insert(value);
+        }
+    }
+
+    let first = |keys: &[&str]| keys.iter().find_map(|k| meta.get(*k).cloned());
+
+    let title = Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap()
+        .captures(html)
+        .map(|caps| caps[1].trim().to_string())
+        .filter(|t| !t.is_empty())
+        .or(first(&["og:title"]));
+
+    let date = first(&["article:published_time", "date", "dc.date", "datepublished", "og:published_time"])
+        .or(Regex::new(r#"(?i)<time[^>]*\bdatetime\s*=\s*["']([^"']+)["']"#).unwrap().captures(html).map(|caps| caps[1].to_string()));
+
+    PageMeta
+    {
+        title: title.map(|t| unescaped(&t)),
+        summary: first(&["description", "og:description"]).map(|s| unescaped(&s)),
+        date
+    }
+}
+
+fn unescaped(s: &str) -> String
+{
+    match unescape(s)
+    {
+        Ok(u) => u.to_string(),
+        Err(_) => s.to_string()
+    }
+}
+
+/// Parse RFC 3339, RFC 2822 or YYYY-MM-DD dates
+pub fn parse_date(date: &str) -> Option<DateTime<Utc>>
+{
+    let date = date.trim();
+    if let Ok(d) = DateTime::parse_from_rfc3339(date) { return Some(d.with_timezone(&Utc)) }
+    if let Ok(d) = DateTime::parse_from_rfc2822(date) { return Some(d.with_timezone(&Utc)) }
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| d.and_utc())
+}
+
+/// A [FeedEntry] for the page source served at uri from disk_path, Markdown front-matter or
+///   html meta tags are used, falling back to the file name and last_modified
+pub fn feed_entry(uri: &str, disk_path: &str, source: &str, last_modified: &LastModified) -> Option<FeedEntry>
+{
+    let meta = if is_markdown(disk_path)
+    {
+        let (front_matter, _) = split_front_matter(source);
+        PageMeta { title: front_matter.title, summary: front_matter.summary, date: front_matter.date }
+    }
+    else
+    {
+        html_meta(source)
+    };
+
+    let title = meta.title.unwrap_or_else(||
+        std::path::Path::new(disk_path).file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string()
+    );
+
+    let date = match meta.date.as_deref().and_then(parse_date)
+    {
+        Some(date) => date,
+        None => last_modified.get(disk_path)?
+    };
+
+    Some(FeedEntry { uri: uri.to_string(), title, summary: meta.summary, date })
+}
+
+/// Write a feed, newest entries first
+pub fn to_xml(config: &FeedConfig, domain: &str, entries: &[FeedEntry]) -> Vec<u8>
+{
+    let mut entries = entries.to_vec();
+    entries.sort_by(|a, b| b.date.cmp(&a.date).then(a.uri.cmp(&b.uri)));
+    entries.truncate(config.max_entries.unwrap_or(DEFAULT_MAX_ENTRIES));
+
+    let mut buffer = Vec::new();
+    let mut writer = Writer::new_with_indent(&mut buffer, b' ', 4);
+
+    let result = writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+        .and_then(|_| match config.format.unwrap_or(FeedFormat::Rss)
+        {
+            FeedFormat::Rss => write_rss(&mut writer, config, domain, &entries),
+            FeedFormat::Atom => write_atom(&mut writer, config, domain, &entries)
+        });
+
+    if let Err(e) = result
+    {
+        crate::debug(format!("Error {} writing feed {}", e, config.uri), None);
+    }
+    buffer
+}
+
+fn write_rss(writer: &mut Writer<&mut Vec<u8>>, config: &FeedConfig, domain: &str, entries: &[FeedEntry]) -> Result<(), Error>
+{
+    let title = config.title.clone().unwrap_or(domain.to_string());
+    writer.create_element("rss")
+        .with_attributes([("version", "2.0"), ("xmlns:atom", "http://www.w3.org/2005/Atom")])
+        .write_inner_content::<_, Error>(|writer|
+        {
+            writer.create_element("channel").write_inner_content::<_, Error>(|writer|
+            {
+                writer.create_element("title").write_text_content(BytesText::new(&title))?;
+                writer.create_element("link").write_text_content(BytesText::new(&format!("{}{}", domain, config.prefix)))?;
+                writer.create_element("description").write_text_content(BytesText::new(config.description.as_deref().unwrap_or(&title)))?;
+                writer.create_element("atom:link")
+                    .with_attributes([("href", format!("{}{}", domain, config.uri).as_str()), ("rel", "self"), ("type", "application/rss+xml")])
+                    .write_empty()?;
+                if let Some(newest) = entries.first()
+                {
+                    writer.create_element("lastBuildDate").write_text_content(BytesText::new(&newest.date.to_rfc2822()))?;
+                }
+                for entry in entries
+                {
+                    let link = format!("{}{}", domain, entry.uri);
+                    writer.create_element("item").write_inner_content::<_, Error>(|writer|
+                    {
+                        writer.create_element("title").write_text_content(BytesText::new(&entry.title))?;
+                        writer.create_element("link").write_text_content(BytesText::new(&link))?;
+                        writer.create_element("guid").write_text_content(BytesText::new(&link))?;
+                        writer.create_element("pubDate").write_text_content(BytesText::new(&entry.date.to_rfc2822()))?;
+                        if let Some(ref summary) = entry.summary
+                        {
+                            writer.create_element("description").write_text_content(BytesText::new(summary))?;
+                        }
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+fn write_atom(writer: &mut Writer<&mut Vec<u8>>, config: &FeedConfig, domain: &str, entries: &[FeedEntry]) -> Result<(), Error>
+{
+    let title = config.title.clone().unwrap_or(domain.to_string());
+    let feed_url = format!("{}{}", domain, config.uri);
+    let updated = entries.first().map_or(DateTime::UNIX_EPOCH, |e| e.date);
+    writer.create_element("feed")
+        .with_attribute(("xmlns", "http://www.w3.org/2005/Atom"))
+        .write_inner_content::<_, Error>(|writer|
+        {
+            writer.create_element("title").write_text_content(BytesText::new(&title))?;
+            if let Some(ref description) = config.description
+            {
+                writer.create_element("subtitle").write_text_content(BytesText::new(description))?;
+            }
+            writer.create_element("id").write_text_content(BytesText::new(&feed_url))?;
+            writer.create_element("link").with_attributes([("href", feed_url.as_str()), ("rel", "self")]).write_empty()?;
+            writer.create_element("link").with_attribute(("href", format!("{}{}", domain, config.prefix).as_str())).write_empty()?;
+            writer.create_element("updated").write_text_content(BytesText::new(&updated.to_rfc3339()))?;
+            writer.create_element("author").write_inner_content::<_, Error>(|writer|
+            {
+                writer.create_element("name").write_text_content(BytesText::new(config.author.as_deref().unwrap_or(domain)))?;
+                Ok(())
+            })?;
+            for entry in entries
+            {
+                let link = format!("{}{}", domain, entry.uri);
+                writer.create_element("entry").write_inner_content::<_, Error>(|writer|
+                {
+                    writer.create_element("title").write_text_content(BytesText::new(&entry.title))?;
+                    writer.create_element("id").write_text_content(BytesText::new(&link))?;
+                    writer.create_element("link").with_attribute(("href", link.as_str())).write_empty()?;
+                    writer.create_element("updated").write_text_content(BytesText::new(&entry.date.to_rfc3339()))?;
+                    if let Some(ref summary) = entry.summary
+                    {
+                        writer.create_element("summary").write_text_content(BytesText::new(summary))?;
+                    }
+                    Ok(())
+                })?;
+            }
+            Ok(())
+        })?;
+    Ok(())
+}
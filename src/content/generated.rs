@@ -0,0 +1,71 @@
+use std::{collections::BTreeMap, sync::{Arc, RwLock}};
+
+use axum::{http::StatusCode, response::IntoResponse, routing::{get, MethodRouter}};
+
+use super::mime_type::{Mime, MIME};
+
+/// Files generated from the site, e.g. robots.txt, sitemaps and feeds. They are
+///   served from memory by uri so the content path, often a git checkout, is untouched.
+///   Clones share files, so routers see regenerated files without being rebuilt
+#[derive(Clone)]
+pub struct Generated
+{
+    files: Arc<RwLock<BTreeMap<String, Vec<u8>>>>,
+    browser_cache_period_seconds: u16
+}
+
+impl Generated
+{
+    pub fn new(browser_cache_period_seconds: u16) -> Generated
+    {
+        Generated { files: Arc::new(RwLock::new(BTreeMap::new())), browser_cache_period_seconds }
+    }
+
+    pub fn get(&self, uri: &str) -> Option<Vec<u8>>
+    {
+        self.files.read().unwrap().get(uri).cloned()
+    }
+
+    pub fn uris(&self) -> Vec<String>
+    {
+        self.files.read().unwrap().keys().cloned().collect()
+    }
+
+    /// Remove files with uris matching stale, then insert files
+    pub fn update(&self, stale: impl Fn(&str) -> bool, files: Vec<(String, Vec<u8>)>)
+    {
+        let mut current = self.files.write().unwrap();
+        current.retain(|uri, _| !stale(uri));
+        current.extend(files);
+    }
+
+    /// Replace all files with other's
+    pub fn replace_with(&self, other: &Generated)
+    {
+        if Arc::ptr_eq(&self.files, &other.files) { return }
+        let files = other.files.read().unwrap().clone();
+        *self.files.write().unwrap() = files;
+    }
+
+    /// A route serving the file at uri as it is when requested
+    pub fn route(&self, uri: &str) -> MethodRouter
+    {
+        let generated = self.clone();
+        let uri = uri.to_string();
+        get(move || async move
+        {
+            match generated.get(&uri)
+            {
+                Some(body) =>
+                {
+                    let mut response = body.into_response();
+                    let mime = <MIME as Mime>::infer_mime_type(&uri);
+                    response.headers_mut().insert("content-type", mime.header_value().parse().unwrap());
+                    response.headers_mut().insert("cache-control", format!("public, max-age={}", generated.browser_cache_period_seconds).parse().unwrap());
+                    response
+                },
+                None => StatusCode::NOT_FOUND.into_response()
+            }
+        })
+    }
+}
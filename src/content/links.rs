@@ -0,0 +1,148 @@
+use std::{collections::HashSet, sync::{Arc, OnceLock, RwLock}};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, integrations::{discord::post::try_post, git::HeadInfo}};
+
+use super::media::resolve_reference;
+
+/// A reference from a page to a uri the site does not serve
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BrokenLink
+{
+    /// The referencing page's uri
+    pub page: String,
+    /// The ```href``` or ```src``` as written
+    pub reference: String,
+    /// The uri it resolves to
+    pub target: String
+}
+
+/// Broken internal links found when the sitemap was last built, with the
+///   content's git HEAD at the time
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkReport
+{
+    pub checked_utc: String,
+    pub commit: Option<HeadInfo>,
+    pub pages: usize,
+    pub links: usize,
+    pub broken: Vec<BrokenLink>
+}
+
+pub type SharedLinkReport = Arc<RwLock<LinkReport>>;
+
+impl LinkReport
+{
+    /// A notification listing (up to max) broken links, None if there are none
+    pub fn message(&self, domain: &str, max: usize) -> Option<String>
+    {
+        if self.broken.is_empty() { return None }
+
+        let mut lines: Vec<String> = self.broken.iter().take(max)
+            .map(|b| format!(" {} -> {}", b.page, b.reference))
+            .collect();
+        if self.broken.len() > max { lines.push(format!(" and {} more", self.broken.len() - max)); }
+
+        let commit = match self.commit
+        {
+            Some(ref info) => format!(" at commit {} by {} at {}", info.hash, info.author_name, info.datetime),
+            None => String::new()
+        };
+
+        Some(format!("Found {} broken links on {}{}:\n```\n{}\n```", self.broken.len(), domain, commit, lines.join("\n")))
+    }
+}
+
+/// Values of ```href``` and ```src``` attributes in html
+pub fn link_references(html: &str) -> Vec<String>
+{
+    static COMMENT: OnceLock<Regex> = OnceLock::new();
+    static REFERENCE: OnceLock<Regex> = OnceLock::new();
+    let html = COMMENT.get_or_init(|| Regex::new(r"(?s)<!--.*?-->").unwrap()).replace_all(html, "");
+    REFERENCE.get_or_init(|| Regex::new(r#"(?i)\s(?:href|src)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap())
+        .captures_iter(&html)
+        .filter_map(|c| c.get(1).or(c.get(2)).map(|m| m.as_str().trim().to_string()))
+        .collect()
+}
+
+/// Decode %XX escapes, e.g. in ```/my%20page.html```
+pub fn percent_decode(uri: &str) -> String
+{
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len()
+    {
+        if bytes[i] == b'%' && i + 2 < bytes.len()
+        {
+            if let Some(b) = uri.get(i+1..i+3).and_then(|h| u8::from_str_radix(h, 16).ok())
+            {
+                decoded.push(b);
+                i += 3;
+                continue
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8(decoded).unwrap_or(uri.to_string())
+}
+
+/// Check ```href```/```src``` references in pages (uri, html) resolve to uris
+///   the site serves. External links, fragments and other schemes (e.g. ```mailto:```)
+///   are not checked
+pub fn check_links(pages: &[(String, String)], served: &HashSet<String>, domain: &str) -> LinkReport
+{
+    let references: Vec<(String, Vec<String>)> = pages.iter().map(|(uri, html)| (uri.clone(), link_references(html))).collect();
+    check_references(&references, served, domain)
+}
+
+/// [check_links] for pages (uri, references) whose references are already found,
+///   e.g. cached, see [super::page_cache::PageCache]
+pub fn check_references(pages: &[(String, Vec<String>)], served: &HashSet<String>, domain: &str) -> LinkReport
+{
+    static SCHEME: OnceLock<Regex> = OnceLock::new();
+    let scheme = SCHEME.get_or_init(|| Regex::new(r"^[a-zA-Z][a-zA-Z0-9+.-]*:").unwrap());
+    let mut report = LinkReport { pages: pages.len(), ..Default::default() };
+    let mut broken: HashSet<BrokenLink> = HashSet::new();
+
+    for (uri, references) in pages
+    {
+        for reference in references.iter().cloned()
+        {
+            if scheme.is_match(&reference) && !reference.starts_with("http://") && !reference.starts_with("https://") { continue }
+            let target = match resolve_reference(uri, &reference, domain)
+            {
+                Some(t) => percent_decode(&t),
+                None => continue
+            };
+            report.links += 1;
+            if !served.contains(&target)
+            {
+                broken.insert(BrokenLink { page: uri.clone(), reference, target });
+            }
+        }
+    }
+
+    report.broken = broken.into_iter().collect();
+    report.broken.sort();
+    report
+}
+
+/// Post the report to [crate::config::Config::notification_endpoint] if its broken
+///   links differ from previous, e.g. after a git pull removed a linked file
+pub async fn notify_broken_links(report: &LinkReport, previous: &[BrokenLink], config: &Config)
+{
+    if report.broken == previous { return }
+    match report.message(&config.domain, 20)
+    {
+        Some(msg) =>
+        {
+            crate::debug(msg.clone(), Some("LINKS"));
+            try_post(config.notification_endpoint.clone(), &msg).await;
+        },
+        None => crate::debug("Broken links fixed".to_string(), Some("LINKS"))
+    }
+}
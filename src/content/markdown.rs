@@ -0,0 +1,194 @@
+use std::path::Path;
+
+use pulldown_cmark::{html, Options, Parser};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+use crate::filesystem::file::{read_file_utf8, FileError};
+
+use super::template::content_file;
+
+/// Used when no layout is configured, see [crate::config::ContentConfig::markdown_layout]
+pub const DEFAULT_LAYOUT: &str = "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{{ title }}</title>
+</head>
+<body>
+{{ content }}
+</body>
+</html>
+";
+
+/// Front-matter of a Markdown page, YAML between ```---``` lines at the top of the file
+///
+/// - ```title: Option<String>```: the page title, defaults to the file name
+/// - ```date: Option<String>```: the publication date
+/// - ```summary: Option<String>```: a short description, used in feeds
+/// - ```draft: Option<bool>```: drafts are not served or listed in the sitemap
+/// - ```template: Option<String>```: a layout for this page (relative to the content path),
+///   instead of [crate::config::ContentConfig::markdown_layout]. Layouts outside the content path
+///   are refused, see [super::template::content_file]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrontMatter
+{
+    pub title: Option<String>,
+    pub date: Option<String>,
+    pub summary: Option<String>,
+    pub draft: Option<bool>,
+    pub template: Option<String>
+}
+
+impl FrontMatter
+{
+    pub fn is_draft(&self) -> bool
+    {
+        self.draft.is_some_and(|d| d)
+    }
+}
+
+/// A rendered Markdown page
+///
+/// - ```html```: the page, in its layout
+/// - ```front_matter```: the page's [FrontMatter]
+/// - ```dependencies```: disk paths the page depends on (its layout)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page
+{
+    pub html: String,
+    pub front_matter: FrontMatter,
+    pub dependencies: Vec<String>
+}
+
+/// Where to find layouts for rendering Markdown [super::Content]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarkdownOptions
+{
+    pub content_path: String,
+    pub layout: Option<String>
+}
+
+/// If the disk path is a Markdown file
+pub fn is_markdown(path: &str) -> bool
+{
+    Regex::new(r"(?i)\.(md|markdown)$").unwrap().is_match(path)
+}
+
+/// Split the source into its [FrontMatter] and Markdown body.
+///   Missing or malformed front-matter is the default [FrontMatter]
+pub fn split_front_matter(source: &str) -> (FrontMatter, &str)
+{
+    let re = Regex::new(r"^(\u{feff})?---[ \t]*\r?\n((?s).*?)\r?\n---[ \t]*(\r?\n|$)").unwrap();
+    match re.captures(source)
+    {
+        Some(captures) =>
+        {
+            let body = &source[captures.get(0).unwrap().end()..];
+            match serde_yaml::from_str::<FrontMatter>(&captures[2])
+            {
+                Ok(front_matter) => (front_matter, body),
+                Err(e) =>
+                {
+                    crate::debug(format!("Error parsing front-matter {}", e), None);
+                    (FrontMatter::default(), body)
+                }
+            }
+        },
+        None => (FrontMatter::default(), source)
+    }
+}
+
+/// Read only the [FrontMatter] of a Markdown file
+pub fn read_front_matter(path: &str) -> Option<FrontMatter>
+{
+    read_file_utf8(path).map(|source| split_front_matter(&source).0)
+}
+
+/// Render Markdown (CommonMark with tables, footnotes, strikethrough and task lists) to html
+pub fn render_markdown(markdown: &str) -> String
+{
+    let options = Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, Parser::new_ext(markdown, options));
+    rendered
+}
+
+/// Substitute ```{{ content }}```, ```{{ title }}``` and ```{{ date }}``` in a layout.
+///   Unknown placeholders are left as-is
+pub fn apply_layout(layout: &str, content: &str, title: &str, date: &str) -> String
+{
+    let re = Regex::new(r"\{\{\s*(content|title|date)\s*\}\}").unwrap();
+    re.replace_all(layout, |caps: &Captures|
+    {
+        match &caps[1]
+        {
+            "content" => content.to_string(),
+            "title" => escape_html(title),
+            _ => escape_html(date)
+        }
+    }).to_string()
+}
+
+/// Render the Markdown file at path to a html [Page] using its layout
+pub fn render_page(path: &str, options: &MarkdownOptions) -> Result<Page, FileError>
+{
+    let source = match read_file_utf8(path)
+    {
+        Some(s) => s,
+        None => return Err(FileError { why: format!("Could not read Markdown from {}", path) })
+    };
+
+    let (front_matter, markdown) = split_front_matter(&source);
+
+    let layout_path = match front_matter.template
+    {
+        Some(ref template) => match content_file(&options.content_path, template)
+        {
+            Some(layout_path) => Some(layout_path),
+            None =>
+            {
+                crate::debug(format!("Refusing layout {} for {}, outside the content path, using the default", template, path), None);
+                None
+            }
+        },
+        None => options.layout.clone()
+    };
+
+    let layout = match layout_path
+    {
+        Some(ref layout_path) => match read_file_utf8(layout_path)
+        {
+            Some(layout) => layout,
+            None =>
+            {
+                crate::debug(format!("Could not read layout {} for {}, using the default", layout_path, path), None);
+                DEFAULT_LAYOUT.to_string()
+            }
+        },
+        None => DEFAULT_LAYOUT.to_string()
+    };
+
+    let title = match front_matter.title
+    {
+        Some(ref title) => title.clone(),
+        None => Path::new(path).file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string()
+    };
+
+    let date = front_matter.date.clone().unwrap_or_default();
+
+    Ok
+    (
+        Page
+        {
+            html: apply_layout(&layout, &render_markdown(markdown), &title, &date),
+            front_matter,
+            dependencies: layout_path.into_iter().collect()
+        }
+    )
+}
+
+fn escape_html(s: &str) -> String
+{
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
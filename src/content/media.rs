@@ -0,0 +1,91 @@
+use regex::Regex;
+
+use super::mime_type::MIME;
+
+/// An image or video used by a page, see [media_references]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Media
+{
+    pub uri: String,
+    pub mime: MIME,
+    pub disk_path: String
+}
+
+/// Image and video references in html, from ```<img>``` (src and srcset), ```<video>```
+///   (src and poster), ```<source>``` (src and srcset) and og:image meta tags, as written
+pub fn media_references(html: &str) -> Vec<String>
+{
+    let tag = Regex::new(r"(?is)<(img|video|source|meta)\s[^>]*>").unwrap();
+    let attribute = Regex::new(r#"(?is)\b(src|srcset|poster|content|property|name)\s*=\s*("([^"]*)"|'([^']*)')"#).unwrap();
+
+    let mut references = vec![];
+    for caps in tag.captures_iter(html)
+    {
+        let is_meta = caps[1].eq_ignore_ascii_case("meta");
+        let mut og_image = false;
+        let mut values = vec![];
+        for attr in attribute.captures_iter(&caps[0])
+        {
+            let name = attr[1].to_lowercase();
+            let value = attr.get(3).or(attr.get(4)).map_or("", |m| m.as_str()).trim();
+            match name.as_str()
+            {
+                "property" | "name" => og_image |= value.eq_ignore_ascii_case("og:image"),
+                "content" => if is_meta { values.push(value.to_string()) },
+                // candidates are "a.jpg 1x, b.jpg 2x"
+                "srcset" => if !is_meta
+                {
+                    values.extend(value.split(',').filter_map(|c| c.split_whitespace().next()).map(|c| c.to_string()))
+                },
+                _ => if !is_meta { values.push(value.to_string()) }
+            }
+        }
+        if is_meta && !og_image { continue }
+        references.extend(values.into_iter().filter(|v| !v.is_empty()));
+    }
+    references.dedup();
+    references
+}
+
+/// The site uri a reference from the page at page_uri points to, None if
+///   it is on another domain or is inline data
+pub fn resolve_reference(page_uri: &str, reference: &str, domain: &str) -> Option<String>
+{
+    let reference = reference.split(['?', '#']).next().unwrap_or_default();
+    if reference.is_empty() || reference.starts_with("data:") { return None }
+
+    let domain = domain.trim_end_matches('/');
+    let schemeless = domain.split_once("://").map_or(domain, |(_, d)| d);
+    let path = if let Some(rest) = reference.strip_prefix(domain)
+    {
+        rest.to_string()
+    }
+    else if let Some(rest) = reference.strip_prefix("//").and_then(|r| r.strip_prefix(schemeless))
+    {
+        rest.to_string()
+    }
+    else if reference.contains("://") || reference.starts_with("//")
+    {
+        return None
+    }
+    else if reference.starts_with('/')
+    {
+        reference.to_string()
+    }
+    else
+    {
+        format!("{}{}", &page_uri[..page_uri.rfind('/').map_or(0, |i| i + 1)], reference)
+    };
+
+    let mut segments: Vec<&str> = vec![];
+    for segment in path.split('/')
+    {
+        match segment
+        {
+            "" | "." => (),
+            ".." => { segments.pop(); },
+            s => segments.push(s)
+        }
+    }
+    Some(format!("/{}", segments.join("/")))
+}
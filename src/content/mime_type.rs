@@ -1,13 +1,14 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::Path, sync::{OnceLock, RwLock}};
 
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 
 /// Identifies the MIME type by file extension, no attempt is made to verify the file's content
-/// 
-/// Supported MIME types in Busser, default is ```"application/octet-stream"```
+///
+/// Supported MIME types in Busser, default is ```"application/octet-stream"```.
+/// Further extensions may be mapped with [register_mime_types], types not listed
+/// here are [MIME::Custom].
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
-pub enum MIME 
+pub enum MIME
 {
     TextPlain,
     TextHtml,
@@ -15,6 +16,7 @@ pub enum MIME
     TextCSV,
     TextJavascript,
     TextXML,
+    TextMarkdown,
     ImageGIF,
     ImagePNG,
     ImageJPEG,
@@ -23,103 +25,288 @@ pub enum MIME
     ImageXICON,
     ImageDJV,
     ImageSVG,
+    ImageAVIF,
+    ImageBMP,
+    ImageAPNG,
     VideoMPEG,
     VideoMP4,
     VideoQuicktime,
     VideoWMV,
     VideoFLV,
     VideoWEBM,
+    VideoOGG,
+    AudioMPEG,
+    AudioOGG,
+    AudioWAV,
+    AudioFLAC,
+    AudioAAC,
+    AudioMP4,
+    AudioOPUS,
+    AudioWEBM,
+    FontWOFF,
+    FontWOFF2,
+    FontTTF,
+    FontOTF,
+    FontEOT,
+    ApplicationJSON,
+    ApplicationManifest,
+    ApplicationPDF,
+    ApplicationWASM,
+    ApplicationZIP,
+    ApplicationGZIP,
+    ApplicationRSS,
+    ApplicationAtom,
+    /// A user mapped type, see [register_mime_types]
+    Custom(usize),
     Unknown
 }
 
-const IS_HTML: [bool; 21] = 
+/// Built-in extension (lowercase) to [MIME] mappings
+const EXTENSIONS: [(&str, MIME); 65] =
     [
-        false, true, false, false, false, false, false, false, false, false, false, 
-        false, false, false, false, false, false, false, false, false, false
+        ("txt", MIME::TextPlain),
+        ("html", MIME::TextHtml),
+        ("htm", MIME::TextHtml),
+        ("css", MIME::TextCSS),
+        ("csv", MIME::TextCSV),
+        ("js", MIME::TextJavascript),
+        ("mjs", MIME::TextJavascript),
+        ("javascript", MIME::TextJavascript),
+        ("xml", MIME::TextXML),
+        ("md", MIME::TextMarkdown),
+        ("markdown", MIME::TextMarkdown),
+        ("gif", MIME::ImageGIF),
+        ("png", MIME::ImagePNG),
+        ("jpg", MIME::ImageJPEG),
+        ("jpeg", MIME::ImageJPEG),
+        ("webp", MIME::ImageWEBP),
+        ("tiff", MIME::ImageTIFF),
+        ("tif", MIME::ImageTIFF),
+        ("ico", MIME::ImageXICON),
+        ("djvu", MIME::ImageDJV),
+        ("djv", MIME::ImageDJV),
+        ("svg", MIME::ImageSVG),
+        ("avif", MIME::ImageAVIF),
+        ("bmp", MIME::ImageBMP),
+        ("apng", MIME::ImageAPNG),
+        ("mpeg", MIME::VideoMPEG),
+        ("mpg", MIME::VideoMPEG),
+        ("mp2", MIME::VideoMPEG),
+        ("mpe", MIME::VideoMPEG),
+        ("mpv", MIME::VideoMPEG),
+        ("m2v", MIME::VideoMPEG),
+        ("mp4", MIME::VideoMP4),
+        ("m4v", MIME::VideoMP4),
+        ("qt", MIME::VideoQuicktime),
+        ("mov", MIME::VideoQuicktime),
+        ("wmv", MIME::VideoWMV),
+        ("flv", MIME::VideoFLV),
+        ("f4v", MIME::VideoFLV),
+        ("f4p", MIME::VideoFLV),
+        ("f4a", MIME::VideoFLV),
+        ("f4b", MIME::VideoFLV),
+        ("webm", MIME::VideoWEBM),
+        ("ogv", MIME::VideoOGG),
+        ("mp3", MIME::AudioMPEG),
+        ("ogg", MIME::AudioOGG),
+        ("oga", MIME::AudioOGG),
+        ("wav", MIME::AudioWAV),
+        ("flac", MIME::AudioFLAC),
+        ("aac", MIME::AudioAAC),
+        ("m4a", MIME::AudioMP4),
+        ("opus", MIME::AudioOPUS),
+        ("weba", MIME::AudioWEBM),
+        ("woff", MIME::FontWOFF),
+        ("woff2", MIME::FontWOFF2),
+        ("ttf", MIME::FontTTF),
+        ("otf", MIME::FontOTF),
+        ("eot", MIME::FontEOT),
+        ("json", MIME::ApplicationJSON),
+        ("webmanifest", MIME::ApplicationManifest),
+        ("pdf", MIME::ApplicationPDF),
+        ("wasm", MIME::ApplicationWASM),
+        ("zip", MIME::ApplicationZIP),
+        ("gz", MIME::ApplicationGZIP),
+        ("rss", MIME::ApplicationRSS),
+        ("atom", MIME::ApplicationAtom)
     ];
 
-const IS_TEXT: [bool; 21] = 
+/// All built-in types, for lookup by string form
+const BUILT_IN: [MIME; 47] =
     [
-        true, true, true, true, true, true, false, false, false, false, false, 
-        false, false, false, false, false, false, false, false, false, false
+        MIME::TextPlain, MIME::TextHtml, MIME::TextCSS, MIME::TextCSV, MIME::TextJavascript, MIME::TextXML,
+        MIME::TextMarkdown, MIME::ImageGIF, MIME::ImagePNG, MIME::ImageJPEG, MIME::ImageWEBP, MIME::ImageTIFF,
+        MIME::ImageXICON, MIME::ImageDJV, MIME::ImageSVG, MIME::ImageAVIF, MIME::ImageBMP, MIME::ImageAPNG,
+        MIME::VideoMPEG, MIME::VideoMP4, MIME::VideoQuicktime, MIME::VideoWMV, MIME::VideoFLV, MIME::VideoWEBM,
+        MIME::VideoOGG, MIME::AudioMPEG, MIME::AudioOGG, MIME::AudioWAV, MIME::AudioFLAC, MIME::AudioAAC,
+        MIME::AudioMP4, MIME::AudioOPUS, MIME::AudioWEBM, MIME::FontWOFF, MIME::FontWOFF2, MIME::FontTTF,
+        MIME::FontOTF, MIME::FontEOT, MIME::ApplicationJSON, MIME::ApplicationManifest, MIME::ApplicationPDF,
+        MIME::ApplicationWASM, MIME::ApplicationZIP, MIME::ApplicationGZIP, MIME::ApplicationRSS,
+        MIME::ApplicationAtom, MIME::Unknown
     ];
 
-const IS_IMAGE: [bool; 21] = 
-    [
-        false, false, false, false, false, false, true, true, true, true, true, 
-        true, true, true, false, false, false, false, false, false, false
-    ];
+/// Extension to [MIME] lookup, compiled once, plus user types for [MIME::Custom]
+struct MimeRegistry
+{
+    extensions: HashMap<String, MIME>,
+    custom: Vec<&'static str>
+}
 
-const IS_VIDEO: [bool; 21] = 
-    [
-        false, false, false, false, false, false, false, false, false, false, 
-        false, false, false, false, true, true, true, true, true, true, false
-    ];
+impl MimeRegistry
+{
+    fn built_in() -> HashMap<String, MIME>
+    {
+        EXTENSIONS.iter().map(|(e, m)| (e.to_string(), *m)).collect()
+    }
+}
 
-const STR_FORM: [&'static str; 21] = 
-    [
-        "text/plain", "text/html", "text/css", "text/csv", "text/javascript", "text/xml", 
-        "image/gif", "image/png", "image/jpeg", "image/webp", "image/tiff", "image/x-icon",
-        "image/vnd.djvu", "image/svg+xml", "video/mpeg", "video/mp4", "video/quicktime",
-        "video/x-ms-wmv", "video/x-flv", "video/webm", "application/octet-stream"
-    ];
-pub trait Mime 
+fn registry() -> &'static RwLock<MimeRegistry>
+{
+    static REGISTRY: OnceLock<RwLock<MimeRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(MimeRegistry { extensions: MimeRegistry::built_in(), custom: vec![] }))
+}
+
+/// Map extensions (without the dot) to MIME type strings, e.g. ```"glb": "model/gltf-binary"```,
+///   replacing any previously registered user mappings. User mappings take precedence over
+///   built-in ones. See [crate::config::ContentConfig::mime_types]
+pub fn register_mime_types(mappings: &HashMap<String, String>)
+{
+    let mut registry = registry().write().unwrap();
+    registry.extensions = MimeRegistry::built_in();
+
+    for (extension, mime) in mappings
+    {
+        let mime = mime.trim().to_lowercase();
+        let mime_type = match BUILT_IN.iter().find(|m| m.as_str() == mime)
+        {
+            Some(m) => *m,
+            None =>
+            {
+                match registry.custom.iter().position(|c| *c == mime)
+                {
+                    Some(i) => MIME::Custom(i),
+                    None =>
+                    {
+                        // each distinct user type is stored once for the program's lifetime
+                        registry.custom.push(Box::leak(mime.into_boxed_str()));
+                        MIME::Custom(registry.custom.len()-1)
+                    }
+                }
+            }
+        };
+        registry.extensions.insert(extension.trim_start_matches('.').to_lowercase(), mime_type);
+    }
+}
+
+pub trait Mime
 {
     fn is_html(&self) -> bool {false}
     fn is_text(&self) -> bool {false}
     fn is_image(&self) -> bool {false}
     fn is_video(&self) -> bool {false}
-    
+    fn is_audio(&self) -> bool {false}
+
     fn in_sitemap(&self) -> bool { self.is_html() || self.is_image() || self.is_video() }
 
     fn as_str(&self) -> &'static str {"application/octet-stream"}
-    fn infer_mime_type(extension: &str) -> MIME;
+    /// The content-type header value, with a utf-8 charset for text types
+    fn header_value(&self) -> String
+    {
+        if self.is_text() { format!("{}; charset=utf-8", self.as_str()) } else { self.as_str().to_string() }
+    }
+    fn infer_mime_type(path: &str) -> MIME;
 }
 
 impl Mime for MIME
 {
-    fn is_html(&self) -> bool {IS_HTML[self.clone() as usize]}
-    fn is_text(&self) -> bool {IS_TEXT[self.clone() as usize]}
-    fn is_image(&self) -> bool {IS_IMAGE[self.clone() as usize]}
-    fn is_video(&self) -> bool {IS_VIDEO[self.clone() as usize]}
-    fn as_str(&self) -> &'static str { STR_FORM[self.clone() as usize] }
+    fn is_html(&self) -> bool { self.as_str() == "text/html" }
 
-    fn infer_mime_type(extension: &str) -> MIME
+    fn is_text(&self) -> bool
     {
-        let content_types = HashMap::from
-        ( 
-            [
-                (r"\.txt$", MIME::TextPlain),
-                (r"\.html$", MIME::TextHtml),
-                (r"\.css$", MIME::TextCSS),
-                (r"\.csv$", MIME::TextCSV),
-                (r"\.(javascript|js)$", MIME::TextJavascript),
-                (r"\.xml$", MIME::TextXML),
-                (r"\.gif$", MIME::ImageGIF), 
-                (r"\.webp$", MIME::ImageWEBP),   
-                (r"\.(jpg|jpeg)$", MIME::ImageJPEG),   
-                (r"\.png$", MIME::ImagePNG),   
-                (r"\.tiff$", MIME::ImageTIFF),      
-                (r"\.ico$", MIME::ImageXICON),  
-                (r"\.(djvu)|(djv)$", MIME::ImageDJV),  
-                (r"\.svg$", MIME::ImageSVG),
-                (r"\.(mpeg|mpg|mp2|mpe|mpv|m2v)$", MIME::VideoMPEG),    
-                (r"\.(mp4|m4v)$", MIME::VideoMP4),    
-                (r"\.(qt|mov)$", MIME::VideoQuicktime),    
-                (r"\.(wmv)$", MIME::VideoWMV),    
-                (r"\.(flv|f4v|f4p|f4a|f4b)$", MIME::VideoFLV),   
-                (r"\.webm$", MIME::VideoWEBM)    
-            ]
-        );
-
-        for (re, content) in content_types
+        let s = self.as_str();
+        s.starts_with("text/") || s == "application/json" || s.ends_with("+json") ||
+            s == "application/xml" || (s.ends_with("+xml") && !self.is_image())
+    }
+
+    fn is_image(&self) -> bool { self.as_str().starts_with("image/") }
+    fn is_video(&self) -> bool { self.as_str().starts_with("video/") }
+    fn is_audio(&self) -> bool { self.as_str().starts_with("audio/") }
+
+    fn as_str(&self) -> &'static str
+    {
+        match self
         {
-            if Regex::new(re).unwrap().is_match(&extension)
+            MIME::TextPlain => "text/plain",
+            MIME::TextHtml => "text/html",
+            MIME::TextCSS => "text/css",
+            MIME::TextCSV => "text/csv",
+            MIME::TextJavascript => "text/javascript",
+            MIME::TextXML => "text/xml",
+            MIME::TextMarkdown => "text/markdown",
+            MIME::ImageGIF => "image/gif",
+            MIME::ImagePNG => "image/png",
+            MIME::ImageJPEG => "image/jpeg",
+            MIME::ImageWEBP => "image/webp",
+            MIME::ImageTIFF => "image/tiff",
+            MIME::ImageXICON => "image/x-icon",
+            MIME::ImageDJV => "image/vnd.djvu",
+            MIME::ImageSVG => "image/svg+xml",
+            MIME::ImageAVIF => "image/avif",
+            MIME::ImageBMP => "image/bmp",
+            MIME::ImageAPNG => "image/apng",
+            MIME::VideoMPEG => "video/mpeg",
+            MIME::VideoMP4 => "video/mp4",
+            MIME::VideoQuicktime => "video/quicktime",
+            MIME::VideoWMV => "video/x-ms-wmv",
+            MIME::VideoFLV => "video/x-flv",
+            MIME::VideoWEBM => "video/webm",
+            MIME::VideoOGG => "video/ogg",
+            MIME::AudioMPEG => "audio/mpeg",
+            MIME::AudioOGG => "audio/ogg",
+            MIME::AudioWAV => "audio/wav",
+            MIME::AudioFLAC => "audio/flac",
+            MIME::AudioAAC => "audio/aac",
+            MIME::AudioMP4 => "audio/mp4",
+            MIME::AudioOPUS => "audio/opus",
+            MIME::AudioWEBM => "audio/webm",
+            MIME::FontWOFF => "font/woff",
+            MIME::FontWOFF2 => "font/woff2",
+            MIME::FontTTF => "font/ttf",
+            MIME::FontOTF => "font/otf",
+            MIME::FontEOT => "application/vnd.ms-fontobject",
+            MIME::ApplicationJSON => "application/json",
+            MIME::ApplicationManifest => "application/manifest+json",
+            MIME::ApplicationPDF => "application/pdf",
+            MIME::ApplicationWASM => "application/wasm",
+            MIME::ApplicationZIP => "application/zip",
+            MIME::ApplicationGZIP => "application/gzip",
+            MIME::ApplicationRSS => "application/rss+xml",
+            MIME::ApplicationAtom => "application/atom+xml",
+            MIME::Custom(i) =>
             {
-                return content
-            }
+                match registry().read().unwrap().custom.get(*i)
+                {
+                    Some(s) => s,
+                    None => "application/octet-stream"
+                }
+            },
+            MIME::Unknown => "application/octet-stream"
         }
+    }
 
-        MIME::Unknown
+    /// Look up the (lowercased) extension of path
+    fn infer_mime_type(path: &str) -> MIME
+    {
+        let extension = match Path::new(path).extension().and_then(|e| e.to_str())
+        {
+            Some(e) => e.to_lowercase(),
+            None => return MIME::Unknown
+        };
+
+        match registry().read().unwrap().extensions.get(&extension)
+        {
+            Some(m) => *m,
+            None => MIME::Unknown
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,23 +1,48 @@
 use std::cmp::min;
 use std::time::SystemTime;
 
+use axum::body::Body;
+use git2::Oid;
 use axum::response::{Html, IntoResponse, Response};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
+use crate::config::SnippetConfig;
 use crate::filesystem::file::{file_hash, File, Observed};
 use crate::filesystem::file::{read_file_bytes, read_file_utf8, write_file_bytes, FileError};
 use crate::filesystem::folder::{list_dir_by, list_sub_dirs};
+use crate::integrations::git::objects::GitSource;
 use crate::program_version;
 use crate::util::{dump_bytes, hash};
 
+use self::cache::SharedContentCache;
+use self::dependents::SharedDependents;
+use self::encoding::{preferred_encoding, sidecar_of, Encoding};
 use self::filter::ContentFilter;
+use self::markdown::{is_markdown, render_page, MarkdownOptions};
 use self::mime_type::{Mime, MIME};
+use self::snippet::{applies_to, inject, inject_after_doctype, snippet_html};
+use self::template::{expand, hash_dependencies, same_path, IncludeOptions};
 
 pub mod mime_type;
 pub mod filter;
 pub mod sitemap;
 pub mod error_page;
+pub mod cache;
+pub mod dependents;
+pub mod page_cache;
+pub mod watcher;
+pub mod markdown;
+pub mod template;
+pub mod snippet;
+pub mod encoding;
+pub mod negotiate;
+pub mod feed;
+pub mod media;
+pub mod robots;
+pub mod generated;
+pub mod search;
+pub mod links;
 
 /// Store web content
 ///
@@ -25,6 +50,14 @@ pub mod error_page;
 /// - The body may be converted to a utf8 string using [Content::utf8_body]
 /// - A hash of the file is used to check it is stale, used by [Observed]
 /// - Content may have different server side and browser side cache ages
+/// - With a [cache::ContentCache] the body is held there, within a memory budget, see [Content::set_cache]
+/// - Files larger than a threshold are streamed from disk, see [Content::set_stream_threshold]
+/// - Markdown files may be rendered to html when loaded, see [Content::set_markdown]
+/// - Html may have server side includes expanded when loaded, see [Content::set_server_side_includes],
+///   and snippets injected, see [Content::set_snippets].
+/// - Precompressed sidecars (app.js.br for app.js) are served when accepted, see [Content::negotiate_encoding]
+///   Rendered content is stale if any file it depends on changes
+/// - Content may be read from git objects instead of disk, see [Content::set_git_source]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Content
 {
@@ -36,7 +69,24 @@ pub struct Content
     server_cache_period_seconds: u16,
     hash: Vec<u8>,
     last_refreshed: SystemTime,
-    tag_insertion: bool
+    tag_insertion: bool,
+    #[serde(skip)]
+    cache: Option<SharedContentCache>,
+    stream_threshold_bytes: Option<u64>,
+    streamed: bool,
+    markdown: Option<MarkdownOptions>,
+    draft: bool,
+    includes: Option<IncludeOptions>,
+    snippets: Vec<SnippetConfig>,
+    sidecars: Vec<(Encoding, String)>,
+    dependencies: Vec<String>,
+    dependencies_hash: Vec<u8>,
+    #[serde(skip)]
+    dependents: Option<SharedDependents>,
+    #[serde(skip)]
+    git: Option<GitSource>,
+    #[serde(skip)]
+    loaded_commit: Option<Oid>
 }
 
 pub trait HasUir
@@ -65,12 +115,20 @@ impl File for Content
 
     fn read_bytes(&self) -> Option<Vec<u8>>
     {
-        read_file_bytes(&self.disk_path)
+        match self.git
+        {
+            Some(ref git) => git.read(&git.tree_path(&self.disk_path)),
+            None => read_file_bytes(&self.disk_path)
+        }
     }
 
     fn read_utf8(&self) -> Option<String>
     {
-        read_file_utf8(&self.disk_path)
+        match self.git
+        {
+            Some(_) => self.read_bytes().and_then(|b| String::from_utf8(b).ok()),
+            None => read_file_utf8(&self.disk_path)
+        }
     }
 
     fn path(&self) -> String { self.disk_path.clone() }
@@ -85,7 +143,9 @@ impl Observed for Content
         //  very soon after creation/modification, plus may
         //  not be guaranteed cross platform, this is.
         //  We can check 100,000 files in 447 millis
-        return file_hash(&self.disk_path) != self.hash
+        if self.git.is_some() { return self.source_changed() }
+        file_hash(&self.disk_path) != self.hash ||
+            (!self.dependencies.is_empty() && hash_dependencies(&self.dependencies) != self.dependencies_hash)
     }
 
     fn refresh(&mut self)
@@ -125,8 +185,261 @@ impl Content
             browser_cache_period_seconds: browser_cache,
             hash: vec![],
             last_refreshed: SystemTime::UNIX_EPOCH,
-            tag_insertion
+            tag_insertion,
+            cache: None,
+            stream_threshold_bytes: None,
+            streamed: false,
+            markdown: None,
+            draft: false,
+            includes: None,
+            snippets: vec![],
+            sidecars: vec![],
+            dependencies: vec![],
+            dependencies_hash: vec![],
+            dependents: None,
+            git: None,
+            loaded_commit: None
+        }
+    }
+
+    /// Read the body from the blob at the disk path (within the repository) in
+    ///   the [GitSource]'s served commit, instead of from disk
+    pub fn set_git_source(&mut self, git: Option<GitSource>)
+    {
+        self.git = git;
+    }
+
+    /// If the [GitSource]'s served commit changed since the body was loaded
+    pub fn source_changed(&self) -> bool
+    {
+        self.git.as_ref().is_some_and(|git| self.loaded_commit != Some(git.commit()))
+    }
+
+    /// If the content is read from git objects but the served commit does not have it
+    pub fn is_absent(&self) -> bool
+    {
+        self.git.is_some() && self.loaded_commit.is_some() && self.hash.is_empty()
+    }
+
+    /// Record [Content::dependencies] in a shared index as they change when loaded
+    pub fn set_dependents(&mut self, dependents: Option<SharedDependents>)
+    {
+        self.dependents = dependents;
+    }
+
+    /// Hold the body in a shared, memory bounded, [cache::ContentCache]
+    ///   instead of in this [Content]
+    pub fn set_cache(&mut self, cache: Option<SharedContentCache>)
+    {
+        self.cache = cache;
+    }
+
+    /// Files larger than threshold bytes are never held in memory,
+    ///   they are hashed and served in chunks from disk
+    pub fn set_stream_threshold(&mut self, threshold: Option<u64>)
+    {
+        self.stream_threshold_bytes = threshold;
+    }
+
+    /// If the body is served from disk, see [Content::set_stream_threshold]
+    pub fn is_streamed(&self) -> bool
+    {
+        self.streamed
+    }
+
+    /// Render a Markdown file to a html page when loaded, see [markdown::render_page].
+    ///   Has no effect if the disk path is not Markdown
+    pub fn set_markdown(&mut self, options: Option<MarkdownOptions>)
+    {
+        if !is_markdown(&self.disk_path) { return }
+        self.content_type = match options
+        {
+            Some(_) => MIME::TextHtml,
+            None => <MIME as Mime>::infer_mime_type(&self.disk_path)
+        };
+        self.markdown = options;
+    }
+
+    /// If the content is rendered from Markdown, see [Content::set_markdown]
+    pub fn is_markdown(&self) -> bool
+    {
+        self.markdown.is_some()
+    }
+
+    /// If rendered Markdown's [markdown::FrontMatter] marks it as a draft, drafts are not served
+    pub fn is_draft(&self) -> bool
+    {
+        self.draft
+    }
+
+    /// Expand server side includes and variables in html when loaded, see [template::IncludeOptions].
+    ///   Has no effect if the content is not html
+    pub fn set_server_side_includes(&mut self, options: Option<IncludeOptions>)
+    {
+        self.includes = if self.content_type.is_html() { options } else { None };
+    }
+
+    /// Inject the snippets that apply to this content's uri when loaded. Has no effect if the content is not html
+    pub fn set_snippets(&mut self, snippets: &[SnippetConfig])
+    {
+        self.snippets = if self.content_type.is_html()
+        {
+            snippets.iter().filter(|s| applies_to(s, &self.uri)).cloned().collect()
+        }
+        else
+        {
+            vec![]
+        };
+    }
+
+    /// Serve a precompressed copy of this content at path, when the [Encoding] is accepted
+    pub fn add_sidecar(&mut self, encoding: Encoding, path: &str)
+    {
+        self.sidecars.retain(|(e, _)| *e != encoding);
+        self.sidecars.push((encoding, path.to_string()));
+    }
+
+    /// Stop serving the sidecar at path, returns true if it was present
+    pub fn remove_sidecar(&mut self, path: &str) -> bool
+    {
+        let n = self.sidecars.len();
+        self.sidecars.retain(|(_, p)| p != path);
+        n != self.sidecars.len()
+    }
+
+    /// Available precompressed [Encoding]s
+    pub fn sidecars(&self) -> Vec<Encoding>
+    {
+        self.sidecars.iter().map(|(e, _)| *e).collect()
+    }
+
+    /// The sidecar [Encoding] to serve for an Accept-Encoding header, if any.
+    ///   Sidecars are only served when the body would be served verbatim,
+    ///   i.e. not rendered and without a [insert_tag]
+    pub fn negotiate_encoding(&self, accept_encoding: Option<&str>) -> Option<Encoding>
+    {
+        if self.sidecars.is_empty() || self.is_rendered() || (self.tag_insertion && self.content_type == MIME::TextHtml)
+        {
+            return None
+        }
+        accept_encoding.and_then(|accept| preferred_encoding(accept, &self.sidecars()))
+    }
+
+    /// Respond with the precompressed sidecar for encoding, with the original's content-type
+    pub fn encoded_response(&self, encoding: Encoding) -> Response
+    {
+        let path = match self.sidecars.iter().find(|(e, _)| *e == encoding)
+        {
+            Some((_, path)) => path,
+            None => return self.clone().into_response()
+        };
+
+        let mut response = match std::fs::File::open(path)
+        {
+            Ok(file) =>
+            {
+                let stream = tokio_util::io::ReaderStream::new(tokio::fs::File::from_std(file));
+                Body::from_stream(stream).into_response()
+            },
+            Err(e) =>
+            {
+                crate::debug(format!("Error {} streaming {}, serving {}", e, path, self.disk_path), None);
+                return self.clone().into_response()
+            }
+        };
+
+        self.insert_headers(&mut response);
+        response.headers_mut()
+            .insert("content-encoding", encoding.as_str().parse().unwrap());
+        response
+    }
+
+    fn insert_headers(&self, response: &mut Response)
+    {
+        response.headers_mut()
+            .insert("content-type", self.content_type.header_value().parse().unwrap());
+
+        let time_stamp = chrono::offset::Utc::now().to_rfc3339();
+        response.headers_mut()
+            .insert("date", time_stamp.parse().unwrap());
+
+        response.headers_mut()
+            .insert("cache-control", format!("public, max-age={}", self.browser_cache_period_seconds).parse().unwrap());
+
+        if !self.sidecars.is_empty()
+        {
+            response.headers_mut()
+                .insert("vary", "accept-encoding".parse().unwrap());
+        }
+    }
+
+    /// Disk paths the (rendered) body depends on, e.g. included partials and layouts
+    pub fn dependencies(&self) -> &[String]
+    {
+        &self.dependencies
+    }
+
+    /// If path is one of [Content::dependencies]
+    pub fn depends_on(&self, path: &str) -> bool
+    {
+        self.dependencies.iter().any(|d| same_path(d, path))
+    }
+
+    fn is_rendered(&self) -> bool
+    {
+        self.markdown.is_some() || self.includes.is_some() || !self.snippets.is_empty()
+    }
+
+    /// Rendered content is cached per uri, since variables may depend on it
+    fn cache_key(&self) -> String
+    {
+        if self.is_rendered() { format!("{}#{}", self.disk_path, self.uri) } else { self.disk_path.clone() }
+    }
+
+    /// Render Markdown, expand includes and inject snippets, returns the body,
+    ///   if it is a draft, and its dependencies. Snippets are injected after includes
+    ///   are expanded, so a ```<head>``` or ```</body>``` from a partial is found
+    fn render(&self) -> Result<(Vec<u8>, bool, Vec<String>), FileError>
+    {
+        let (mut html, draft, mut dependencies) = match self.markdown
+        {
+            Some(ref options) =>
+            {
+                let page = render_page(&self.disk_path, options)?;
+                (page.html, page.front_matter.is_draft(), page.dependencies)
+            },
+            None => match self.read_utf8()
+            {
+                Some(html) => (html, false, vec![]),
+                None => return Err(FileError { why: format!("Could not read utf8 from {}", self.disk_path) })
+            }
+        };
+
+        if let Some(ref options) = self.includes
+        {
+            let expanded = expand(&html, &self.uri, &self.disk_path, options);
+            for dependency in expanded.dependencies
+            {
+                if !dependencies.contains(&dependency) { dependencies.push(dependency); }
+            }
+            html = expanded.html;
         }
+
+        for snippet in &self.snippets
+        {
+            if let Some(ref file) = snippet.file
+            {
+                if !dependencies.contains(file) { dependencies.push(file.clone()); }
+            }
+
+            match snippet_html(snippet)
+            {
+                Some(snippet_html) => html = inject(&html, snippet.position, &snippet_html),
+                None => crate::debug(format!("Could not read snippet {:?} for {}", snippet.file, self.uri), None)
+            }
+        }
+
+        Ok((html.into_bytes(), draft, dependencies))
     }
 
     pub fn server_cache_expired(&self) -> bool
@@ -144,18 +457,64 @@ impl Content
 
     pub fn load_from_file(&mut self) -> Result<(), FileError>
     {
+        self.loaded_commit = self.git.as_ref().map(|git| git.commit());
+
+        if self.is_rendered()
+        {
+            self.last_refreshed = SystemTime::now();
+            let (body, draft, dependencies) = match self.render()
+            {
+                Ok(rendered) => rendered,
+                Err(e) => { if self.git.is_some() { self.hash.clear(); } return Err(e) }
+            };
+            self.hash = if self.git.is_some() { hash(body.clone()) } else { file_hash(&self.disk_path) };
+            self.draft = draft;
+            self.dependencies_hash = hash_dependencies(&dependencies);
+            if let Some(ref dependents) = self.dependents
+            {
+                dependents.lock().unwrap().update(&self.uri, &self.dependencies, &dependencies);
+            }
+            self.dependencies = dependencies;
+            match self.cache
+            {
+                Some(ref cache) => { cache.lock().unwrap().insert(&self.cache_key(), body); },
+                None => { self.body = body; }
+            }
+            return Ok(())
+        }
+
+        if let Some(threshold) = self.stream_threshold_bytes.filter(|_| self.git.is_none())
+        {
+            if std::fs::metadata(&self.disk_path).is_ok_and(|m| m.len() > threshold)
+            {
+                self.streamed = true;
+                self.body = vec![];
+                if let Some(ref cache) = self.cache { cache.lock().unwrap().remove(&self.disk_path); }
+                self.hash = file_hash(&self.disk_path);
+                self.last_refreshed = SystemTime::now();
+                crate::debug(format!("Streaming {} from disk", self.disk_path), Some("CACHE"));
+                return Ok(())
+            }
+        }
+        self.streamed = false;
+
         match self.read_bytes()
         {
             Some(data) =>
             {
-                self.body = data.clone();
-                self.hash = hash(data);
+                self.hash = hash(data.clone());
+                match self.cache
+                {
+                    Some(ref cache) => { cache.lock().unwrap().insert(&self.disk_path, data); },
+                    None => { self.body = data; }
+                }
                 self.last_refreshed = SystemTime::now();
                 Ok(())
             }
             None =>
             {
                 self.last_refreshed = SystemTime::now();
+                if self.git.is_some() { self.hash.clear(); }
                 Err(FileError { why: format!("Could not read bytes from {}", self.disk_path)})
             }
         }
@@ -163,12 +522,49 @@ impl Content
 
     pub fn utf8_body(&self) -> Result<String, std::string::FromUtf8Error>
     {
-        String::from_utf8(self.body.clone())
+        String::from_utf8(self.byte_body())
     }
 
+    /// The body, from the [cache::ContentCache] if set (re-read from
+    ///   disk if it was evicted). Empty if the content is streamed
     pub fn byte_body(&self) -> Vec<u8>
     {
-        self.body.clone()
+        if self.streamed
+        {
+            return vec![]
+        }
+
+        match self.cache
+        {
+            Some(ref cache) =>
+            {
+                if self.hash.is_empty()
+                {
+                    return vec![]
+                }
+
+                if let Some(body) = cache.lock().unwrap().get(&self.cache_key())
+                {
+                    return body.to_vec()
+                }
+
+                let data = if self.is_rendered()
+                {
+                    self.render().ok().map(|(body, _, _)| body)
+                }
+                else
+                {
+                    self.read_bytes()
+                };
+
+                match data
+                {
+                    Some(data) => cache.lock().unwrap().insert(&self.cache_key(), data).to_vec(),
+                    None => vec![]
+                }
+            },
+            None => self.body.clone()
+        }
     }
 
     pub fn get_content_type(&self) -> MIME
@@ -183,25 +579,42 @@ impl Content
             Ok(s) => s[0..min(s.len(), n)].to_string(),
             Err(_e) =>
             {
-                dump_bytes(&self.body)[0..min(self.body.len(), n)].to_string()
+                let body = self.byte_body();
+                dump_bytes(&body)[0..min(body.len(), n)].to_string()
             }
         };
         format!("uri: {}, body: {} ...", self.get_uri(), preview_body)
     }
 }
 
-/// Insert a tag indicating the page was served by busser
+/// Insert a tag indicating the page was served by busser, after any doctype
 /// this may be disabled by launching as busser --no-tagging
 pub fn insert_tag(body: String)
  -> String
 {
-    format!("<!--Hosted by Busser {}, https://github.com/JerboaBurrow/Busser-->\n{}", program_version(), body)
+    inject_after_doctype(&body, &format!("<!--Hosted by Busser {}, https://github.com/JerboaBurrow/Busser-->\n", program_version()))
 }
 
 impl IntoResponse for Content {
     fn into_response(self) -> Response {
 
-        let mut response = if self.content_type == MIME::TextHtml
+        let mut response = if self.streamed
+        {
+            match std::fs::File::open(&self.disk_path)
+            {
+                Ok(file) =>
+                {
+                    let stream = tokio_util::io::ReaderStream::new(tokio::fs::File::from_std(file));
+                    Body::from_stream(stream).into_response()
+                },
+                Err(e) =>
+                {
+                    crate::debug(format!("Error {} streaming {}", e, self.disk_path), None);
+                    return axum::http::StatusCode::NOT_FOUND.into_response()
+                }
+            }
+        }
+        else if self.content_type == MIME::TextHtml
         {
             let mut string_body = match self.utf8_body()
             {
@@ -218,19 +631,10 @@ impl IntoResponse for Content {
         }
         else
         {
-            Html(self.body).into_response()
+            Html(self.byte_body()).into_response()
         };
 
-        response.headers_mut()
-            .insert("content-type", self.content_type.as_str().parse().unwrap());
-
-        let time_stamp = chrono::offset::Utc::now().to_rfc3339();
-        response.headers_mut()
-            .insert("date", time_stamp.parse().unwrap());
-
-        response.headers_mut()
-            .insert("cache-control", format!("public, max-age={}", self.browser_cache_period_seconds).parse().unwrap());
-
+        self.insert_headers(&mut response);
         response
     }
 }
@@ -253,6 +657,45 @@ pub fn is_page(uri: &str, domain: &str) -> bool
     }
 }
 
+/// Content for the files in a [GitSource]'s served commit, with disk paths
+///   under root (the repository), see [get_content]
+pub fn get_git_content
+(
+    source: &GitSource,
+    root: &str,
+    server_cache_period_seconds: Option<u16>,
+    browser_cache_period_seconds: Option<u16>,
+    tagging: Option<bool>,
+    filter: Option<&ContentFilter>
+) -> Vec<Content>
+{
+    let mut content_paths: Vec<String> = match source.files()
+    {
+        Ok(files) => files.into_iter().map(|f| format!("{}/{}", root.trim_end_matches('/'), f)).collect(),
+        Err(e) =>
+        {
+            crate::debug(format!("Error {} listing files in git objects at {}", e, root), Some("GIT"));
+            vec![]
+        }
+    };
+
+    if let Some(filter) = filter
+    {
+        content_paths = filter.filter_uris(content_paths);
+    }
+
+    content_paths.iter()
+        .map(|path| Content::new
+        (
+            &path.replacen(root.trim_end_matches('/'), "", 1),
+            path,
+            server_cache_period_seconds.unwrap_or(60),
+            browser_cache_period_seconds.unwrap_or(3600),
+            tagging.unwrap_or(false)
+        ))
+        .collect()
+}
+
 pub fn get_content
 (
     root: &str,
@@ -290,10 +733,27 @@ pub fn get_content
         None => 60
     };
 
-    for content_path in content_paths
+    let mut sidecars = vec![];
+    for content_path in &content_paths
     {
+        if let Some((original, encoding)) = sidecar_of(content_path)
+        {
+            if content_paths.contains(&original)
+            {
+                sidecars.push((original, encoding, content_path.clone()));
+                continue
+            }
+        }
         let uri = content_path.clone().replace(root,"");
-        contents.push(Content::new(&uri, &content_path, server_cache, browser_cache, tag));
+        contents.push(Content::new(&uri, content_path, server_cache, browser_cache, tag));
+    }
+
+    for (original, encoding, sidecar) in sidecars
+    {
+        if let Some(content) = contents.iter_mut().find(|c| c.disk_path == original)
+        {
+            content.add_sidecar(encoding, &sidecar);
+        }
     }
 
     let mut dirs = list_sub_dirs(path.to_string());
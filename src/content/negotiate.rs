@@ -0,0 +1,236 @@
+use std::{collections::BTreeMap, sync::{Arc, OnceLock}};
+
+use axum::http::HeaderMap;
+use regex::Regex;
+use tokio::sync::Mutex;
+
+use crate::config::NegotiationConfig;
+
+use super::{mime_type::{Mime, MIME}, Content};
+
+/// Image formats negotiated for jpeg, png and gif images, in order of preference
+pub const IMAGE_VARIANTS: [MIME; 2] = [MIME::ImageAVIF, MIME::ImageWEBP];
+
+/// How a [Variant] differs from the content at its group's uri
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariantKind
+{
+    /// e.g. photo.avif for photo.jpg
+    Image(MIME),
+    /// e.g. page.fr.html for /page, lowercase
+    Language(String)
+}
+
+/// A negotiable alternative of the content at a uri
+#[derive(Clone)]
+pub struct Variant
+{
+    pub kind: VariantKind,
+    pub uri: String,
+    pub content: Arc<Mutex<Content>>
+}
+
+/// Variant groups by uri, and how to choose between them, see [NegotiationConfig]
+#[derive(Clone)]
+pub struct Negotiation
+{
+    pub config: NegotiationConfig,
+    pub groups: BTreeMap<String, Vec<Variant>>
+}
+
+/// A negotiated response, a variant (or None for the uri's own content) and the Vary header value
+pub struct Selection
+{
+    pub content: Option<Arc<Mutex<Content>>>,
+    pub vary: String
+}
+
+impl Negotiation
+{
+    /// Choose the content to serve at uri for the request headers, None if uri has no variants
+    pub fn select(&self, uri: &str, headers: &HeaderMap) -> Option<Selection>
+    {
+        let variants = self.groups.get(uri)?;
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+
+        match variants.first().map(|v| &v.kind)
+        {
+            Some(VariantKind::Image(_)) =>
+            {
+                let available: Vec<MIME> = variants.iter().filter_map(|v| match v.kind { VariantKind::Image(m) => Some(m), _ => None }).collect();
+                let content = preferred_image(&header("accept"), &available)
+                    .and_then(|m| variants.iter().find(|v| v.kind == VariantKind::Image(m)))
+                    .map(|v| v.content.clone());
+                Some(Selection { content, vary: "accept".to_string() })
+            },
+            Some(VariantKind::Language(_)) =>
+            {
+                let available: Vec<String> = variants.iter().filter_map(|v| match v.kind { VariantKind::Language(ref l) => Some(l.clone()), _ => None }).collect();
+                let cookie = self.config.language_cookie.as_ref().and_then(|name| cookie_value(&header("cookie"), name));
+                let language = match cookie
+                {
+                    Some(ref language) if available.contains(&language.to_lowercase()) => Some(language.to_lowercase()),
+                    _ => preferred_language(&header("accept-language"), &available)
+                        .or(self.config.default_language.as_ref().map(|l| l.to_lowercase()))
+                };
+                let content = language
+                    .and_then(|l| variants.iter().find(|v| v.kind == VariantKind::Language(l.clone())))
+                    .map(|v| v.content.clone());
+                let vary = if self.config.language_cookie.is_some() { "accept-language, cookie" } else { "accept-language" };
+                Some(Selection { content, vary: vary.to_string() })
+            },
+            None => None
+        }
+    }
+
+    /// The uri serving a language variant directly, /fr/page if
+    ///   [NegotiationConfig::language_prefix] otherwise the variant's own uri
+    pub fn language_uri(&self, variant: &Variant) -> String
+    {
+        match (&variant.kind, self.config.language_prefix)
+        {
+            (VariantKind::Language(language), Some(true)) => match language_variant(&variant.uri)
+            {
+                Some((base, _)) => format!("/{}{}", language, base),
+                None => variant.uri.clone()
+            },
+            _ => variant.uri.clone()
+        }
+    }
+
+    /// hreflang alternates (language, uri) for uris of language variants and their groups,
+    ///   including x-default as the group's uri
+    pub fn alternates(&self) -> BTreeMap<String, Vec<(String, String)>>
+    {
+        let mut alternates = BTreeMap::new();
+        for (uri, variants) in &self.groups
+        {
+            let languages: Vec<(String, String)> = variants.iter()
+                .filter_map(|v| match v.kind { VariantKind::Language(ref l) => Some((l.clone(), self.language_uri(v))), _ => None })
+                .collect();
+            if languages.is_empty() { continue }
+
+            let base = match language_variant(&variants[0].uri) { Some((base, _)) => base, None => continue };
+            let mut links = languages.clone();
+            links.push(("x-default".to_string(), base));
+
+            alternates.insert(uri.clone(), links.clone());
+            for variant in variants
+            {
+                alternates.insert(variant.uri.clone(), links.clone());
+            }
+        }
+        alternates
+    }
+}
+
+/// Group image and language variants amongst uris, see [NegotiationConfig]
+pub fn group_variants(contents: &BTreeMap<String, Arc<Mutex<Content>>>, config: &NegotiationConfig) -> BTreeMap<String, Vec<Variant>>
+{
+    let mut groups: BTreeMap<String, Vec<Variant>> = BTreeMap::new();
+
+    for (uri, content) in contents
+    {
+        if config.images.is_some_and(|i| i)
+        {
+            let mime = <MIME as Mime>::infer_mime_type(uri);
+            if [MIME::ImageJPEG, MIME::ImagePNG, MIME::ImageGIF].contains(&mime)
+            {
+                let stem = &uri[0..uri.rfind('.').unwrap()];
+                let variants: Vec<Variant> = IMAGE_VARIANTS.iter()
+                    .filter_map(|m|
+                    {
+                        let variant_uri = format!("{}.{}", stem, m.as_str().trim_start_matches("image/"));
+                        contents.get(&variant_uri).map(|c| Variant { kind: VariantKind::Image(*m), uri: variant_uri, content: c.clone() })
+                    })
+                    .collect();
+                if !variants.is_empty() { groups.insert(uri.clone(), variants); }
+            }
+        }
+
+        if config.languages.is_some_and(|l| l)
+        {
+            if let Some((base, language)) = language_variant(uri)
+            {
+                let variant = Variant { kind: VariantKind::Language(language), uri: uri.clone(), content: content.clone() };
+                for group in [base.clone(), format!("{}.html", base)]
+                {
+                    groups.entry(group).or_default().push(variant.clone());
+                }
+            }
+        }
+    }
+    groups
+}
+
+/// If uri is a language variant, e.g. /page.fr.html, its base (/page) and language (fr)
+pub fn language_variant(uri: &str) -> Option<(String, String)>
+{
+    static VARIANT: OnceLock<Regex> = OnceLock::new();
+    VARIANT.get_or_init(|| Regex::new(r"^(.+)\.([a-zA-Z]{2}(?:-[a-zA-Z]{2})?)\.html?$").unwrap())
+        .captures(uri).map(|caps| (caps[1].to_string(), caps[2].to_lowercase()))
+}
+
+/// Parse an Accept style header into values and qualities, highest quality first
+pub fn parse_quality_list(header: &str) -> Vec<(String, f32)>
+{
+    let mut values: Vec<(String, f32)> = header.split(',')
+        .filter_map(|token|
+        {
+            let mut parts = token.split(';');
+            let value = parts.next()?.trim().to_lowercase();
+            if value.is_empty() { return None }
+            let q = parts
+                .filter_map(|p| p.trim().strip_prefix("q=").and_then(|q| q.trim().parse::<f32>().ok()))
+                .next()
+                .unwrap_or(1.0);
+            Some((value, q))
+        })
+        .collect();
+    // stable, so equal qualities keep the client's order
+    values.sort_by(|a, b| b.1.total_cmp(&a.1));
+    values
+}
+
+/// The preferred image format explicitly accepted, wildcards do not count
+///   as clients send them without supporting every format
+pub fn preferred_image(accept: &str, available: &[MIME]) -> Option<MIME>
+{
+    let accepted = parse_quality_list(accept);
+    let mut best: Option<(MIME, f32)> = None;
+    for mime in IMAGE_VARIANTS.iter().filter(|m| available.contains(m))
+    {
+        if let Some((_, q)) = accepted.iter().find(|(value, _)| value == mime.as_str())
+        {
+            if *q > 0.0 && best.is_none_or(|(_, best_q)| *q > best_q) { best = Some((*mime, *q)); }
+        }
+    }
+    best.map(|(mime, _)| mime)
+}
+
+/// The preferred available language for an Accept-Language header, matching exactly
+///   or by primary subtag (en-GB and en)
+pub fn preferred_language(accept_language: &str, available: &[String]) -> Option<String>
+{
+    for (language, q) in parse_quality_list(accept_language)
+    {
+        if q <= 0.0 || language == "*" { continue }
+        if available.contains(&language) { return Some(language) }
+
+        let primary = language.split('-').next().unwrap_or_default();
+        if let Some(l) = available.iter().find(|l| l.split('-').next() == Some(primary))
+        {
+            return Some(l.clone())
+        }
+    }
+    None
+}
+
+/// The value of a cookie from a Cookie header
+pub fn cookie_value(cookie: &str, name: &str) -> Option<String>
+{
+    cookie.split(';')
+        .filter_map(|c| c.trim().split_once('='))
+        .find(|(n, _)| *n == name)
+        .map(|(_, v)| v.trim().to_string())
+}
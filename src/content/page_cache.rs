@@ -0,0 +1,85 @@
+use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex}};
+
+use git2::Oid;
+
+use super::{search::Document, template::same_path};
+
+/// [PageCache] shared by clones of a [super::sitemap::SiteMap]
+pub type SharedPageCache = Arc<Mutex<PageCache>>;
+
+/// What [super::sitemap::SiteMap::generate] derives from a page as rendered
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CachedPage
+{
+    /// ```href``` and ```src``` references, see [super::links::link_references]
+    pub links: Vec<String>,
+    /// Image and video references, see [super::media::media_references]
+    pub media: Vec<String>,
+    /// The page in the search index, if searched
+    pub document: Option<Document>,
+    /// Files the rendered page depends on, e.g. includes and layouts
+    pub dependencies: Vec<String>
+}
+
+/// [CachedPage]s by uri and disk path, kept until the page or a file it depends on
+///   changes, see [PageCache::invalidate], so only changed pages are rendered again
+#[derive(Debug, Default)]
+pub struct PageCache
+{
+    pages: HashMap<(String, String), CachedPage>,
+    /// The served commit, if read from git objects
+    commit: Option<Oid>
+}
+
+impl PageCache
+{
+    pub fn shared() -> SharedPageCache
+    {
+        Arc::new(Mutex::new(PageCache::default()))
+    }
+
+    pub fn get(&self, uri: &str, disk_path: &str) -> Option<CachedPage>
+    {
+        self.pages.get(&(uri.to_string(), disk_path.to_string())).cloned()
+    }
+
+    pub fn insert(&mut self, uri: &str, disk_path: &str, page: CachedPage)
+    {
+        self.pages.insert((uri.to_string(), disk_path.to_string()), page);
+    }
+
+    /// Forget pages served from, or depending on, the file at path
+    pub fn invalidate(&mut self, path: &str)
+    {
+        self.pages.retain(|(_, disk_path), page|
+        {
+            !same_path(disk_path, path) && !page.dependencies.iter().any(|d| same_path(d, path))
+        });
+    }
+
+    /// Forget pages no longer in pages (uri, disk path)
+    pub fn retain(&mut self, pages: &HashSet<(String, String)>)
+    {
+        self.pages.retain(|key, _| pages.contains(key));
+    }
+
+    /// Forget every page if the served commit moved, e.g. after a pull or pin
+    pub fn at_commit(&mut self, commit: Option<Oid>)
+    {
+        if self.commit != commit
+        {
+            self.pages.clear();
+            self.commit = commit;
+        }
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.pages.is_empty()
+    }
+}
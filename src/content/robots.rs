@@ -0,0 +1,143 @@
+use std::fmt;
+
+use crate::config::RobotsGroupConfig;
+
+/// A group of robots.txt rules, (directive, value) e.g. ("Disallow", "/private/"),
+///   for user agents
+#[derive(Debug, Clone, PartialEq)]
+pub struct RobotsGroup
+{
+    pub user_agents: Vec<String>,
+    pub rules: Vec<(String, String)>
+}
+
+impl RobotsGroup
+{
+    fn same_agents(&self, user_agents: &[String]) -> bool
+    {
+        let mut a: Vec<String> = self.user_agents.iter().map(|u| u.to_lowercase()).collect();
+        let mut b: Vec<String> = user_agents.iter().map(|u| u.to_lowercase()).collect();
+        a.sort();
+        b.sort();
+        a == b
+    }
+}
+
+/// A robots.txt, see <https://www.rfc-editor.org/rfc/rfc9309.html>
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Robots
+{
+    /// Comments before the first group
+    pub header: Vec<String>,
+    pub groups: Vec<RobotsGroup>,
+    pub sitemaps: Vec<String>
+}
+
+impl Robots
+{
+    /// Parse a robots.txt, comments within groups and unknown lines outside of
+    ///   groups are dropped
+    pub fn parse(robots: &str) -> Robots
+    {
+        let mut parsed = Robots::default();
+        // consecutive user-agent lines start one group
+        let mut agents_open = false;
+        for line in robots.lines()
+        {
+            let line = line.trim();
+            if line.starts_with('#')
+            {
+                if parsed.groups.is_empty() { parsed.header.push(line.to_string()); }
+                continue
+            }
+
+            let (directive, value) = match line.split_once(':')
+            {
+                Some((d, v)) => (d.trim(), v.split('#').next().unwrap_or_default().trim()),
+                None => continue
+            };
+
+            match directive.to_lowercase().as_str()
+            {
+                "user-agent" =>
+                {
+                    match parsed.groups.last_mut()
+                    {
+                        Some(group) if agents_open => group.user_agents.push(value.to_string()),
+                        _ => parsed.groups.push(RobotsGroup { user_agents: vec![value.to_string()], rules: vec![] })
+                    }
+                    agents_open = true;
+                },
+                "sitemap" => parsed.add_sitemap(value),
+                _ =>
+                {
+                    agents_open = false;
+                    if let Some(group) = parsed.groups.last_mut()
+                    {
+                        group.rules.push((directive.to_string(), value.to_string()));
+                    }
+                }
+            }
+        }
+        parsed
+    }
+
+    /// Add rules to the group for the same user agents, or a new group.
+    ///   A crawl delay replaces an existing one
+    pub fn merge(&mut self, config: &RobotsGroupConfig)
+    {
+        let index = match self.groups.iter().position(|g| g.same_agents(&config.user_agents))
+        {
+            Some(i) => i,
+            None =>
+            {
+                self.groups.push(RobotsGroup { user_agents: config.user_agents.clone(), rules: vec![] });
+                self.groups.len() - 1
+            }
+        };
+        let group = &mut self.groups[index];
+
+        let rules = config.allow.iter().flatten().map(|p| ("Allow", p))
+            .chain(config.disallow.iter().flatten().map(|p| ("Disallow", p)));
+        for (directive, path) in rules
+        {
+            if !group.rules.iter().any(|(d, v)| d.eq_ignore_ascii_case(directive) && v == path)
+            {
+                group.rules.push((directive.to_string(), path.clone()));
+            }
+        }
+
+        if let Some(delay) = config.crawl_delay
+        {
+            group.rules.retain(|(d, _)| !d.eq_ignore_ascii_case("crawl-delay"));
+            group.rules.push(("Crawl-delay".to_string(), delay.to_string()));
+        }
+    }
+
+    /// Add a Sitemap: url, if not already present
+    pub fn add_sitemap(&mut self, url: &str)
+    {
+        if !self.sitemaps.iter().any(|s| s == url) { self.sitemaps.push(url.to_string()); }
+    }
+}
+
+impl fmt::Display for Robots
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let mut sections: Vec<String> = vec![];
+        if !self.header.is_empty() { sections.push(self.header.join("\n")); }
+        for group in &self.groups
+        {
+            let lines: Vec<String> = group.user_agents.iter().map(|u| format!("User-agent: {}", u))
+                .chain(group.rules.iter().map(|(d, v)| format!("{}: {}", d, v)))
+                .collect();
+            sections.push(lines.join("\n"));
+        }
+        if !self.sitemaps.is_empty()
+        {
+            sections.push(self.sitemaps.iter().map(|s| format!("Sitemap: {}", s)).collect::<Vec<String>>().join("\n"));
+        }
+        write!(f, "{}", sections.join("\n\n"))
+    }
+}
@@ -0,0 +1,337 @@
+use std::{collections::{BTreeMap, BTreeSet, HashMap}, sync::{Arc, OnceLock, RwLock}};
+
+use axum::{extract::Query, http::HeaderMap, response::{Html, IntoResponse}, routing::{get, MethodRouter}, Json};
+use quick_xml::escape::{escape, unescape};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::SearchConfig, filesystem::file::read_file_utf8};
+
+use super::{feed::html_meta, markdown::{is_markdown, render_markdown, split_front_matter}};
+
+pub const DEFAULT_URI: &str = "/search";
+pub const DEFAULT_MAX_RESULTS: usize = 10;
+/// Characters of page text in a result's snippet
+pub const SNIPPET_CHARS: usize = 160;
+/// Words in a page's title count this many times
+const TITLE_WEIGHT: u32 = 3;
+/// Query words matching the start of an indexed word score this fraction of an exact match
+const PREFIX_WEIGHT: f32 = 0.5;
+
+/// Used when no results template is configured, see [crate::config::SearchConfig::results_template]
+pub const DEFAULT_RESULTS_BODY: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Search: SEARCH_QUERY</title>
+</head>
+<body>
+    <form action="SEARCH_URI">
+        <input type="search" name="q" value="SEARCH_QUERY">
+        <button type="submit">Search</button>
+    </form>
+    SEARCH_RESULTS
+    <a href="LINK_TO_HOME">Home</a>
+</body>
+</html>
+"#;
+
+/// A page in a [SearchIndex]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document
+{
+    pub uri: String,
+    pub title: String,
+    pub text: String
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchResult
+{
+    pub uri: String,
+    pub title: String,
+    pub snippet: String,
+    pub score: f32
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchResponse
+{
+    pub query: String,
+    pub results: Vec<SearchResult>
+}
+
+/// An inverted index of page words, ranked by tf-idf with title words
+///   weighted higher
+#[derive(Debug, Clone, Default)]
+pub struct SearchIndex
+{
+    documents: Vec<Document>,
+    /// (stemmed) words to documents containing them, with weighted counts
+    terms: BTreeMap<String, Vec<(usize, u32)>>,
+    /// Words as they appear in pages to their terms, so prefixes match before stemming
+    unstemmed: BTreeMap<String, String>,
+    stemming: bool
+}
+
+pub type SharedSearchIndex = Arc<RwLock<SearchIndex>>;
+
+impl SearchIndex
+{
+    pub fn new(documents: Vec<Document>, stemming: bool) -> SearchIndex
+    {
+        let mut terms: BTreeMap<String, Vec<(usize, u32)>> = BTreeMap::new();
+        let mut unstemmed: BTreeMap<String, String> = BTreeMap::new();
+        for (i, document) in documents.iter().enumerate()
+        {
+            let mut counts: HashMap<String, u32> = HashMap::new();
+            for (word, weight) in words(&document.title).into_iter().map(|w| (w, TITLE_WEIGHT)).chain(words(&document.text).into_iter().map(|w| (w, 1)))
+            {
+                let t = unstemmed.entry(word).or_insert_with_key(|w| term(w, stemming));
+                *counts.entry(t.clone()).or_default() += weight;
+            }
+            for (t, count) in counts
+            {
+                terms.entry(t).or_default().push((i, count));
+            }
+        }
+        SearchIndex { documents, terms, unstemmed, stemming }
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.documents.is_empty()
+    }
+
+    /// Pages matching query words, best first. Pages matching more of the words rank higher
+    pub fn search(&self, query: &str, prefix_matching: bool, limit: usize) -> Vec<SearchResult>
+    {
+        let query_words = words(query);
+        if query_words.is_empty() { return vec![] }
+
+        // score and number of query words matched
+        let mut scores: HashMap<usize, (f32, usize)> = HashMap::new();
+        for word in &query_words
+        {
+            let exact = term(word, self.stemming);
+            let mut matched: HashMap<usize, f32> = HashMap::new();
+            if let Some(postings) = self.terms.get(&exact)
+            {
+                self.score(postings, 1.0, &mut matched);
+            }
+            if prefix_matching
+            {
+                let prefixed: BTreeSet<&String> = self.unstemmed.range(word.clone()..)
+                    .take_while(|(w, _)| w.starts_with(word.as_str()))
+                    .map(|(_, t)| t)
+                    .filter(|t| **t != exact)
+                    .collect();
+                for postings in prefixed.into_iter().filter_map(|t| self.terms.get(t))
+                {
+                    self.score(postings, PREFIX_WEIGHT, &mut matched);
+                }
+            }
+            for (document, score) in matched
+            {
+                let total = scores.entry(document).or_default();
+                total.0 += score;
+                total.1 += 1;
+            }
+        }
+
+        let mut ranked: Vec<(usize, f32)> = scores.into_iter()
+            .map(|(document, (score, count))| (document, score * count as f32 / query_words.len() as f32))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(self.documents[a.0].uri.cmp(&self.documents[b.0].uri)));
+        ranked.truncate(limit);
+
+        ranked.into_iter()
+            .map(|(i, score)|
+            {
+                let document = &self.documents[i];
+                SearchResult { uri: document.uri.clone(), title: document.title.clone(), snippet: snippet(&document.text, &query_words), score }
+            })
+            .collect()
+    }
+
+    /// A document's score for a word is its best matching term's
+    fn score(&self, postings: &[(usize, u32)], weight: f32, matched: &mut HashMap<usize, f32>)
+    {
+        let idf = (1.0 + self.documents.len() as f32 / postings.len() as f32).ln();
+        for (document, count) in postings
+        {
+            let score = weight * (1.0 + (*count as f32).ln()) * idf;
+            let best = matched.entry(*document).or_default();
+            *best = best.max(score);
+        }
+    }
+}
+
+/// Lowercase words (letters and digits) in text
+pub fn words(text: &str) -> Vec<String>
+{
+    static WORD: OnceLock<Regex> = OnceLock::new();
+    WORD.get_or_init(|| Regex::new(r"[\p{L}\p{N}]+").unwrap())
+        .find_iter(text)
+        .map(|m| m.as_str().to_lowercase())
+        .collect()
+}
+
+fn term(word: &str, stemming: bool) -> String
+{
+    if stemming { stem(word) } else { word.to_string() }
+}
+
+/// A light English stemmer, removes common suffixes so e.g. runs, running
+///   and run are all run
+pub fn stem(word: &str) -> String
+{
+    if word.chars().count() <= 3 || !word.is_ascii() { return word.to_string() }
+
+    let undouble = |stem: &str| -> String
+    {
+        let bytes = stem.as_bytes();
+        let n = bytes.len();
+        if n > 2 && bytes[n-1] == bytes[n-2] && !b"aeiouylsz".contains(&bytes[n-1]) { stem[..n-1].to_string() } else { stem.to_string() }
+    };
+
+    if let Some(stem) = word.strip_suffix("ies") { if stem.len() > 1 { return format!("{}y", stem) } }
+    if let Some(stem) = word.strip_suffix("ing") { if stem.len() > 2 { return undouble(stem) } }
+    if let Some(stem) = word.strip_suffix("ed") { if stem.len() > 2 { return undouble(stem) } }
+    if let Some(stem) = word.strip_suffix("ly") { if stem.len() > 2 { return stem.to_string() } }
+    for suffix in ["sses", "xes", "ches", "shes", "zes"]
+    {
+        if word.ends_with(suffix) { return word[..word.len()-2].to_string() }
+    }
+    if word.ends_with('s') && !word.ends_with("ss") && !word.ends_with("us") && !word.ends_with("is")
+    {
+        return word[..word.len()-1].to_string()
+    }
+    word.to_string()
+}
+
+/// Visible text of html, without the head, scripts, styles, comments and tags
+pub fn extract_text(html: &str) -> String
+{
+    static HIDDEN: OnceLock<Regex> = OnceLock::new();
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    static SPACE: OnceLock<Regex> = OnceLock::new();
+    let hidden = HIDDEN.get_or_init(|| Regex::new(r"(?is)<head[\s>].*?</head\s*>|<script[\s>].*?</script\s*>|<style[\s>].*?</style\s*>|<!--.*?-->").unwrap());
+    let text = hidden.replace_all(html, " ");
+    let text = TAG.get_or_init(|| Regex::new(r"(?s)<[^>]*>").unwrap()).replace_all(&text, " ");
+    let text = SPACE.get_or_init(|| Regex::new(r"\s+").unwrap()).replace_all(&text, " ");
+    let text = text.trim();
+    match unescape(text)
+    {
+        Ok(t) => t.to_string(),
+        Err(_) => text.to_string()
+    }
+}
+
+/// Text around the first query word in text, or its start
+pub fn snippet(text: &str, query_words: &[String]) -> String
+{
+    let found = query_words.iter()
+        .filter_map(|w| Regex::new(&format!("(?i){}", regex::escape(w))).ok()?.find(text).map(|m| m.start()))
+        .min()
+        .unwrap_or(0);
+
+    let boundary = |mut i: usize| { while !text.is_char_boundary(i) { i -= 1; } i };
+    let start = boundary(found.saturating_sub(SNIPPET_CHARS / 4));
+    let end = boundary((start + SNIPPET_CHARS).min(text.len()));
+
+    format!
+    (
+        "{}{}{}",
+        if start > 0 { "…" } else { "" },
+        text[start..end].trim(),
+        if end < text.len() { "…" } else { "" }
+    )
+}
+
+/// A [Document] for the page source from disk_path served at uri, with its title
+///   from Markdown front-matter or the html ```<title>```
+pub fn document(uri: &str, disk_path: &str, source: &str) -> Document
+{
+    let (title, text) = if is_markdown(disk_path)
+    {
+        let (front_matter, body) = split_front_matter(source);
+        (front_matter.title, extract_text(&render_markdown(body)))
+    }
+    else
+    {
+        (html_meta(source).title, extract_text(source))
+    };
+
+    let title = title.unwrap_or_else(||
+        std::path::Path::new(disk_path).file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string()
+    );
+    Document { uri: uri.to_string(), title, text }
+}
+
+/// A html search results page, the template's SEARCH_QUERY, SEARCH_URI, SEARCH_RESULTS
+///   and LINK_TO_HOME are replaced
+pub struct ResultsPage
+{
+    pub body_template: String
+}
+
+impl ResultsPage
+{
+    fn expand_template(template: String, config: &SearchConfig, domain: &str) -> String
+    {
+        let mut domain = domain.to_string();
+        if !domain.starts_with("https://") {domain = format!("https://{domain}")}
+        template.replace("LINK_TO_HOME", &domain).replace("SEARCH_URI", config.uri.as_deref().unwrap_or(DEFAULT_URI))
+    }
+
+    pub fn expand_results(&self, query: &str, results: &[SearchResult]) -> String
+    {
+        let items: Vec<String> = results.iter()
+            .map(|r| format!("<li><a href=\"{}\">{}</a><p>{}</p></li>", escape(r.uri.as_str()), escape(r.title.as_str()), escape(r.snippet.as_str())))
+            .collect();
+        let results = if items.is_empty() && !query.is_empty() { "<p>No results</p>".to_string() } else { format!("<ol>{}</ol>", items.join("")) };
+        self.body_template.replace("SEARCH_QUERY", &escape(query)).replace("SEARCH_RESULTS", &results)
+    }
+
+    pub fn from(config: &SearchConfig, domain: &str) -> ResultsPage
+    {
+        if let Some(ref path) = config.results_template
+        {
+            if let Some(body) = read_file_utf8(path)
+            {
+                return ResultsPage {body_template: Self::expand_template(body, config, domain)}
+            }
+        }
+        ResultsPage {body_template: Self::expand_template(DEFAULT_RESULTS_BODY.to_string(), config, domain)}
+    }
+}
+
+/// A route serving results for ```?q=words&limit=n``` as a json [SearchResponse], or
+///   a [ResultsPage] to requests accepting html if [SearchConfig::results_page]
+pub fn search_route(index: SharedSearchIndex, config: SearchConfig, domain: &str) -> MethodRouter
+{
+    let page = if config.results_page.is_some_and(|p| p) { Some(Arc::new(ResultsPage::from(&config, domain))) } else { None };
+    let max_results = config.max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+    let prefix_matching = config.prefix_matching.unwrap_or(true);
+
+    get(move |Query(params): Query<HashMap<String, String>>, headers: HeaderMap| async move
+    {
+        let query = params.get("q").cloned().unwrap_or_default();
+        let limit = params.get("limit").and_then(|l| l.parse::<usize>().ok()).map_or(max_results, |l| l.min(max_results));
+        let results = index.read().unwrap().search(&query, prefix_matching, limit);
+
+        let accepts_html = headers.get("accept").and_then(|a| a.to_str().ok()).is_some_and(|a| a.contains("text/html"));
+        match page
+        {
+            Some(ref page) if accepts_html => Html(page.expand_results(&query, &results)).into_response(),
+            _ => Json(SearchResponse { query, results }).into_response()
+        }
+    })
+}
@@ -1,18 +1,26 @@
 
-use std::{collections::BTreeMap, sync::Arc, time::{Duration, Instant, SystemTime}, vec};
+use std::{collections::{BTreeMap, HashMap, HashSet}, path::{Path, PathBuf}, sync::{Arc, RwLock}, time::{Duration, Instant, SystemTime}, vec};
 use openssl::sha::Sha256;
 use tokio::sync::Mutex;
 
-use axum::{response::IntoResponse, routing::get, Router};
+use axum::{http::{HeaderMap, StatusCode}, response::{IntoResponse, Response}, routing::get, Router};
 use chrono::{DateTime, Datelike, Utc};
+use git2::{Oid, Repository};
 use indicatif::ProgressBar;
 use quick_xml::{events::{BytesText, Event}, Error, Writer};
 use regex::Regex;
-use crate::{config::{read_config, Config, CONFIG_PATH}, content::{filter::ContentFilter, HasUir}, filesystem::file::{write_file_bytes, File, Observed}, util::format_elapsed};
+use crate::{config::{is_config_file, read_config, Config, ContentConfig, FeedConfig, SitemapRuleConfig, CONFIG_PATH}, content::{filter::ContentFilter, HasUir}, filesystem::file::{read_file_utf8, File, Observed}, integrations::git::{head_info, last_commit_times, objects::GitSource}, util::{format_elapsed, matches_one}};
 
 use crate::server::https::parse_uri;
 
-use super::{get_content, mime_type::{Mime, MIME}, Content};
+use super::{cache::{ContentCache, SharedContentCache}, dependents::{Dependents, SharedDependents}, encoding::{find_sidecars, sidecar_of, Encoding}, feed::{self, feed_entry, FeedEntry}, generated::Generated, get_content, get_git_content, links::{check_references, link_references, SharedLinkReport}, markdown::{is_markdown, read_front_matter, render_markdown, render_page, split_front_matter, MarkdownOptions}, media::{media_references, resolve_reference, Media}, mime_type::{register_mime_types, Mime, MIME}, negotiate::{group_variants, Negotiation, VariantKind}, page_cache::{CachedPage, PageCache, SharedPageCache}, robots::Robots, search::{self, search_route, SearchIndex, SharedSearchIndex}, template::{expand, Expanded, IncludeOptions}, Content};
+
+/// The most urls in one sitemap, see <https://www.sitemaps.org/protocol.html>
+pub const MAX_SITEMAP_URLS: usize = 50_000;
+/// The largest (uncompressed) sitemap in bytes
+pub const MAX_SITEMAP_BYTES: usize = 52_428_800;
+/// Reserved for a sitemap's xml declaration and ```<urlset>```
+const SITEMAP_OVERHEAD_BYTES: usize = 1024;
 
 /// A tree structure representing a uri stem and content
 ///  convertable to a [Router] which monitors the content if
@@ -25,6 +33,7 @@ pub struct ContentTree
     uri_stem: String,
     contents: BTreeMap<String, Arc<Mutex<Content>>>,
     content_types: BTreeMap<String, MIME>,
+    disk_paths: BTreeMap<String, String>,
     children: BTreeMap<String, ContentTree>,
     sitmap_content: bool
 }
@@ -33,13 +42,13 @@ impl ContentTree
 {
     pub fn new(uri_stem: &str) -> ContentTree
     {
-        ContentTree { uri_stem: uri_stem.to_string(), contents: BTreeMap::new(), children: BTreeMap::new(), sitmap_content: false, content_types: BTreeMap::new() }
+        ContentTree { uri_stem: uri_stem.to_string(), contents: BTreeMap::new(), children: BTreeMap::new(), sitmap_content: false, content_types: BTreeMap::new(), disk_paths: BTreeMap::new() }
     }
 
     fn collect(&self) -> Vec<Arc<Mutex<Content>>>
     {
         let mut contents: Vec<Arc<Mutex<Content>>> = self.contents.values().cloned().collect();
-        for (_, child) in &self.children
+        for child in self.children.values()
         {
             contents.append(&mut child.collect());
         }
@@ -55,32 +64,45 @@ impl ContentTree
         }
     }
 
+    /// The uri, disk path and [MIME] type of all content, without locking it
+    pub fn collect_files(&self) -> Vec<(String, String, MIME)>
+    {
+        let mut files: Vec<(String, String, MIME)> = self.content_types.iter()
+            .map(|(uri, mime)| (uri.clone(), self.disk_paths.get(uri).cloned().unwrap_or_default(), *mime))
+            .collect();
+        for child in self.children.values()
+        {
+            files.append(&mut child.collect_files());
+        }
+        files
+    }
+
+    /// All content by uri
+    pub fn collect_contents(&self) -> BTreeMap<String, Arc<Mutex<Content>>>
+    {
+        let mut contents = self.contents.clone();
+        for child in self.children.values()
+        {
+            contents.append(&mut child.collect_contents());
+        }
+        contents
+    }
+
     /// Build a [Router] to serve the content. If static_router then
-    ///   content is never refreshed within the router
-    fn route(&self, static_router: bool) -> Router
+    ///   content is never refreshed within the router, only loaded
+    ///   when first served. Variants are chosen by [Negotiation::select]
+    fn route(&self, static_router: bool, negotiation: &Option<Arc<Negotiation>>, skip: &[String]) -> Router
     {
         let mut router = Router::new();
         for (uri, content) in self.contents.clone()
         {
-            router = router.route
-            (
-                &uri,
-                get(move || async move
-                    {
-                        let mut content = content.lock().await;
-                        if !static_router && content.server_cache_expired() && content.is_stale()
-                        {
-                            content.refresh();
-                            crate::debug(format!("Refresh called on Content {}", content.get_uri()), None);
-                        }
-                        content.clone().into_response()
-                    })
-            );
+            if skip.contains(&uri) { continue }
+            router = router.route(&uri, negotiated_route(uri.clone(), content, static_router, negotiation.clone()));
         }
 
         for (_uri, child) in &self.children
         {
-            router = router.merge(child.route(static_router));
+            router = router.merge(child.route(static_router, negotiation, skip));
         }
 
         router
@@ -123,6 +145,7 @@ impl ContentTree
         {
             if content.content_type.in_sitemap() { self.sitmap_content = true; }
             self.content_types.insert(content.get_uri(), content.content_type);
+            self.disk_paths.insert(content.get_uri(), content.path());
             self.contents.insert(content.get_uri(),Arc::new(Mutex::new(content)));
 
             return;
@@ -160,90 +183,224 @@ impl ContentTree
             {
                 if content.content_type.in_sitemap() { self.sitmap_content = true; }
                 self.content_types.insert(content.get_uri(), content.content_type);
+                self.disk_paths.insert(content.get_uri(), content.path());
                 self.contents.insert(content.get_uri(), Arc::new(Mutex::new(content)));
             }
         }
     }
 
-    /// If [ContentTree::contents] has any html, image, or video content
-    pub fn has_sitemap_content(&self) -> bool
+    /// Find the content served at uri
+    pub fn get(&self, uri: &str) -> Option<Arc<Mutex<Content>>>
     {
-        (!self.contents.is_empty()) && self.sitmap_content
+        if let Some(content) = self.contents.get(uri)
+        {
+            return Some(content.clone())
+        }
+
+        for child in self.children.values()
+        {
+            if let Some(content) = child.get(uri)
+            {
+                return Some(content)
+            }
+        }
+        None
     }
 
-    /// Implements writing to an xml conforming to <https://www.sitemaps.org/protocol.html>
-    ///  with <http://www.google.com/schemas/sitemap-image/1.1> and <http://www.google.com/schemas/sitemap-video/1.1>
-    pub fn to_xml(&self, domain: String) -> Vec<u8>
+    /// Remove the content served at uri, returns true if it was found
+    pub fn remove(&mut self, uri: &str) -> bool
     {
-        if self.contents.is_empty() && self.children.is_empty()
+        if self.contents.remove(uri).is_some()
         {
-            return vec![];
+            self.content_types.remove(uri);
+            self.disk_paths.remove(uri);
+            self.sitmap_content = self.content_types.values().any(|t| t.in_sitemap());
+            return true
         }
 
-        let mut buffer = vec![];
-        let mut writer = Writer::new_with_indent(&mut buffer, b' ', 8);
+        for child in self.children.values_mut()
+        {
+            if child.remove(uri)
+            {
+                return true
+            }
+        }
+        false
+    }
+
+    /// If [ContentTree::contents] has any html, image, or video content
+    pub fn has_sitemap_content(&self) -> bool
+    {
+        (!self.contents.is_empty()) && self.sitmap_content
+    }
+
+    /// Sitemap ```<url>``` elements, one per page with its hreflang alternates
+    ///   and the images and videos it uses, see [SiteMap::to_xml]
+    pub fn url_elements(&self, context: &UrlContext) -> Vec<String>
+    {
+        let mut urls = vec![];
+        let modified = |disk_path: Option<&String>| lastmod
+        (
+            disk_path
+                .and_then(|path| context.last_modified.get(path))
+                .map_or(SystemTime::now(), |t| t.into())
+        );
 
         if self.has_sitemap_content()
         {
-            match writer.create_element("url")
-                .write_inner_content::<_, Error>
-                (|writer|
+            for (uri, content) in &self.content_types
+            {
+                if uri.contains("sitemap.xml") || !content.is_html()
                 {
-                    for (uri, content) in &self.content_types
+                    continue;
+                }
+                let rule = context.rules.iter().find(|r| matches_one(uri, &r.paths));
+                urls.extend(url_element(&format!("{} in uri stem {}", uri, self.uri_stem), |writer|
+                {
+                    writer.create_element("loc").write_text_content(BytesText::new(&format!("{}{}", context.domain, uri)))?;
+                    writer.create_element("lastmod").write_text_content(BytesText::new(&modified(self.disk_paths.get(uri))))?;
+                    if let Some(changefreq) = rule.and_then(|r| r.changefreq)
                     {
-                        if uri.contains("sitemap.xml")
-                        {
-                            continue;
-                        }
-                        if content.is_image()
+                        writer.create_element("changefreq").write_text_content(BytesText::new(changefreq.as_str()))?;
+                    }
+                    if let Some(priority) = rule.and_then(|r| r.priority)
+                    {
+                        writer.create_element("priority").write_text_content(BytesText::new(&format!("{:.1}", priority.clamp(0.0, 1.0))))?;
+                    }
+                    for (language, href) in context.alternates.get(uri).into_iter().flatten()
+                    {
+                        writer.create_element("xhtml:link")
+                            .with_attributes([("rel", "alternate"), ("hreflang", language.as_str()), ("href", format!("{}{}", context.domain, href).as_str())])
+                            .write_empty()?;
+                    }
+                    for media in context.media.get(uri).into_iter().flatten()
+                    {
+                        if media.mime.is_image()
                         {
                             writer.create_element("image:image").write_inner_content::<_, Error>(|writer|
                                 {
-                                    writer.create_element("image:loc").write_text_content(BytesText::new(&format!("{}{}",domain, uri)))?;
+                                    writer.create_element("image:loc").write_text_content(BytesText::new(&format!("{}{}", context.domain, media.uri)))?;
                                     Ok(())
                                 })?;
                         }
-                        else if content.is_video()
+                        else if media.mime.is_video()
                         {
                             writer.create_element("video:video").write_inner_content::<_, Error>(|writer|
                                 {
-                                    writer.create_element("video:content_loc").write_text_content(BytesText::new(&format!("{}{}",domain, uri)))?;
-                                    writer.create_element("video:publication_date").write_text_content(BytesText::new(&lastmod(SystemTime::now())))?;
+                                    writer.create_element("video:content_loc").write_text_content(BytesText::new(&format!("{}{}", context.domain, media.uri)))?;
+                                    writer.create_element("video:publication_date").write_text_content(BytesText::new(&modified(Some(&media.disk_path))))?;
                                     Ok(())
                                 })?;
                         }
-                        else if content.is_html()
-                        {
-                            writer.create_element("loc").write_text_content(BytesText::new(&format!("{}{}",domain, uri)))?;
-                            writer.create_element("lastmod").write_text_content(BytesText::new(&lastmod(SystemTime::now())))?;
-                        }
                     }
                     Ok(())
-                })
-            {
-                Ok(_) =>
-                {
-                    if buffer.len() > 0
-                    {
-                        buffer.append(&mut "\n".as_bytes().to_vec());
-                    }
-                },
-                Err(e) => {crate::debug(format!("Error {} writing content for uri stem {} of sitemap to xml", e, self.uri_stem), None)}
+                }));
             }
         }
 
-        for (_uri, child) in &self.children
+        for child in self.children.values()
+        {
+            urls.append(&mut child.url_elements(context));
+        }
+        urls
+    }
+}
+
+/// What [ContentTree::url_elements] needs to describe content
+pub struct UrlContext<'a>
+{
+    pub domain: &'a str,
+    /// hreflang alternates (language, uri) by uri, see [Negotiation::alternates]
+    pub alternates: &'a BTreeMap<String, Vec<(String, String)>>,
+    pub rules: &'a [SitemapRuleConfig],
+    pub last_modified: &'a LastModified,
+    /// Images and videos by the uri of the page using them, see [SiteMap::page_media]
+    pub media: &'a BTreeMap<String, Vec<Media>>
+}
+
+/// Write a ```<url>``` element, None if it could not be written
+fn url_element(uri: &str, inner: impl FnOnce(&mut Writer<&mut Vec<u8>>) -> Result<(), Error>) -> Option<String>
+{
+    let mut buffer = vec![];
+    let mut writer = Writer::new_with_indent(&mut buffer, b' ', 4);
+    match writer.create_element("url").write_inner_content::<_, Error>(inner)
+    {
+        Ok(_) => String::from_utf8(buffer).ok(),
+        Err(e) =>
+        {
+            crate::debug(format!("Error {} writing {} to sitemap xml", e, uri), None);
+            None
+        }
+    }
+}
+
+/// Last modified times of content, by the last git commit changing it
+///   if tracked, otherwise the file's modified time
+pub struct LastModified
+{
+    /// The HEAD commit times were read at
+    pub head: Option<Oid>,
+    root: Option<PathBuf>,
+    commits: HashMap<PathBuf, DateTime<Utc>>
+}
+
+impl LastModified
+{
+    /// Read commit times from the repository containing path, if any
+    pub fn new(path: &str) -> LastModified
+    {
+        match Repository::discover(path)
+        {
+            Ok(repo) => LastModified
+            {
+                head: repo.head().ok().and_then(|h| h.target()),
+                // bare repositories, see [GitSource], have no working directory
+                root: std::fs::canonicalize(repo.workdir().unwrap_or(repo.path())).ok(),
+                commits: last_commit_times(&repo)
+            },
+            Err(_) => LastModified { head: None, root: None, commits: HashMap::new() }
+        }
+    }
+
+    pub fn get(&self, disk_path: &str) -> Option<DateTime<Utc>>
+    {
+        let committed = self.root.as_ref().and_then(|root|
+        {
+            // content served from git objects need not be on disk
+            let path = std::fs::canonicalize(disk_path).or_else(|_| std::path::absolute(disk_path)).ok()?;
+            self.commits.get(path.strip_prefix(root).ok()?).copied()
+        });
+
+        match committed
         {
-            buffer.append(&mut child.to_xml(domain.clone()));
+            Some(time) => Some(time),
+            None => std::fs::metadata(disk_path).and_then(|m| m.modified()).ok().map(|t| t.into())
         }
-        buffer
     }
 }
 
+/// Split (indented) sitemap ```<url>``` elements into sitemaps of at most max_urls,
+///   and max_bytes of elements
+pub fn split_urls(urls: Vec<String>, max_urls: usize, max_bytes: usize) -> Vec<Vec<String>>
+{
+    let mut sitemaps: Vec<Vec<String>> = vec![];
+    let mut bytes = 0;
+    for url in urls
+    {
+        let size = url.len() + 1;
+        match sitemaps.last_mut()
+        {
+            Some(sitemap) if sitemap.len() < max_urls && bytes + size <= max_bytes => { sitemap.push(url); },
+            _ => { bytes = 0; sitemaps.push(vec![url]); }
+        }
+        bytes += size;
+    }
+    sitemaps
+}
+
 /// Represents the structure of a site.
-///  If no sitemap.xml or robots.txt is present
-///  these will be generated by calling [SiteMap::to_xml]
-///  and inserting the resulting sitemap.xml
+///  sitemap.xml (see [SiteMap::to_xml]), robots.txt (see [SiteMap::robots]) and feeds
+///  can be generated, these are served from memory, see [Generated]
 ///
 /// Convertable to a router, see [ContentTree] for dynamic
 ///  options
@@ -253,13 +410,23 @@ pub struct SiteMap
     contents: ContentTree,
     domain: String,
     path: String,
-    hash: Vec<u8>
+    hash: Vec<u8>,
+    cache: Option<SharedContentCache>,
+    content_config: ContentConfig,
+    tag: bool,
+    generated: Generated,
+    search: SharedSearchIndex,
+    links: SharedLinkReport,
+    last_modified: Arc<RwLock<Option<Arc<LastModified>>>>,
+    dependents: SharedDependents,
+    page_cache: SharedPageCache,
+    git: Option<GitSource>
 }
 
 impl SiteMap
 {
     /// Searches the content path from [SiteMap::new] for [Content]
-    ///  robots.txt and sitemap.xml can be generated and added here
+    ///  robots.txt, sitemap.xml and feeds are generated here
     pub fn build
     (
         config: &Config,
@@ -268,9 +435,13 @@ impl SiteMap
     ) -> SiteMap
     {
 
+        if let Some(mime_types) = &config.content.mime_types
+        {
+            register_mime_types(mime_types);
+        }
+
         let server_cache_period = config.content.server_cache_period_seconds;
         let browser_cache_period = config.content.browser_cache_period_seconds;
-        let short_urls = config.content.allow_without_extension;
         let filter = match config.content.ignore_regexes.clone()
         {
             Some(p) => Some(ContentFilter::new(p.clone())),
@@ -290,15 +461,42 @@ impl SiteMap
             None
         };
 
-        let mut contents = get_content
-        (
-            &config.content.path,
-            &config.content.path,
-            Some(server_cache_period),
-            Some(browser_cache_period),
-            Some(tag),
-            filter.as_ref()
-        );
+        let git = match config.git
+        {
+            // a checked out tag is the (detached) HEAD
+            Some(ref git) if git.serves_objects() => match GitSource::open(&config.content.path, if git.tags.is_some() { "HEAD" } else { &git.branch })
+            {
+                Ok(source) => Some(source),
+                Err(e) =>
+                {
+                    crate::debug(format!("Cannot serve git objects from {}, {}", config.content.path, e), Some("GIT"));
+                    None
+                }
+            },
+            _ => None
+        };
+
+        let mut contents = match git
+        {
+            Some(ref source) => get_git_content
+            (
+                source,
+                &config.content.path,
+                Some(server_cache_period),
+                Some(browser_cache_period),
+                Some(tag),
+                filter.as_ref()
+            ),
+            None => get_content
+            (
+                &config.content.path,
+                &config.content.path,
+                Some(server_cache_period),
+                Some(browser_cache_period),
+                Some(tag),
+                filter.as_ref()
+            )
+        };
 
         if !silent
         {
@@ -319,23 +517,27 @@ impl SiteMap
             None
         };
 
-        let mut content_tree = ContentTree::new("/");
+        let mut sitemap = SiteMap
+        {
+            contents: ContentTree::new("/"),
+            domain: config.domain.clone(),
+            path: config.content.path.clone(),
+            hash: vec![],
+            cache: config.content.cache_budget_bytes.map(ContentCache::shared),
+            content_config: config.content.clone(),
+            tag,
+            generated: Generated::new(config.content.browser_cache_period_seconds),
+            search: SharedSearchIndex::default(),
+            links: SharedLinkReport::default(),
+            last_modified: Arc::new(RwLock::new(None)),
+            dependents: Dependents::shared(),
+            page_cache: PageCache::shared(),
+            git
+        };
 
         for content in contents
         {
-            if content.get_uri().contains("config.json") { continue }
-            crate::debug(format!("Adding content {:?}", content.preview(64)), None);
-            let path = config.content.path.clone()+"/";
-            let uri = parse_uri(content.get_uri(), path);
-
-            if short_urls && content.get_content_type().is_html()
-            {
-                let short_uri = Regex::new(r"\.\S+$").unwrap().replacen(&uri, 1, "");
-                crate::debug(format!("Adding content as short url: {}", short_uri), None);
-                content_tree.push(short_uri.to_string(), Content::new(&short_uri, &content.path(), server_cache_period, browser_cache_period, tag));
-            }
-
-            content_tree.push(content.uri.clone(), content);
+            sitemap.insert(content);
             if !silent {bar.as_ref().unwrap().inc(1);}
         }
         if !silent
@@ -344,15 +546,7 @@ impl SiteMap
             println!("Building sitemap took {}", format_elapsed(tic));
         }
 
-        let mut sitemap = SiteMap
-        {
-            contents: content_tree,
-            domain: config.domain.clone(),
-            path: config.content.path.clone(),
-            hash: vec![]
-        };
-
-        let home = Content::new
+        let mut home = Content::new
         (
             "/",
             &config.content.home.clone(),
@@ -360,34 +554,487 @@ impl SiteMap
             config.content.browser_cache_period_seconds,
             tag
         );
+        for (encoding, sidecar) in find_sidecars(&config.content.home)
+        {
+            home.add_sidecar(encoding, &sidecar);
+        }
 
         sitemap.push(home, Some("/"));
 
-        if let Some(true) = config.content.generate_sitemap
+        sitemap.calculate_hash();
+        sitemap.generate();
+
+        sitemap
+    }
+
+    /// Add content found on disk to [SiteMap::contents], also as a short
+    ///   url if [crate::config::ContentConfig::allow_without_extension]
+    fn insert(&mut self, mut content: Content)
+    {
+        if is_config_file(&content.get_uri()) { return }
+        crate::debug(format!("Adding content {:?}", content.preview(64)), None);
+        let path = self.path.clone()+"/";
+        let uri = parse_uri(content.get_uri(), path);
+
+        if self.markdown_options().is_some() && is_markdown(&content.path())
         {
-            sitemap.write_robots();
-            sitemap.write_sitemap_xml();
+            if read_front_matter(&content.path()).is_some_and(|f| f.is_draft())
+            {
+                crate::debug(format!("Skipping draft {}", content.path()), None);
+                return
+            }
+            // rendered pages are only served without their extension
+            content.uri = short_uri(&uri);
+            self.prepare(&mut content);
+            self.contents.push(content.uri.clone(), content);
+            return
         }
 
-        sitemap.calculate_hash();
+        self.prepare(&mut content);
 
-        sitemap
+        if self.content_config.allow_without_extension && content.get_content_type().is_html()
+        {
+            let short_uri = short_uri(&uri);
+            crate::debug(format!("Adding content as short url: {}", short_uri), None);
+            let mut short_content = Content::new
+            (
+                &short_uri,
+                &content.path(),
+                self.content_config.server_cache_period_seconds,
+                self.content_config.browser_cache_period_seconds,
+                self.tag
+            );
+            self.prepare(&mut short_content);
+            short_content.sidecars = content.sidecars.clone();
+            self.contents.push(short_uri.to_string(), short_content);
+        }
+
+        self.contents.push(content.uri.clone(), content);
     }
 
-    pub fn write_robots(&self)
+    /// Apply the [SiteMap]'s cache, streaming and Markdown settings to content
+    fn prepare(&self, content: &mut Content)
     {
-        write_file_bytes(&format!("{}/{}",self.path,"robots.txt"), format!("Sitemap: {}/sitemap.xml", self.domain).as_bytes());
+        content.set_git_source(self.git.clone());
+        content.set_cache(self.cache.clone());
+        content.set_dependents(Some(self.dependents.clone()));
+        content.set_stream_threshold(self.content_config.stream_threshold_bytes);
+        content.set_markdown(self.markdown_options());
+        content.set_server_side_includes(self.include_options());
+        content.set_snippets(self.content_config.snippets.as_deref().unwrap_or_default());
     }
 
-    pub fn write_sitemap_xml(&self)
+    /// Rendering reads files from disk, so is not done when serving git objects
+    fn markdown_options(&self) -> Option<MarkdownOptions>
     {
-        write_file_bytes(&format!("{}/{}",self.path,"sitemap.xml"), &self.to_xml());
+        if self.git.is_some() { return None }
+        match self.content_config.render_markdown
+        {
+            Some(true) => Some(MarkdownOptions { content_path: self.path.clone(), layout: self.content_config.markdown_layout.clone() }),
+            _ => None
+        }
+    }
+
+    fn include_options(&self) -> Option<IncludeOptions>
+    {
+        if self.git.is_some() { return None }
+        match self.content_config.server_side_includes
+        {
+            Some(true) => Some(IncludeOptions { content_path: self.path.clone(), domain: self.domain.clone() }),
+            _ => None
+        }
+    }
+
+    /// The uri content at a disk path is served under, without the extension if rendered Markdown
+    fn served_uri(&self, path: &str) -> String
+    {
+        let uri = path.replacen(&self.path, "", 1);
+        if self.markdown_options().is_some() && is_markdown(path) { short_uri(&uri) } else { uri }
+    }
+
+    /// Update the [SiteMap] for files (or directories) at disk paths that have been
+    ///   created, modified or removed, e.g. from a [super::watcher::ContentWatcher].
+    ///   Only the affected content is touched. Modified content is refreshed in place,
+    ///   so routers already serving it see the change.
+    ///
+    /// Returns true if uris were added or removed (the (path) hash changed).
+    ///   Generated files are regenerated, e.g. for new lastmods or feed entries, rendering
+    ///   only changed pages and those depending on changed files, see [PageCache]
+    pub async fn apply_changes(&mut self, paths: Vec<String>) -> bool
+    {
+        let filter = self.content_config.ignore_regexes.clone().map(ContentFilter::new);
+        let mut paths = match filter
+        {
+            Some(ref f) => f.filter_uris(paths),
+            None => paths
+        };
+        paths.sort();
+        paths.dedup();
+
+        let old_hash = self.hash.clone();
+
+        for path in paths
+        {
+            let uri = self.served_uri(&path);
+            if is_config_file(&uri) { continue }
+
+            self.page_cache.lock().unwrap().invalidate(&path);
+            let dependents = self.dependents.lock().unwrap().of(&path);
+            for content in dependents.iter().filter_map(|uri| self.contents.get(uri))
+            {
+                let mut content = content.lock().await;
+                crate::debug(format!("Refreshing {}, {} changed", content.get_uri(), path), None);
+                content.refresh();
+            }
+
+            let disk_path = Path::new(&path);
+
+            if let Some((original, encoding)) = sidecar_of(&path)
+            {
+                let exists = disk_path.is_file();
+                if self.update_sidecar(&original, encoding, &path, exists).await
+                {
+                    if exists && self.contents.remove(&uri) { crate::debug(format!("Serving {} as a sidecar of {}", path, original), None); }
+                    continue
+                }
+            }
+
+            if disk_path.is_file()
+            {
+                if self.markdown_options().is_some() && is_markdown(&path) && read_front_matter(&path).is_some_and(|f| f.is_draft())
+                {
+                    if self.contents.remove(&uri) { crate::debug(format!("Removing draft {}", uri), None); }
+                    continue
+                }
+
+                match self.contents.get(&uri)
+                {
+                    Some(content) =>
+                    {
+                        crate::debug(format!("Refreshing changed content {}", uri), None);
+                        for content in [Some(content), self.contents.get(&short_uri(&uri)), self.contents.get("/")].into_iter().flatten()
+                        {
+                            let mut content = content.lock().await;
+                            if content.path() == path { content.refresh(); }
+                        }
+                    },
+                    None =>
+                    {
+                        crate::debug(format!("Adding new content {}", uri), None);
+                        self.insert(self.new_content(&path.replacen(&self.path, "", 1), &path));
+                        for (encoding, sidecar) in find_sidecars(&path)
+                        {
+                            self.update_sidecar(&path, encoding, &sidecar, true).await;
+                            self.contents.remove(&self.served_uri(&sidecar));
+                        }
+                    }
+                }
+            }
+            else if disk_path.is_dir()
+            {
+                let existing = self.collect_uris();
+                let filter = self.content_config.ignore_regexes.clone().map(ContentFilter::new);
+                for content in get_content
+                (
+                    &self.path,
+                    &path,
+                    Some(self.content_config.server_cache_period_seconds),
+                    Some(self.content_config.browser_cache_period_seconds),
+                    Some(self.tag),
+                    filter.as_ref()
+                )
+                {
+                    if !existing.contains(&self.served_uri(&content.path()))
+                    {
+                        crate::debug(format!("Adding new content {}", content.get_uri()), None);
+                        self.insert(content);
+                    }
+                }
+            }
+            else
+            {
+                let dir_uri = format!("{}/", uri);
+                let short = if <MIME as Mime>::infer_mime_type(&path).is_html() || is_markdown(&path) { Some(short_uri(&uri)) } else { None };
+                for removed in self.collect_uris().into_iter().filter(|u| u.starts_with(&dir_uri) || *u == uri || Some(u) == short.as_ref())
+                {
+                    if removed == "/" { continue }
+                    crate::debug(format!("Removing content {}", removed), None);
+                    self.contents.remove(&removed);
+                }
+            }
+        }
+
+        self.calculate_hash();
+        self.generate();
+
+        self.hash != old_hash
+    }
+
+    /// Add (or remove) a precompressed sidecar to content served from the original
+    ///   disk path, returns true if there is such content
+    async fn update_sidecar(&self, original: &str, encoding: Encoding, sidecar: &str, add: bool) -> bool
+    {
+        let uri = self.served_uri(original);
+        let mut found = false;
+        for content in [self.contents.get(&uri), self.contents.get(&short_uri(&uri)), self.contents.get("/")].into_iter().flatten()
+        {
+            let mut content = content.lock().await;
+            if content.path() != original { continue }
+            found = true;
+            if add { content.add_sidecar(encoding, sidecar); } else { content.remove_sidecar(sidecar); }
+        }
+        found
+    }
+
+    fn new_content(&self, uri: &str, disk_path: &str) -> Content
+    {
+        Content::new
+        (
+            uri,
+            disk_path,
+            self.content_config.server_cache_period_seconds,
+            self.content_config.browser_cache_period_seconds,
+            self.tag
+        )
+    }
+
+    /// Generate robots.txt, sitemaps and feeds, as configured, into [SiteMap::generated],
+    ///   rebuild the [SiteMap::search_index] and check links, see [SiteMap::link_report].
+    ///   Pages are rendered only if not in the [PageCache]
+    pub fn generate(&self)
+    {
+        {
+            let html: HashSet<(String, String)> = self.contents.collect_files().into_iter()
+                .filter(|(_, _, mime)| mime.is_html())
+                .map(|(uri, disk_path, _)| (uri, disk_path))
+                .collect();
+            let mut pages = self.page_cache.lock().unwrap();
+            pages.at_commit(self.git.as_ref().map(|git| git.commit()));
+            pages.retain(&html);
+        }
+
+        if let Some(ref config) = self.content_config.search
+        {
+            let documents = self.pages("").into_iter()
+                .filter_map(|(uri, disk_path)| self.cached_page(&uri, &disk_path)?.document)
+                .collect();
+            *self.search.write().unwrap() = SearchIndex::new(documents, config.stemming.unwrap_or(true));
+        }
+
+        let mut files = vec![];
+        if let Some(robots) = self.robots()
+        {
+            files.push(("/robots.txt".to_string(), robots.to_string().into_bytes()));
+        }
+        if let Some(true) = self.content_config.generate_sitemap
+        {
+            files.extend(self.sitemaps().into_iter().map(|(name, xml)| (format!("/{}", name), xml)));
+        }
+        for config in self.content_config.feeds.as_deref().unwrap_or_default()
+        {
+            files.push((config.uri.clone(), feed::to_xml(config, &self.domain, &self.feed_entries(config))));
+        }
+        self.generated.update(|_| true, files);
+
+        if let Some(true) = self.content_config.check_links
+        {
+            let pages: Vec<(String, Vec<String>)> = self.pages("").into_iter()
+                .filter_map(|(uri, disk_path)| Some((uri.clone(), self.cached_page(&uri, &disk_path)?.links)))
+                .collect();
+            let mut report = check_references(&pages, &self.served_uris(), &self.domain);
+            report.checked_utc = Utc::now().to_rfc3339();
+            report.commit = Repository::open(&self.path).ok().and_then(|repo| head_info(&repo));
+            *self.links.write().unwrap() = report;
+        }
+    }
+
+    /// Every uri served, including generated files, search and negotiated uris
+    fn served_uris(&self) -> HashSet<String>
+    {
+        let mut uris: HashSet<String> = self.collect_uris().into_iter().chain(self.generated.uris()).collect();
+        if let Some(ref search) = self.content_config.search
+        {
+            uris.insert(search.uri.clone().unwrap_or(search::DEFAULT_URI.to_string()));
+        }
+        if let Some(negotiation) = self.negotiation()
+        {
+            for (uri, variants) in &negotiation.groups
+            {
+                uris.insert(uri.clone());
+                uris.extend(variants.iter().map(|v| negotiation.language_uri(v)));
+            }
+        }
+        uris
+    }
+
+    /// Uris of content depending on the file at path, e.g. a partial or layout
+    pub fn dependents(&self, path: &str) -> Vec<String>
+    {
+        self.dependents.lock().unwrap().of(path)
+    }
+
+    /// Files generated from the site, see [SiteMap::generate]
+    pub fn generated(&self) -> &Generated
+    {
+        &self.generated
+    }
+
+    /// The index of html (and Markdown) pages if [crate::config::ContentConfig::search]
+    ///   is configured, see [SiteMap::generate]
+    pub fn search_index(&self) -> &SharedSearchIndex
+    {
+        &self.search
+    }
+
+    /// The git objects content is read from, if [crate::config::GitConfig::serve_objects]
+    pub fn git_source(&self) -> Option<&GitSource>
+    {
+        self.git.as_ref()
+    }
+
+    /// Broken links found when last generated, empty unless
+    ///   [crate::config::ContentConfig::check_links], see [SiteMap::generate]
+    pub fn link_report(&self) -> &SharedLinkReport
+    {
+        &self.links
+    }
+
+    /// The robots.txt, if [crate::config::ContentConfig::robots] is configured or
+    ///   [crate::config::ContentConfig::generate_sitemap], when it links to sitemap.xml.
+    ///   Configured rules are merged into a robots.txt in the content path, unless
+    ///   [crate::config::RobotsConfig::merge] is false
+    pub fn robots(&self) -> Option<Robots>
+    {
+        let generate_sitemap = self.content_config.generate_sitemap.is_some_and(|g| g);
+        if !generate_sitemap && self.content_config.robots.is_none() { return None }
+
+        let config = self.content_config.robots.clone().unwrap_or_default();
+        let path = format!("{}/robots.txt", self.path);
+        let mut robots = match config.merge
+        {
+            Some(false) => Robots::default(),
+            _ if Path::new(&path).is_file() => read_file_utf8(&path).map_or(Robots::default(), |r| Robots::parse(&r)),
+            _ => Robots::default()
+        };
+
+        for group in config.groups.iter().flatten()
+        {
+            robots.merge(group);
+        }
+        if generate_sitemap
+        {
+            robots.add_sitemap(&format!("{}/sitemap.xml", self.domain));
+        }
+        Some(robots)
+    }
+
+    /// Pages served under a feed's prefix, once per disk path (at the shortest uri)
+    pub fn feed_entries(&self, config: &FeedConfig) -> Vec<FeedEntry>
+    {
+        let last_modified = self.last_modified();
+        self.pages(&config.prefix).into_iter()
+            .filter_map(|(uri, disk_path)| feed_entry(&uri, &disk_path, &self.page_source(&disk_path)?, &last_modified))
+            .collect()
+    }
+
+    /// Commit times of content, read again only when HEAD moves, see [LastModified]
+    fn last_modified(&self) -> Arc<LastModified>
+    {
+        let head = Repository::discover(&self.path).ok().and_then(|repo| repo.head().ok()?.target());
+        if let Some(cached) = self.last_modified.read().unwrap().as_ref().filter(|l| l.head == head)
+        {
+            return cached.clone()
+        }
+        let last_modified = Arc::new(LastModified::new(&self.path));
+        *self.last_modified.write().unwrap() = Some(last_modified.clone());
+        last_modified
+    }
+
+    /// The source of the page at disk_path, read from git objects if served, see [GitSource::read]
+    fn page_source(&self, disk_path: &str) -> Option<String>
+    {
+        match self.git
+        {
+            Some(ref git) => git.read(&git.tree_path(disk_path)).and_then(|b| String::from_utf8(b).ok()),
+            None => read_file_utf8(disk_path)
+        }
+    }
+
+    /// The html of the page served at uri from disk_path as rendered, with Markdown
+    ///   (in its layout) and server side includes, see [SiteMap::page_source]. With
+    ///   the files it depends on, e.g. the layout and includes
+    fn page_html(&self, uri: &str, disk_path: &str) -> Option<Expanded>
+    {
+        let (html, mut dependencies) = match self.markdown_options()
+        {
+            Some(ref options) if is_markdown(disk_path) =>
+            {
+                let page = render_page(disk_path, options).ok()?;
+                (page.html, page.dependencies)
+            },
+            _ if is_markdown(disk_path) => (render_markdown(split_front_matter(&self.page_source(disk_path)?).1), vec![]),
+            _ => (self.page_source(disk_path)?, vec![])
+        };
+        match self.include_options()
+        {
+            Some(ref options) =>
+            {
+                let expanded = expand(&html, uri, disk_path, options);
+                dependencies.extend(expanded.dependencies);
+                Some(Expanded { html: expanded.html, dependencies })
+            },
+            None => Some(Expanded { html, dependencies })
+        }
+    }
+
+    /// The links, media and search document of the page served at uri from disk_path,
+    ///   rendered only if not in the [PageCache]
+    fn cached_page(&self, uri: &str, disk_path: &str) -> Option<CachedPage>
+    {
+        if let Some(page) = self.page_cache.lock().unwrap().get(uri, disk_path) { return Some(page) }
+
+        let rendered = self.page_html(uri, disk_path)?;
+        let document = match self.content_config.search
+        {
+            Some(_) => self.page_source(disk_path).map(|source| search::document(uri, disk_path, &source)),
+            None => None
+        };
+        let page = CachedPage
+        {
+            links: link_references(&rendered.html),
+            media: media_references(&rendered.html),
+            document,
+            dependencies: rendered.dependencies
+        };
+        self.page_cache.lock().unwrap().insert(uri, disk_path, page.clone());
+        Some(page)
+    }
+
+    /// Html pages (uri, disk path) with uris starting with prefix. Pages served at
+    ///   several uris, e.g. /page.html and /page, are listed once at the shortest
+    fn pages(&self, prefix: &str) -> Vec<(String, String)>
+    {
+        let mut files: Vec<(String, String, MIME)> = self.contents.collect_files().into_iter()
+            .filter(|(uri, disk_path, mime)| mime.is_html() && uri.starts_with(prefix) && !disk_path.is_empty())
+            .collect();
+        files.sort_by(|a, b| a.0.len().cmp(&b.0.len()).then(a.0.cmp(&b.0)));
+
+        let mut seen: Vec<String> = vec![];
+        let mut pages = vec![];
+        for (uri, disk_path, _) in files
+        {
+            if seen.contains(&disk_path) { continue }
+            seen.push(disk_path.clone());
+            pages.push((uri, disk_path));
+        }
+        pages
     }
 
     /// Push to [SiteMap::contents] and update the (path) hash
-    pub fn push(&mut self, content: Content, uri: Option<&str>)
+    pub fn push(&mut self, mut content: Content, uri: Option<&str>)
     {
         let uri = match uri { Some(s) => s.to_string(), None => content.uri.clone() };
+        self.prepare(&mut content);
         self.contents.push(uri, content);
         self.calculate_hash();
     }
@@ -396,6 +1043,18 @@ impl SiteMap
     pub async fn refresh_all(&self)
     {
         self.contents.refresh_all().await;
+        if let Some(ref cache) = self.cache
+        {
+            let cache = cache.lock().unwrap();
+            crate::debug(format!("Resident content ({}/{} bytes):\n {}", cache.resident_bytes(), cache.budget_bytes(), cache.resident().join("\n ")), Some("CACHE"));
+        }
+    }
+
+    /// Disk paths of content bodies held in memory by the [ContentCache],
+    ///   None if there is no memory budget
+    pub fn resident(&self) -> Option<Vec<String>>
+    {
+        self.cache.as_ref().map(|cache| cache.lock().unwrap().resident())
     }
 
     /// Hash a sitemap by detected uri's
@@ -409,64 +1068,184 @@ impl SiteMap
         self.hash = self.contents.calculate_path_hash();
     }
 
+    /// Find the content served at uri
+    pub fn get(&self, uri: &str) -> Option<Arc<Mutex<Content>>>
+    {
+        self.contents.get(uri)
+    }
+
+    /// Image and language variants, if [crate::config::ContentConfig::negotiation] is configured
+    pub fn negotiation(&self) -> Option<Negotiation>
+    {
+        self.content_config.negotiation.as_ref().map(|config|
+        {
+            Negotiation { config: config.clone(), groups: group_variants(&self.contents.collect_contents(), config) }
+        })
+    }
+
     /// Returns all uris in the [SiteMap]
     pub fn collect_uris(&self) -> Vec<String>
     {
         self.contents.collect_uris()
     }
 
+    /// Images and videos by the uri of the pages referencing them, see [media_references].
+    ///   Unreferenced media are listed under the home page, unless
+    ///   [crate::config::ContentConfig::sitemap_unreferenced_media] is false
+    pub fn page_media(&self) -> BTreeMap<String, Vec<Media>>
+    {
+        let files = self.contents.collect_files();
+        let media: BTreeMap<&String, Media> = files.iter()
+            .filter(|(_, _, mime)| mime.is_image() || mime.is_video())
+            .map(|(uri, disk_path, mime)| (uri, Media { uri: uri.clone(), mime: *mime, disk_path: disk_path.clone() }))
+            .collect();
+
+        let mut pages: BTreeMap<String, Vec<Media>> = BTreeMap::new();
+        let mut referenced: Vec<&String> = vec![];
+        for (uri, disk_path, mime) in &files
+        {
+            if !mime.is_html() { continue }
+            let page = match self.cached_page(uri, disk_path) { Some(p) => p, None => continue };
+
+            let mut used: Vec<Media> = vec![];
+            for reference in page.media
+            {
+                let target = match resolve_reference(uri, &reference, &self.domain) { Some(t) => t, None => continue };
+                if let Some((target, m)) = media.get_key_value(&target)
+                {
+                    if !used.contains(m) { used.push(m.clone()); }
+                    referenced.push(target);
+                }
+            }
+            if !used.is_empty() { pages.insert(uri.clone(), used); }
+        }
+
+        if self.content_config.sitemap_unreferenced_media != Some(false)
+        {
+            let unreferenced = media.iter().filter(|(uri, _)| !referenced.contains(uri)).map(|(_, m)| m.clone());
+            pages.entry("/".to_string()).or_default().extend(unreferenced);
+        }
+        pages
+    }
+
+    /// Sitemap ```<url>``` elements for all content, indented for a ```<urlset>```
+    fn url_elements(&self) -> Vec<String>
+    {
+        let alternates = match self.negotiation()
+        {
+            Some(negotiation) => negotiation.alternates(),
+            None => BTreeMap::new()
+        };
+        let context = UrlContext
+        {
+            domain: &self.domain,
+            alternates: &alternates,
+            rules: self.content_config.sitemap_rules.as_deref().unwrap_or_default(),
+            last_modified: &self.last_modified(),
+            media: &self.page_media()
+        };
+
+        self.contents.url_elements(&context).into_iter()
+            .map(|url| url.lines().map(|line| format!("    {}", line)).collect::<Vec<String>>().join("\n"))
+            .collect()
+    }
+
     /// Implements writing to an xml conforming to <https://www.sitemaps.org/protocol.html>
-    ///  with <http://www.google.com/schemas/sitemap-image/1.1> and <http://www.google.com/schemas/sitemap-video/1.1>
-    pub fn to_xml(&self) -> Vec<u8>
+    ///  with <http://www.google.com/schemas/sitemap-image/1.1> and <http://www.google.com/schemas/sitemap-video/1.1>.
+    ///  Returns file names and xml, sitemap.xml first. Above [MAX_SITEMAP_URLS] or [MAX_SITEMAP_BYTES]
+    ///  sitemap.xml is an index of sitemap-1.xml, sitemap-2.xml, ...
+    pub fn sitemaps(&self) -> Vec<(String, Vec<u8>)>
     {
-        let mut buffer = Vec::new();
-        let mut writer = Writer::new_with_indent(&mut buffer, b' ', 4);
+        let urls = self.url_elements();
+        let mut namespaces = vec!
+        [
+            ("xmlns", "http://www.sitemaps.org/schemas/sitemap/0.9"),
+            ("xmlns:image", "http://www.google.com/schemas/sitemap-image/1.1"),
+            ("xmlns:video", "http://www.google.com/schemas/sitemap-video/1.1")
+        ];
+        if urls.iter().any(|url| url.contains("<xhtml:link"))
+        {
+            namespaces.push(("xmlns:xhtml", "http://www.w3.org/1999/xhtml"));
+        }
 
-        match writer.write_event(Event::Text(BytesText::from_escaped("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n")))
+        let parts = split_urls(urls, MAX_SITEMAP_URLS, MAX_SITEMAP_BYTES - SITEMAP_OVERHEAD_BYTES);
+        if parts.len() <= 1
         {
-            Ok(_) => (),
-            Err(e) => {crate::debug(format!("Error {} writing content of sitemap to xml", e), None)}
+            return vec![("sitemap.xml".to_string(), urlset(parts.first().map_or(&[], |p| p.as_slice()), &namespaces))]
         }
 
-        match writer.create_element("urlset")
-            .with_attributes
-            (
-                vec!
-                [
-                    ("xmlns", "http://www.sitemaps.org/schemas/sitemap/0.9"),
-                    ("xmlns:image", "http://www.google.com/schemas/sitemap-image/1.1"),
-                    ("xmlns:video", "http://www.google.com/schemas/sitemap-video/1.1")
-                ].into_iter()
-            )
-            .write_inner_content::<_, Error>
-            (|writer|
-            {
-                let mut content_buffer = self.contents.to_xml(self.domain.clone());
+        let names: Vec<String> = (1..=parts.len()).map(|i| format!("sitemap-{}.xml", i)).collect();
+        let mut sitemaps = vec![("sitemap.xml".to_string(), sitemap_index(&self.domain, &names))];
+        for (name, part) in names.into_iter().zip(parts)
+        {
+            sitemaps.push((name, urlset(&part, &namespaces)));
+        }
+        sitemaps
+    }
 
-                if content_buffer.len() > 0
+    /// The sitemap.xml, see [SiteMap::sitemaps]
+    pub fn to_xml(&self) -> Vec<u8>
+    {
+        self.sitemaps().swap_remove(0).1
+    }
+}
+
+/// Write a ```<urlset>``` of indented ```<url>``` elements
+fn urlset(urls: &[String], namespaces: &[(&str, &str)]) -> Vec<u8>
+{
+    let mut buffer = Vec::new();
+    let mut writer = Writer::new_with_indent(&mut buffer, b' ', 4);
+
+    match writer.write_event(Event::Text(BytesText::from_escaped("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n")))
+        .and_then(|_| writer.create_element("urlset")
+            .with_attributes(namespaces.iter().copied())
+            .write_inner_content::<_, Error>(|writer|
+            {
+                if !urls.is_empty()
                 {
-                    let mut content = "\n".as_bytes().to_vec();
-                    content.append(&mut content_buffer);
-                    content_buffer = content;
+                    writer.write_event(Event::Text(BytesText::from_escaped(format!("\n{}\n", urls.join("\n")))))?;
                 }
+                Ok(())
+            })
+            .map(|_| ()))
+    {
+        Ok(_) => (),
+        Err(e) => {crate::debug(format!("Error {} writing content of sitemap to xml", e), None)}
+    }
 
-                let mut str_content = String::from_utf8(content_buffer)?;
-                let lines = str_content.matches("\n").count();
-                if lines > 1
+    buffer
+}
+
+/// Write a ```<sitemapindex>``` of sitemap file names
+fn sitemap_index(domain: &str, names: &[String]) -> Vec<u8>
+{
+    let mut buffer = Vec::new();
+    let mut writer = Writer::new_with_indent(&mut buffer, b' ', 4);
+    let today = lastmod(SystemTime::now());
+
+    match writer.write_event(Event::Text(BytesText::from_escaped("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n")))
+        .and_then(|_| writer.create_element("sitemapindex")
+            .with_attribute(("xmlns", "http://www.sitemaps.org/schemas/sitemap/0.9"))
+            .write_inner_content::<_, Error>(|writer|
+            {
+                for name in names
                 {
-                    str_content = str_content.replacen("\n", "\n    ", lines-1);
+                    writer.create_element("sitemap").write_inner_content::<_, Error>(|writer|
+                    {
+                        writer.create_element("loc").write_text_content(BytesText::new(&format!("{}/{}", domain, name)))?;
+                        writer.create_element("lastmod").write_text_content(BytesText::new(&today))?;
+                        Ok(())
+                    })?;
                 }
-
-                writer.write_event(Event::Text(BytesText::from_escaped(str_content)))?;
                 Ok(())
             })
-        {
-            Ok(_) => (),
-            Err(e) => {crate::debug(format!("Error {} writing content of sitemap to xml", e), None)}
-        }
-
-        buffer
+            .map(|_| ()))
+    {
+        Ok(_) => (),
+        Err(e) => {crate::debug(format!("Error {} writing sitemap index to xml", e), None)}
     }
+
+    buffer
 }
 
 impl Into<Router> for SiteMap
@@ -477,19 +1256,114 @@ impl Into<Router> for SiteMap
         {
             Some(config) =>
             {
+                // watched content is refreshed by the watcher, not on access
                 match config.content.static_content
                 {
-                    Some(b) => b,
-                    None => false
+                    Some(b) => b || config.content.watch.is_some_and(|w| w),
+                    None => config.content.watch.is_some_and(|w| w)
                 }
             },
             None => false
         };
 
-        self.contents.route(static_router)
+        let negotiation = self.negotiation().map(Arc::new);
+        // generated files, and search, take precedence over those on disk
+        let generated = self.generated.uris();
+        let search_uri = self.content_config.search.as_ref().map(|s| s.uri.clone().unwrap_or(search::DEFAULT_URI.to_string()));
+        let skip: Vec<String> = generated.iter().cloned().chain(search_uri.clone()).collect();
+        let mut router = self.contents.route(static_router, &negotiation, &skip);
+        for uri in &generated
+        {
+            if Some(uri) == search_uri.as_ref() { continue }
+            router = router.route(uri, self.generated.route(uri));
+        }
+        if let (Some(uri), Some(config)) = (search_uri, self.content_config.search.clone())
+        {
+            router = router.route(&uri, search_route(self.search.clone(), config, &self.domain));
+        }
+
+        if let Some(negotiation) = negotiation
+        {
+            let uris = self.collect_uris();
+            let mut routed = vec![];
+            for (uri, variants) in &negotiation.groups
+            {
+                // e.g. /page.html with only page.en.html and page.fr.html on disk
+                if !uris.contains(uri) && !routed.contains(uri)
+                {
+                    let fallback = match negotiation.config.default_language
+                    {
+                        Some(ref l) => variants.iter().find(|v| v.kind == VariantKind::Language(l.to_lowercase())).unwrap_or(&variants[0]),
+                        None => &variants[0]
+                    };
+                    router = router.route(uri, negotiated_route(uri.clone(), fallback.content.clone(), static_router, Some(negotiation.clone())));
+                    routed.push(uri.clone());
+                }
+
+                for variant in variants
+                {
+                    let language_uri = negotiation.language_uri(variant);
+                    if !uris.contains(&language_uri) && !routed.contains(&language_uri)
+                    {
+                        router = router.route(&language_uri, negotiated_route(language_uri.clone(), variant.content.clone(), static_router, None));
+                        routed.push(language_uri);
+                    }
+                }
+            }
+        }
+
+        router
     }
 }
 
+/// A route serving content, or a variant chosen by [Negotiation::select]
+fn negotiated_route(uri: String, content: Arc<Mutex<Content>>, static_router: bool, negotiation: Option<Arc<Negotiation>>) -> axum::routing::MethodRouter
+{
+    get(move |headers: HeaderMap| async move
+    {
+        match negotiation.as_ref().and_then(|n| n.select(&uri, &headers))
+        {
+            Some(selection) =>
+            {
+                let content = selection.content.unwrap_or(content);
+                let mut response = serve(content, static_router, &headers).await;
+                response.headers_mut().append("vary", selection.vary.parse().unwrap());
+                response
+            },
+            None => serve(content, static_router, &headers).await
+        }
+    })
+}
+
+/// Serve content, refreshing it if needed, see [ContentTree::route]
+async fn serve(content: Arc<Mutex<Content>>, static_router: bool, headers: &HeaderMap) -> Response
+{
+    let mut content = content.lock().await;
+    let never_loaded = content.last_refreshed() == SystemTime::UNIX_EPOCH;
+    // a change of served git commit is applied at once
+    if never_loaded || content.source_changed() || (!static_router && content.server_cache_expired() && content.is_stale())
+    {
+        content.refresh();
+        crate::debug(format!("Refresh called on Content {}", content.get_uri()), None);
+    }
+    if content.is_draft() || content.is_absent()
+    {
+        return StatusCode::NOT_FOUND.into_response()
+    }
+    let accept_encoding = headers.get("accept-encoding").and_then(|v| v.to_str().ok());
+    match content.negotiate_encoding(accept_encoding)
+    {
+        Some(encoding) => content.encoded_response(encoding),
+        None => content.clone().into_response()
+    }
+}
+
+/// A uri with its extension removed, e.g. /a/b.html to /a/b
+pub fn short_uri(uri: &str) -> String
+{
+    Regex::new(r"\.\S+$").unwrap().replacen(uri, 1, "").to_string()
+}
+
 /// Format for lastmod (t) in an xml sitemap
 pub fn lastmod(t: SystemTime) -> String
 {
@@ -0,0 +1,72 @@
+use regex::Regex;
+
+use crate::{config::{SnippetConfig, SnippetPosition}, filesystem::file::read_file_utf8, util::matches_one};
+
+/// If the snippet should be injected into the page served at uri
+pub fn applies_to(snippet: &SnippetConfig, uri: &str) -> bool
+{
+    match snippet.paths
+    {
+        Some(ref paths) => matches_one(uri, paths),
+        None => true
+    }
+}
+
+/// The snippet's html, from [SnippetConfig::html] or read from [SnippetConfig::file]
+pub fn snippet_html(snippet: &SnippetConfig) -> Option<String>
+{
+    match (&snippet.html, &snippet.file)
+    {
+        (Some(html), _) => Some(html.clone()),
+        (None, Some(file)) => read_file_utf8(file),
+        (None, None) => None
+    }
+}
+
+/// Inject a snippet into html at a [SnippetPosition], html
+///   without the position's tag is returned unchanged,
+///   except for [SnippetPosition::BodyEnd] which appends
+pub fn inject(html: &str, position: SnippetPosition, snippet: &str) -> String
+{
+    let (re, after) = match position
+    {
+        SnippetPosition::HeadStart => (r"(?i)<head(\s[^>]*)?>", true),
+        SnippetPosition::HeadEnd => (r"(?i)</head\s*>", false),
+        SnippetPosition::BodyEnd => (r"(?i)</body\s*>", false)
+    };
+
+    let found = match position
+    {
+        SnippetPosition::BodyEnd => Regex::new(re).unwrap().find_iter(html).last(),
+        _ => Regex::new(re).unwrap().find(html)
+    };
+
+    match found
+    {
+        Some(m) =>
+        {
+            let at = if after { m.end() } else { m.start() };
+            format!("{}{}{}", &html[..at], snippet, &html[at..])
+        },
+        None => match position
+        {
+            SnippetPosition::BodyEnd => format!("{}{}", html, snippet),
+            _ =>
+            {
+                crate::debug(format!("No {:?} to inject snippet into", position), None);
+                html.to_string()
+            }
+        }
+    }
+}
+
+/// Insert at the start of the document, but after any doctype, so it
+///   cannot change how the page is rendered
+pub fn inject_after_doctype(html: &str, snippet: &str) -> String
+{
+    match Regex::new(r"^\s*(?i)<!DOCTYPE[^>]*>\s*").unwrap().find(html)
+    {
+        Some(m) => format!("{}{}{}", &html[..m.end()], snippet, &html[m.end()..]),
+        None => format!("{}{}", snippet, html)
+    }
+}
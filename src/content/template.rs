@@ -0,0 +1,166 @@
+use std::path::{Path, PathBuf};
+
+use git2::Repository;
+use openssl::sha::Sha256;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::is_config_file, filesystem::file::{file_hash, read_file_utf8}, integrations::git::head_info};
+
+use super::sitemap::lastmod;
+
+/// Includes are expanded at most this deep, deeper (or cyclic) includes are errors
+pub const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// Server side includes and variables for html [super::Content]
+///
+/// - ```<!--#include "partials/nav.html"-->``` (or ```file="..."```, ```virtual="..."```) is replaced
+///   by that file, relative to the content path. Includes may be nested, but not reach outside
+///   the content path or include config files, see [content_file]
+/// - ```<!--#echo var="name"-->``` is replaced by a variable:
+///   - ```domain```: the site domain, [crate::config::Config::domain]
+///   - ```commit```: the short hash of the content's git HEAD, see [crate::integrations::git::HeadInfo]
+///   - ```uri```: the uri the page is served at
+///   - ```lastmod```: the page's last modified date
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IncludeOptions
+{
+    pub content_path: String,
+    pub domain: String
+}
+
+/// A page with its includes expanded, and the disk paths it depends on
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expanded
+{
+    pub html: String,
+    pub dependencies: Vec<String>
+}
+
+/// Expand the includes and variables in a page served at uri from disk_path
+pub fn expand(html: &str, uri: &str, disk_path: &str, options: &IncludeOptions) -> Expanded
+{
+    let mut dependencies = vec![];
+    let mut stack = vec![disk_path.to_string()];
+    let html = expand_includes(html, options, &mut stack, &mut dependencies);
+    Expanded { html: expand_variables(&html, uri, disk_path, options), dependencies }
+}
+
+fn expand_includes(html: &str, options: &IncludeOptions, stack: &mut Vec<String>, dependencies: &mut Vec<String>) -> String
+{
+    let re = Regex::new(r#"<!--#include\s+(?:(?:file|virtual)=)?"([^"]+)"\s*-->"#).unwrap();
+    re.replace_all(html, |caps: &Captures|
+    {
+        let path = match content_file(&options.content_path, &caps[1])
+        {
+            Some(path) => path,
+            None =>
+            {
+                crate::debug(format!("Refusing to include {} in {}, outside the content path", &caps[1], stack.last().unwrap()), None);
+                return format!("<!-- error including {} -->", &caps[1])
+            }
+        };
+        if !dependencies.contains(&path) { dependencies.push(path.clone()); }
+
+        if stack.contains(&path) || stack.len() > MAX_INCLUDE_DEPTH
+        {
+            crate::debug(format!("Include of {} from {} is cyclic or too deep", path, stack.join(" -> ")), None);
+            return format!("<!-- error including {} -->", &caps[1])
+        }
+
+        match read_file_utf8(&path)
+        {
+            Some(partial) =>
+            {
+                stack.push(path);
+                let expanded = expand_includes(&partial, options, stack, dependencies);
+                stack.pop();
+                expanded
+            },
+            None =>
+            {
+                crate::debug(format!("Could not include {} in {}", path, stack.last().unwrap()), None);
+                format!("<!-- error including {} -->", &caps[1])
+            }
+        }
+    }).to_string()
+}
+
+fn expand_variables(html: &str, uri: &str, disk_path: &str, options: &IncludeOptions) -> String
+{
+    let re = Regex::new(r#"<!--#echo\s+var="(\w+)"\s*-->"#).unwrap();
+    if !re.is_match(html) { return html.to_string() }
+
+    re.replace_all(html, |caps: &Captures|
+    {
+        match &caps[1]
+        {
+            "domain" => options.domain.clone(),
+            "commit" => commit(&options.content_path).unwrap_or_default(),
+            "uri" => uri.to_string(),
+            "lastmod" => match std::fs::metadata(disk_path).and_then(|m| m.modified())
+            {
+                Ok(t) => lastmod(t),
+                Err(_) => String::new()
+            },
+            _ => caps[0].to_string()
+        }
+    }).to_string()
+}
+
+/// The path of file relative to content_path, e.g. an include or layout. None if it
+///   resolves outside content_path (by ```..``` or symlinks), or is a config file
+pub fn content_file(content_path: &str, file: &str) -> Option<String>
+{
+    let path = format!("{}/{}", content_path.trim_end_matches('/'), file.trim_start_matches('/'));
+    let root = std::fs::canonicalize(content_path).ok()?;
+    let resolved = resolve(&path)?;
+    if !resolved.starts_with(&root) || is_config_file(&resolved.to_string_lossy())
+    {
+        return None
+    }
+    Some(path)
+}
+
+/// The canonical path, resolved through its directory if the file does not exist (yet)
+fn resolve(path: &str) -> Option<PathBuf>
+{
+    let path = Path::new(path);
+    if let Ok(canonical) = std::fs::canonicalize(path) { return Some(canonical) }
+    match (path.parent().and_then(|p| std::fs::canonicalize(p).ok()), path.file_name())
+    {
+        (Some(parent), Some(name)) if name != ".." => Some(parent.join(name)),
+        _ => None
+    }
+}
+
+/// The short hash of HEAD for a git repository containing path
+pub fn commit(path: &str) -> Option<String>
+{
+    match Repository::discover(path)
+    {
+        Ok(repo) => head_info(&repo).map(|info| info.hash),
+        Err(_) => None
+    }
+}
+
+/// A combined hash of each dependency's content, to detect changes
+pub fn hash_dependencies(dependencies: &[String]) -> Vec<u8>
+{
+    let mut sha = Sha256::new();
+    for dependency in dependencies
+    {
+        sha.update(&file_hash(dependency));
+    }
+    sha.finish().to_vec()
+}
+
+/// If paths a and b are the same file, or equal as paths if either does not exist
+pub fn same_path(a: &str, b: &str) -> bool
+{
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b))
+    {
+        (Ok(a), Ok(b)) => a == b,
+        _ => Path::new(a) == Path::new(b)
+    }
+}
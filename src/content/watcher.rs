@@ -0,0 +1,97 @@
+use core::fmt;
+use std::{path::Path, time::Duration};
+
+use notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+/// Events closer together than this are reported as one batch
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone)]
+pub struct WatchError
+{
+    pub why: String
+}
+
+impl fmt::Display for WatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.why)
+    }
+}
+
+impl From<notify::Error> for WatchError
+{
+    fn from(value: notify::Error) -> Self
+    {
+        WatchError
+        {
+            why: format!("notify::Error {}", value)
+        }
+    }
+}
+
+/// Watches a content path recursively (inotify on Linux) for created,
+///   modified and removed files. Debounced batches of changed paths are
+///   returned by [ContentWatcher::next], for [super::sitemap::SiteMap::apply_changes]
+pub struct ContentWatcher
+{
+    _debouncer: Debouncer<RecommendedWatcher>,
+    changes: UnboundedReceiver<Vec<String>>
+}
+
+impl ContentWatcher
+{
+    pub fn new(path: &str, debounce: Duration) -> Result<ContentWatcher, WatchError>
+    {
+        let root = std::fs::canonicalize(path).map_err(|e| WatchError { why: format!("Cannot watch {}, {}", path, e) })?;
+        let content_path = path.to_string();
+        let (sender, changes) = unbounded_channel();
+
+        let mut debouncer = new_debouncer(debounce, move |result: DebounceEventResult|
+        {
+            match result
+            {
+                Ok(events) =>
+                {
+                    let paths: Vec<String> = events.into_iter()
+                        .filter_map(|e| to_content_path(&e.path, &root, &content_path))
+                        .collect();
+                    if !paths.is_empty()
+                    {
+                        crate::debug(format!("Content changed:\n {}", paths.join("\n ")), Some("WATCH"));
+                        let _ = sender.send(paths);
+                    }
+                },
+                Err(e) => crate::debug(format!("Error watching content {}", e), Some("WATCH"))
+            }
+        })?;
+
+        debouncer.watcher().watch(Path::new(path), RecursiveMode::Recursive)?;
+        crate::debug(format!("Watching {} for changes", path), Some("WATCH"));
+
+        Ok(ContentWatcher { _debouncer: debouncer, changes })
+    }
+
+    /// Wait for the next batch of changed paths, None if the watcher stopped
+    pub async fn next(&mut self) -> Option<Vec<String>>
+    {
+        self.changes.recv().await
+    }
+}
+
+/// Convert an absolute event path to a path under content_path, as
+///   listed by [super::get_content]
+pub fn to_content_path(event_path: &Path, root: &Path, content_path: &str) -> Option<String>
+{
+    match event_path.strip_prefix(root)
+    {
+        Ok(relative) =>
+        {
+            let relative = relative.to_str()?.replace('\\', "/");
+            if relative.is_empty() { return None }
+            Some(format!("{}/{}", content_path, relative))
+        },
+        Err(_) => None
+    }
+}
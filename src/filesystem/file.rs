@@ -1,6 +1,6 @@
 use std::{fmt, fs, io::{Read, Write}, time::SystemTime};
 
-use crate::util::hash;
+use openssl::sha::Sha256;
 
 #[derive(Debug, Clone)]
 pub struct FileError
@@ -85,11 +85,34 @@ pub fn read_file_bytes(path: &str) -> Option<Vec<u8>>
     }
 }
 
+/// Sha256 of a file, read in chunks so large files are
+///  not held in memory. Empty if the file cannot be read
 pub fn file_hash(path: &str) -> Vec<u8>
 {
-    match read_file_bytes(path)
+    let mut file = match fs::File::open(path)
     {
-        Some(d) => hash(d),
-        None => vec![]
+        Ok(f) => f,
+        Err(why) =>
+        {
+            crate::debug(format!("error hashing file, {}", why), None);
+            return vec![]
+        }
+    };
+
+    let mut sha = Sha256::new();
+    let mut chunk = vec![0u8; 64*1024];
+    loop
+    {
+        match file.read(&mut chunk)
+        {
+            Ok(0) => break,
+            Ok(n) => sha.update(&chunk[0..n]),
+            Err(why) =>
+            {
+                crate::debug(format!("error hashing file, {}", why), None);
+                return vec![]
+            }
+        }
     }
+    sha.finish().to_vec()
 }
\ No newline at end of file
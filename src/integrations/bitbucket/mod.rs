@@ -0,0 +1,77 @@
+use axum::{async_trait, body::Bytes, http::HeaderMap};
+use reqwest::StatusCode;
+
+use super::forge::{parse_body, ForgeWebhook};
+
+/// Bitbucket Cloud (repo:push) and Bitbucket Server (repo:refs_changed) push
+///   webhooks, signed in x-hub-signature
+pub struct Bitbucket;
+
+#[async_trait]
+impl ForgeWebhook for Bitbucket
+{
+    const NAME: &'static str = "BITBUCKET";
+
+    /// x-event-key is repo:push or repo:refs_changed
+    async fn is_push(headers: &HeaderMap) -> StatusCode
+    {
+        let event = match headers.get("x-event-key")
+        {
+            Some(e) => e,
+            None => return StatusCode::CONTINUE
+        };
+        match event.to_str()
+        {
+            Ok("repo:push") | Ok("repo:refs_changed") =>
+            {
+                crate::debug("Recieving bitbucket push event".to_string(), Some(Self::NAME));
+                StatusCode::OK
+            },
+            Ok(_) => StatusCode::CONTINUE,
+            Err(e) =>
+            {
+                crate::debug(format!("Invalid utf8 in x-event-key, {}", e), Some(Self::NAME));
+                StatusCode::BAD_REQUEST
+            }
+        }
+    }
+
+    /// A url in json path ["repository"]["links"], the html link (Cloud, which
+    ///   omits .git) or the clone and self links (Server)
+    fn is_watched_repo(body: &Bytes, url: &str) -> bool
+    {
+        let parsed = match parse_body(body, Self::NAME)
+        {
+            Some(p) => p,
+            None => return false
+        };
+        let links = &parsed["repository"]["links"];
+        let mut urls: Vec<&str> = links["html"]["href"].as_str().into_iter().collect();
+        for key in ["clone", "self"]
+        {
+            urls.extend(links[key].as_array().into_iter().flatten().filter_map(|l| l["href"].as_str()));
+        }
+        urls.iter().any(|u| *u == url || *u == url.trim_end_matches(".git"))
+    }
+
+    /// A change in json path ["push"]["changes"] to the branch (Cloud), or
+    ///   in ["changes"] to refs/heads/branch (Server)
+    fn is_tracked_branch(body: &Bytes, branch: &str) -> bool
+    {
+        let parsed = match parse_body(body, Self::NAME)
+        {
+            Some(p) => p,
+            None => return false
+        };
+        let cloud = parsed["push"]["changes"].as_array().into_iter().flatten()
+            .any(|c| c["new"]["type"].as_str() == Some("branch") && c["new"]["name"].as_str() == Some(branch));
+        let server = parsed["changes"].as_array().into_iter().flatten()
+            .any(|c| c["ref"]["id"].as_str() == Some(&format!("refs/heads/{}", branch)));
+        cloud || server
+    }
+
+    fn is_authentic(headers: &HeaderMap, token: String, body: &Bytes) -> StatusCode
+    {
+        super::is_authentic(headers, "x-hub-signature", token, body)
+    }
+}
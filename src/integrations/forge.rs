@@ -0,0 +1,128 @@
+use std::{sync::Arc, time::SystemTime};
+
+use axum::{async_trait, body::Bytes, extract::State, http::{HeaderMap, Request}, middleware::Next, response::{IntoResponse, Response}};
+use reqwest::StatusCode;
+use tokio::sync::Mutex;
+
+use crate::{config::{Config, CONFIG_PATH}, util::{extract_bytes, strip_control_characters}};
+
+use super::git::refresh::GitRefreshTask;
+
+/// Push webhooks from a git forge, e.g. [crate::integrations::github::Github].
+///   Authentic pushes to the watched repository and branch are pulled by [handle_push]
+#[async_trait]
+pub trait ForgeWebhook
+{
+    /// The tag for debug messages
+    const NAME: &'static str;
+
+    /// [StatusCode::OK] if the headers are from a push event of this forge,
+    ///   [StatusCode::CONTINUE] if they are not and an error if malformed
+    async fn is_push(headers: &HeaderMap) -> StatusCode;
+
+    /// Check the pushed repository in body matches the url
+    fn is_watched_repo(body: &Bytes, url: &str) -> bool;
+
+    /// Check the pushed ref in body is refs/heads/branch
+    fn is_tracked_branch(body: &Bytes, branch: &str) -> bool;
+
+    /// [StatusCode::ACCEPTED] if the request is signed, or carries, token
+    fn is_authentic(headers: &HeaderMap, token: String, body: &Bytes) -> StatusCode;
+}
+
+/// Pull authentic push events of W, see [ForgeWebhook], if [crate::config::GitConfig]
+///   and its remote_webhook_token are not None. Other requests are passed on
+pub async fn filter_push<W: ForgeWebhook>
+(
+    State(repo_lock): State<Arc<Mutex<SystemTime>>>,
+    headers: HeaderMap,
+    request: Request<axum::body::Body>,
+    next: Next
+) -> Result<Response, StatusCode>
+{
+    let config = Config::load_or_default(CONFIG_PATH);
+    let (remote, branch, token) = match config.git
+    {
+        Some(git) => match git.remote_webhook_token
+        {
+            Some(token) => (git.remote, git.branch, token),
+            None => return Ok(next.run(request).await)
+        },
+        None => return Ok(next.run(request).await)
+    };
+    match W::is_push(&headers).await
+    {
+        StatusCode::CONTINUE => Ok(next.run(request).await),
+        StatusCode::OK =>
+        {
+            Ok(handle_push::<W>(repo_lock, headers, request, &remote, &branch, token).await.into_response())
+        },
+        status => Ok(status.into_response())
+    }
+}
+
+/// Checks the push event is authentic, matches remote and pushes
+///   to branch. If so a pull is started, see [GitRefreshTask::refresh], and
+///   [StatusCode::OK] returned without waiting for it
+pub async fn handle_push<W: ForgeWebhook>
+(
+    repo_lock: Arc<Mutex<SystemTime>>,
+    headers: HeaderMap,
+    request: Request<axum::body::Body>,
+    remote: &str,
+    branch: &str,
+    token: String
+) -> StatusCode
+{
+    let bytes = match extract_bytes(request).await
+    {
+        Ok(b) => b,
+        Err(_) => return StatusCode::BAD_REQUEST
+    };
+
+    if !W::is_watched_repo(&bytes, remote) || !W::is_tracked_branch(&bytes, branch)
+    {
+        return StatusCode::OK;
+    }
+
+    match W::is_authentic(&headers, token, &bytes)
+    {
+        StatusCode::ACCEPTED =>
+        {
+            crate::debug(format!("{} push event is authentic", W::NAME), Some(W::NAME));
+            // respond before the forge's delivery times out, pulls and builds may be slow
+            tokio::spawn(async move
+            {
+                let mut lock = repo_lock.lock().await;
+                let config = Config::load_or_default(CONFIG_PATH);
+                GitRefreshTask::refresh(&config).await;
+                *lock = SystemTime::now();
+            });
+            StatusCode::OK
+        },
+        status =>
+        {
+            crate::debug(format!("Authentication error: {}", status), Some(W::NAME));
+            status
+        }
+    }
+}
+
+/// Parse a json webhook body
+pub fn parse_body(body: &Bytes, tag: &str) -> Option<serde_json::Value>
+{
+    let utf8_body = match std::str::from_utf8(body)
+    {
+        Ok(s) => s.to_owned(),
+        Err(e) => { crate::debug(format!("Error parsing body: {}", e), Some(tag)); return None }
+    };
+    match serde_json::from_str(&strip_control_characters(utf8_body))
+    {
+        Ok(d) => Some(d),
+        Err(e) =>
+        {
+            crate::debug(format!("Error parsing body: {}", e), Some(tag));
+            None
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use core::fmt;
+use std::{io::Read, path::Path, process::{Child, Command, Stdio}, thread, time::{Duration, Instant}};
+
+use git2::Repository;
+
+use crate::{config::{BuildConfig, Config}, util::format_elapsed};
+
+pub const DEFAULT_PATH: &str = "source";
+pub const DEFAULT_TIMEOUT_SECONDS: u64 = 600;
+/// Lines of build output included in a failure notification
+pub const NOTIFY_LINES: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct BuildError
+{
+    pub why: String,
+    /// The captured stdout and stderr, if the command ran
+    pub output: String
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.why)
+    }
+}
+
+impl BuildError
+{
+    /// The last lines of the build's output
+    pub fn tail(&self, lines: usize) -> String
+    {
+        let all: Vec<&str> = self.output.lines().collect();
+        all[all.len().saturating_sub(lines)..].join("\n")
+    }
+}
+
+/// Where the repository is cloned when building, see [BuildConfig::path],
+///   otherwise the content path
+pub fn checkout_path(config: &Config) -> String
+{
+    match config.git.as_ref().and_then(|g| g.build.as_ref())
+    {
+        Some(build) => build.path.clone().unwrap_or(DEFAULT_PATH.to_string()),
+        None => config.content.path.clone()
+    }
+}
+
+/// Run [BuildConfig::command] in the clone at checkout, returning its stdout and stderr.
+///   The command is killed if it exceeds [BuildConfig::timeout_seconds]
+pub fn run_build(config: &BuildConfig, checkout: &str) -> Result<String, BuildError>
+{
+    let directory = match config.working_directory
+    {
+        Some(ref dir) => format!("{}/{}", checkout.trim_end_matches('/'), dir),
+        None => checkout.to_string()
+    };
+
+    let mut command = if cfg!(windows)
+    {
+        let mut c = Command::new("cmd");
+        c.args(["/C", &config.command]);
+        c
+    }
+    else
+    {
+        let mut c = Command::new("sh");
+        c.args(["-c", &config.command]);
+        c
+    };
+    command.current_dir(&directory)
+        .envs(config.env.clone().unwrap_or_default())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // a process group so a timed out build's children are killed too
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+    let tic = Instant::now();
+    let mut child = match command.spawn()
+    {
+        Ok(c) => c,
+        Err(e) => return Err(BuildError { why: format!("Could not run {} in {}, {}", config.command, directory, e), output: String::new() })
+    };
+
+    // read while running so a full pipe cannot block the build
+    let mut stdout = child.stdout.take().unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    let out = thread::spawn(move || { let mut s = String::new(); let _ = stdout.read_to_string(&mut s); s });
+    let err = thread::spawn(move || { let mut s = String::new(); let _ = stderr.read_to_string(&mut s); s });
+
+    let timeout = Duration::from_secs(config.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS));
+    let status = loop
+    {
+        match child.try_wait()
+        {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if tic.elapsed() > timeout =>
+            {
+                kill_tree(&mut child);
+                let _ = child.wait();
+                break None
+            },
+            Ok(None) => thread::sleep(Duration::from_millis(50)),
+            Err(e) => return Err(BuildError { why: format!("Error waiting for {}, {}", config.command, e), output: String::new() })
+        }
+    };
+
+    let output = format!("{}{}", out.join().unwrap_or_default(), err.join().unwrap_or_default());
+    crate::debug(format!("Build {} took {}:\n{}", config.command, format_elapsed(tic), output), Some("BUILD"));
+
+    match status
+    {
+        Some(status) if status.success() => Ok(output),
+        Some(status) => Err(BuildError { why: format!("{} failed with {}", config.command, status), output }),
+        None => Err(BuildError { why: format!("{} timed out after {}s", config.command, timeout.as_secs()), output })
+    }
+}
+
+/// Kill the child and the processes it started
+fn kill_tree(child: &mut Child)
+{
+    let pid = child.id().to_string();
+    let killed = if cfg!(windows)
+    {
+        Command::new("taskkill").args(["/T", "/F", "/PID", &pid]).status()
+    }
+    else
+    {
+        Command::new("kill").args(["-s", "KILL", "--", &format!("-{}", pid)]).status()
+    };
+    if !killed.is_ok_and(|s| s.success())
+    {
+        let _ = child.kill();
+    }
+}
+
+/// Where the commit of the last successful build of a checkout is recorded, inside
+///   its .git directory so it is never published
+fn built_record(checkout: &str) -> String
+{
+    format!("{}/.git/busser_built", checkout.trim_end_matches('/'))
+}
+
+/// The commit of the last successful build of checkout
+pub fn built_commit(checkout: &str) -> Option<String>
+{
+    std::fs::read_to_string(built_record(checkout)).ok().map(|c| c.trim().to_string())
+}
+
+fn head_commit(checkout: &str) -> Option<String>
+{
+    let repo = Repository::open(checkout).ok()?;
+    let head = repo.head().ok()?.target()?;
+    Some(head.to_string())
+}
+
+/// If [crate::config::GitConfig::build] is configured and the head of checkout has not
+///   been built, e.g. a new commit or one whose build failed, or nothing is published
+pub fn needs_build(config: &Config, checkout: &str) -> bool
+{
+    if config.git.as_ref().and_then(|g| g.build.as_ref()).is_none() { return false }
+    let head = head_commit(checkout);
+    !Path::new(&config.content.path).exists() || head.is_none() || head != built_commit(checkout)
+}
+
+/// The build output directory within checkout
+pub fn output_path(config: &BuildConfig, checkout: &str) -> String
+{
+    format!("{}/{}", checkout.trim_end_matches('/'), config.output.trim_matches('/'))
+}
+
+/// Build the clone at checkout and, if the build succeeds, replace the content at
+///   [crate::config::ContentConfig::path] with the output. The previous content is
+///   served until the output is copied
+pub fn build_and_publish(config: &Config, checkout: &str) -> Result<String, BuildError>
+{
+    let build = match config.git.as_ref().and_then(|g| g.build.as_ref())
+    {
+        Some(b) => b,
+        None => return Ok(String::new())
+    };
+    let log = run_build(build, checkout)?;
+    let output = output_path(build, checkout);
+    if !Path::new(&output).is_dir()
+    {
+        return Err(BuildError { why: format!("Build output {} does not exist", output), output: log })
+    }
+    match publish(&output, &config.content.path)
+    {
+        Ok(_) =>
+        {
+            if let Some(commit) = head_commit(checkout)
+            {
+                if let Err(e) = std::fs::write(built_record(checkout), commit)
+                {
+                    crate::debug(format!("Could not record the built commit of {}, {}", checkout, e), Some("BUILD"));
+                }
+            }
+            Ok(log)
+        },
+        Err(e) => Err(BuildError { why: format!("Could not publish {} to {}, {}", output, config.content.path, e), output: log })
+    }
+}
+
+/// Copy output to content_path, swapping it in by renames once copied
+pub fn publish(output: &str, content_path: &str) -> Result<(), std::io::Error>
+{
+    let content_path = content_path.trim_end_matches('/');
+    let staged = format!("{}.build", content_path);
+    let previous = format!("{}.previous", content_path);
+    for dir in [&staged, &previous]
+    {
+        if Path::new(dir).exists() { std::fs::remove_dir_all(dir)?; }
+    }
+
+    copy_dir(Path::new(output), Path::new(&staged))?;
+    if Path::new(content_path).exists()
+    {
+        std::fs::rename(content_path, &previous)?;
+    }
+    std::fs::rename(&staged, content_path)?;
+    let _ = std::fs::remove_dir_all(&previous);
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), std::io::Error>
+{
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)?
+    {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir()
+        {
+            copy_dir(&entry.path(), &target)?;
+        }
+        else
+        {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
@@ -1,12 +1,20 @@
 use core::fmt;
-use std::{cmp::min, path::Path};
+use std::{cmp::min, collections::{HashMap, HashSet}, path::{Path, PathBuf}};
 
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use git2::{ Cred, FetchOptions, Oid, RemoteCallbacks, Repository};
 
-use crate::{config::{GitAuthConfig, GitConfig}, filesystem::{folder::list_sub_dirs, set_dir_readonly}};
+use crate::{config::{DivergencePolicy, GitAuthConfig, GitConfig}, filesystem::{folder::list_sub_dirs, set_dir_readonly}};
 
 pub mod refresh;
+pub mod objects;
+pub mod releases;
+pub mod build;
+pub mod tags;
+
+/// The [GitError::why] of a [fast_forward_pull] when the branch has diverged
+pub const CANNOT_FAST_FORWARD: &str = "Cannot fastforward";
 
 #[derive(Debug, Clone)]
 pub struct GitError
@@ -83,7 +91,7 @@ fn build_fetch_option(auth: &GitAuthConfig) -> FetchOptions<'_>
 /// Attempt to clone a remote repo from a [crate::config::GitConfig]
 pub fn from_clone(path: &str, config: &GitConfig) -> Result<Repository, GitError>
 {
-    if let GitConfig{auth: Some(_), remote: _, checkout_schedule: _, branch: _, remote_webhook_token: _} = config
+    if let GitConfig{auth: Some(_), ..} = config
     {
         crate::debug(format!("Attempting authenticated clone of {}", config.remote), Some("GIT"));
         let auth = config.auth.clone().unwrap();
@@ -91,6 +99,7 @@ pub fn from_clone(path: &str, config: &GitConfig) -> Result<Repository, GitError
         let mut builder = git2::build::RepoBuilder::new();
         builder.fetch_options(fo);
         builder.branch(&config.branch);
+        builder.bare(config.serves_objects());
         match builder.clone(&config.remote,Path::new(&path))
         {
             Ok(repo) =>
@@ -108,7 +117,9 @@ pub fn from_clone(path: &str, config: &GitConfig) -> Result<Repository, GitError
     else
     {
         crate::debug(format!("Attempting un-authenticated clone of {}", config.remote), Some("GIT"));
-        match Repository::clone(&config.remote, path)
+        let mut builder = git2::build::RepoBuilder::new();
+        builder.bare(config.serves_objects());
+        match builder.clone(&config.remote, Path::new(path))
         {
             Ok(repo) =>
             {
@@ -138,7 +149,8 @@ pub fn remove_repository(dir: &str) -> Result<(), std::io::Error>
 }
 
 /// Make a fresh clone if [crate::config::Config::git] is present
-///  deleting any file/dir called [crate::config::ContentConfig::path]
+///  deleting any file/dir called [crate::config::ContentConfig::path].
+///  The highest matching tag is checked out if [GitConfig::tags] is configured
 pub fn clean_and_clone(dir: &str, config: GitConfig) -> Result<Repository, GitError>
 {
     let path = Path::new(dir);
@@ -154,6 +166,10 @@ pub fn clean_and_clone(dir: &str, config: GitConfig) -> Result<Repository, GitEr
     {
         Ok(repo) =>
         {
+            if let Some(ref tags) = config.tags
+            {
+                tags::pull_tag(&repo, &config, tags)?;
+            }
             Ok(repo)
         },
         Err(e) =>
@@ -164,9 +180,14 @@ pub fn clean_and_clone(dir: &str, config: GitConfig) -> Result<Repository, GitEr
 }
 
 /// Fast forward pull from the repository, makes no attempt to resolve
-///  if a fast foward is not possible
+///  if a fast foward is not possible, see [resolve_divergence]. If
+///  [GitConfig::tags] is configured the highest matching tag is checked out instead
 pub fn fast_forward_pull(repo: Repository, git: GitConfig) -> Result<Option<HeadInfo>, GitError>
 {
+    if let Some(ref tags) = git.tags
+    {
+        return tags::pull_tag(&repo, &git, tags)
+    }
     let branch = git.branch.clone();
     if git.auth.is_some()
     {
@@ -195,22 +216,154 @@ pub fn fast_forward_pull(repo: Repository, git: GitConfig) -> Result<Option<Head
         let mut reference = repo.find_reference(&refname)?;
         reference.set_target(fetch_commit.id(), "Fast-Forward")?;
         repo.set_head(&refname)?;
-        repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
+        // bare repositories serve content from objects, see [objects::GitSource]
+        if !repo.is_bare()
+        {
+            repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
+        }
         Ok(head_info(&repo))
     }
     else
     {
-        Err(GitError{why: "Cannot fastforward".to_owned()})
+        Err(GitError{why: CANNOT_FAST_FORWARD.to_owned()})
+    }
+}
+
+/// Hard reset [GitConfig::branch] to the last fetched commit, discarding local commits
+pub fn reset_to_fetch_head(repo: &Repository, git: &GitConfig) -> Result<Option<HeadInfo>, GitError>
+{
+    let fetched = repo.find_reference("FETCH_HEAD")?.peel_to_commit()?;
+    let refname = format!("refs/heads/{}", git.branch);
+    repo.reference(&refname, fetched.id(), true, "Reset to diverged remote")?;
+    repo.set_head(&refname)?;
+    if !repo.is_bare()
+    {
+        repo.reset(fetched.as_object(), git2::ResetType::Hard, Some(git2::build::CheckoutBuilder::default().force()))?;
+    }
+    Ok(head_info(repo))
+}
+
+/// The outcome of [resolve_divergence]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence
+{
+    pub policy: DivergencePolicy,
+    /// The local head before the policy was applied
+    pub local: Option<HeadInfo>,
+    /// The fetched head of the remote branch
+    pub remote: Option<HeadInfo>,
+    /// The head after the policy was applied, if it changed
+    pub head: Option<HeadInfo>,
+    /// Why the policy failed, if it did
+    pub error: Option<String>
+}
+
+impl Divergence
+{
+    /// If the diverged remote is now checked out
+    pub fn resolved(&self) -> bool
+    {
+        self.error.is_none() && self.policy != DivergencePolicy::Refuse
+    }
+
+    /// What happened, e.g. reset to the remote
+    pub fn outcome(&self) -> String
+    {
+        match (&self.error, self.policy)
+        {
+            (Some(e), policy) => format!("{:?} failed, {}", policy, e),
+            (None, DivergencePolicy::Reset) => "reset to the remote".to_string(),
+            (None, DivergencePolicy::Reclone) => "re-cloned".to_string(),
+            (None, DivergencePolicy::Refuse) => "refused, content is not updated until this is resolved".to_string()
+        }
+    }
+
+    /// The local and remote commits
+    pub fn commits(&self) -> String
+    {
+        let commit = |info: &Option<HeadInfo>| match info
+        {
+            Some(info) => format!("{}\n by {}\n at {}", info.hash, info.author_name, info.datetime),
+            None => "unknown".to_string()
+        };
+        format!(" local {}\n remote {}", commit(&self.local), commit(&self.remote))
+    }
+
+    /// Format a notification with the local and remote commits
+    pub fn message(&self, domain: &str) -> String
+    {
+        format!("Remote branch diverged for {}, {}:\n```\n{}\n```", domain, self.outcome(), self.commits())
+    }
+
+    /// Record the local and remote commits in the clone at dir, true if they differ
+    ///   from those last recorded, i.e. the divergence has not been notified
+    pub fn record(&self, dir: &str) -> bool
+    {
+        let hash = |info: &Option<HeadInfo>| info.as_ref().map_or("unknown".to_string(), |i| i.hash.clone());
+        let commits = format!("{} {}", hash(&self.local), hash(&self.remote));
+        let path = match divergence_record(dir)
+        {
+            Ok(path) => path,
+            Err(e) =>
+            {
+                crate::debug(format!("Could not record divergence of {}, {}", dir, e), Some("GIT"));
+                return true
+            }
+        };
+        if std::fs::read_to_string(&path).is_ok_and(|recorded| recorded == commits) { return false }
+        match std::fs::write(&path, &commits)
+        {
+            Ok(_) => crate::debug(format!("Recorded divergence {} in {}", commits, path.display()), Some("GIT")),
+            // notified again on the next pull
+            Err(e) => crate::debug(format!("Could not record divergence in {}, {}", path.display(), e), Some("GIT"))
+        }
+        true
     }
 }
 
+/// Where the last notified [Divergence] of a clone is recorded, inside its git directory,
+///   i.e. .git or the repository itself if bare, see [GitConfig::serves_objects]
+fn divergence_record(dir: &str) -> Result<PathBuf, GitError>
+{
+    Ok(Repository::open(dir)?.path().join("busser_diverged"))
+}
+
+/// Apply [GitConfig::on_divergence] to the clone at dir, after
+///   [fast_forward_pull] failed with [CANNOT_FAST_FORWARD]
+pub fn resolve_divergence(dir: &str, git: &GitConfig) -> Divergence
+{
+    let policy = git.on_divergence.unwrap_or(DivergencePolicy::Refuse);
+    let (local, remote) = match Repository::open(dir)
+    {
+        Ok(repo) => (head_info(&repo), fetch_head_info(&repo)),
+        Err(_) => (None, None)
+    };
+    let result = match policy
+    {
+        DivergencePolicy::Reset => Repository::open(dir).map_err(GitError::from).and_then(|repo| reset_to_fetch_head(&repo, git)),
+        DivergencePolicy::Reclone => clean_and_clone(dir, git.clone()).map(|repo| head_info(&repo)),
+        DivergencePolicy::Refuse => Ok(None)
+    };
+    let divergence = match result
+    {
+        Ok(head) => Divergence { policy, local, remote, head, error: None },
+        Err(e) => Divergence { policy, local, remote, head: None, error: Some(e.why) }
+    };
+    crate::debug(format!("Divergence of {}: {:?}", git.remote, divergence), Some("GIT"));
+    divergence
+}
+
 /// Commit hash, author and timestamp for head commit
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct HeadInfo
 {
     pub hash: String,
     pub author_name: String,
     pub author_email: String,
-    pub datetime: String 
+    pub datetime: String,
+    /// The tag checked out, see [crate::config::GitConfig::tags]
+    pub tag: Option<String>
 }
 
 /// Get the [HeadInfo] if it exists
@@ -225,7 +378,19 @@ pub fn head_info(repo: &Repository) -> Option<HeadInfo>
         },
         Err(_) => return None
     };
+    commit_info(repo, head)
+}
 
+/// Get the [HeadInfo] of the last fetched commit, if it exists
+pub fn fetch_head_info(repo: &Repository) -> Option<HeadInfo>
+{
+    let fetched = repo.find_reference("FETCH_HEAD").ok()?.peel_to_commit().ok()?;
+    commit_info(repo, fetched.id())
+}
+
+/// Get the [HeadInfo] of a commit
+fn commit_info(repo: &Repository, head: Oid) -> Option<HeadInfo>
+{
     match repo.find_commit(head)
     {
         Ok(c) =>
@@ -248,7 +413,8 @@ pub fn head_info(repo: &Repository) -> Option<HeadInfo>
                     hash: short_oid(c.id()),
                     author_email: email,
                     author_name: name,
-                    datetime: format!("{}", dt)
+                    datetime: format!("{}", dt),
+                    tag: None
                 }
             )
         },
@@ -256,6 +422,83 @@ pub fn head_info(repo: &Repository) -> Option<HeadInfo>
     }
 }
 
+/// When each file in HEAD was last changed by a commit reachable from HEAD, by path
+///   within the work directory. Files only in the work tree are absent. History is
+///   walked only until every file has a time
+pub fn last_commit_times(repo: &Repository) -> HashMap<PathBuf, DateTime<Utc>>
+{
+    let mut times = HashMap::new();
+    let mut remaining = head_files(repo);
+    let mut walk = match repo.revwalk() { Ok(w) => w, Err(_) => return times };
+    if walk.push_head().is_err() || walk.set_sorting(git2::Sort::TIME).is_err() { return times }
+
+    for oid in walk.flatten()
+    {
+        if remaining.is_empty() { break }
+        let commit = match repo.find_commit(oid) { Ok(c) => c, Err(_) => continue };
+        let tree = match commit.tree() { Ok(t) => t, Err(_) => continue };
+        let parent = commit.parent(0).ok().and_then(|p| p.tree().ok());
+        let diff = match repo.diff_tree_to_tree(parent.as_ref(), Some(&tree), None) { Ok(d) => d, Err(_) => continue };
+        let time = match DateTime::from_timestamp(commit.time().seconds(), 0) { Some(t) => t, None => continue };
+
+        for delta in diff.deltas()
+        {
+            if delta.status() == git2::Delta::Deleted { continue }
+            if let Some(path) = delta.new_file().path()
+            {
+                if remaining.remove(path) { times.insert(path.to_path_buf(), time); }
+            }
+        }
+    }
+    times
+}
+
+/// Paths of the files (blobs) in HEAD's tree
+fn head_files(repo: &Repository) -> HashSet<PathBuf>
+{
+    let mut files = HashSet::new();
+    let tree = match repo.head().and_then(|h| h.peel_to_tree()) { Ok(t) => t, Err(_) => return files };
+    let _ = tree.walk(git2::TreeWalkMode::PreOrder, |root, entry|
+    {
+        if entry.kind() == Some(git2::ObjectType::Blob)
+        {
+            if let Some(name) = entry.name() { files.insert(Path::new(root).join(name)); }
+        }
+        git2::TreeWalkResult::Ok
+    });
+    files
+}
+
+/// Fetch refspec (e.g. ```refs/pull/1/head```) from [GitConfig::remote] into a working copy
+///   at path and check out the fetched commit, detached. The working copy is created if needed
+pub fn checkout_ref(path: &str, config: &GitConfig, refspec: &str) -> Result<Option<HeadInfo>, GitError>
+{
+    let repo = match Repository::open(path)
+    {
+        Ok(repo) => repo,
+        Err(_) =>
+        {
+            std::fs::create_dir_all(path)?;
+            let repo = Repository::init(path)?;
+            repo.remote("origin", &config.remote)?;
+            repo
+        }
+    };
+
+    crate::debug(format!("Fetching {} of {} into {}", refspec, config.remote, path), Some("GIT"));
+    let mut remote = repo.find_remote("origin")?;
+    match config.auth
+    {
+        Some(ref auth) => remote.fetch(&[refspec], Some(&mut build_fetch_option(auth)), None)?,
+        None => remote.fetch(&[refspec], None, None)?
+    };
+
+    let commit = repo.find_reference("FETCH_HEAD")?.peel_to_commit()?;
+    repo.checkout_tree(commit.as_object(), Some(git2::build::CheckoutBuilder::default().force().remove_untracked(true)))?;
+    repo.set_head_detached(commit.id())?;
+    Ok(head_info(&repo))
+}
+
 /// Get first 7 digits of git hash
 pub fn short_oid(id: Oid) -> String
 {
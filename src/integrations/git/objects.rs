@@ -0,0 +1,123 @@
+use std::{path::Path, sync::{Arc, RwLock}};
+
+use git2::{ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
+
+use super::{short_oid, GitError};
+
+/// A reference pinning the served commit, see [GitSource::pin]
+pub const PIN_REF: &str = "refs/busser/pinned";
+
+/// Content read from git objects (blobs in a commit's tree) rather than a
+///   working copy, see [crate::config::GitConfig::serve_objects].
+///
+/// The served commit is the pinned commit ([PIN_REF]) if any, otherwise the
+///   branch head. Clones share the served commit, so content switches as soon
+///   as it changes
+#[derive(Debug, Clone)]
+pub struct GitSource
+{
+    pub repo_path: String,
+    branch: String,
+    commit: Arc<RwLock<Oid>>
+}
+
+impl GitSource
+{
+    pub fn open(repo_path: &str, branch: &str) -> Result<GitSource, GitError>
+    {
+        let repo = Repository::open(repo_path)?;
+        let commit = served_commit(&repo, branch)?;
+        Ok(GitSource { repo_path: repo_path.to_string(), branch: branch.to_string(), commit: Arc::new(RwLock::new(commit)) })
+    }
+
+    /// The commit content is read from
+    pub fn commit(&self) -> Oid
+    {
+        *self.commit.read().unwrap()
+    }
+
+    /// Serve content from commit, e.g. one resolved by another [GitSource]
+    pub fn set_commit(&self, commit: Oid)
+    {
+        *self.commit.write().unwrap() = commit;
+    }
+
+    /// The pinned commit's short hash, if pinned
+    pub fn pinned(&self) -> Option<String>
+    {
+        let repo = Repository::open(&self.repo_path).ok()?;
+        let pinned = repo.find_reference(PIN_REF).ok()?.peel_to_commit().ok()?;
+        Some(short_oid(pinned.id()))
+    }
+
+    /// Serve the commit revision (e.g. a hash) resolves to, until [GitSource::unpin]
+    pub fn pin(&self, revision: &str) -> Result<Oid, GitError>
+    {
+        let repo = Repository::open(&self.repo_path)?;
+        let commit = repo.revparse_single(revision)?.peel_to_commit()?;
+        repo.reference(PIN_REF, commit.id(), true, &format!("busser: pin {}", revision))?;
+        self.set_commit(commit.id());
+        Ok(commit.id())
+    }
+
+    /// Serve the branch head again
+    pub fn unpin(&self) -> Result<Oid, GitError>
+    {
+        let repo = Repository::open(&self.repo_path)?;
+        if let Ok(mut reference) = repo.find_reference(PIN_REF)
+        {
+            reference.delete()?;
+        }
+        let commit = served_commit(&repo, &self.branch)?;
+        self.set_commit(commit);
+        Ok(commit)
+    }
+
+    /// Paths of files (blobs) in the served commit's tree
+    pub fn files(&self) -> Result<Vec<String>, GitError>
+    {
+        let repo = Repository::open(&self.repo_path)?;
+        let tree = repo.find_commit(self.commit())?.tree()?;
+        let mut files = vec![];
+        tree.walk(TreeWalkMode::PreOrder, |root, entry|
+        {
+            if entry.kind() == Some(ObjectType::Blob)
+            {
+                if let Some(name) = entry.name() { files.push(format!("{}{}", root, name)); }
+            }
+            TreeWalkResult::Ok
+        })?;
+        Ok(files)
+    }
+
+    /// The file at path (relative to the repository root) in the served commit
+    pub fn read(&self, path: &str) -> Option<Vec<u8>>
+    {
+        let repo = Repository::open(&self.repo_path).ok()?;
+        let tree = repo.find_commit(self.commit()).ok()?.tree().ok()?;
+        let entry = tree.get_path(Path::new(path.trim_start_matches('/'))).ok()?;
+        let blob = entry.to_object(&repo).ok()?.peel_to_blob().ok()?;
+        Some(blob.content().to_vec())
+    }
+
+    /// The path of a (content path prefixed) disk path within the repository
+    pub fn tree_path(&self, disk_path: &str) -> String
+    {
+        let root = self.repo_path.trim_end_matches('/');
+        disk_path.strip_prefix(root).unwrap_or(disk_path).trim_start_matches('/').to_string()
+    }
+}
+
+/// The pinned commit, or the head of branch (HEAD if there is no such branch, e.g. ```HEAD```)
+pub fn served_commit(repo: &Repository, branch: &str) -> Result<Oid, GitError>
+{
+    if let Ok(pinned) = repo.find_reference(PIN_REF)
+    {
+        return Ok(pinned.peel_to_commit()?.id())
+    }
+    match repo.find_reference(&format!("refs/heads/{}", branch))
+    {
+        Ok(head) => Ok(head.peel_to_commit()?.id()),
+        Err(_) => Ok(repo.head()?.peel_to_commit()?.id())
+    }
+}
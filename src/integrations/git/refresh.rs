@@ -9,7 +9,14 @@ use tokio::sync::Mutex;
 
 use crate::{config::{Config, CONFIG_PATH}, integrations::discord::post::try_post, task::{next_job_time, schedule_from_option, Task}};
 
-use super::{clean_and_clone, fast_forward_pull, HeadInfo};
+use super::{build::{build_and_publish, checkout_path, needs_build, BuildError, NOTIFY_LINES}, clean_and_clone, fast_forward_pull, releases::Releases, resolve_divergence, Divergence, GitError, HeadInfo, CANNOT_FAST_FORWARD};
+
+/// What [GitRefreshTask::update] did, to be notified
+enum Refreshed
+{
+    Deployed(Result<Option<HeadInfo>, GitError>),
+    Pulled { info: Option<HeadInfo>, divergence: Option<Box<Divergence>>, build: Option<Result<(), BuildError>> }
+}
 
 pub struct GitRefreshTask
 {
@@ -36,24 +43,26 @@ impl GitRefreshTask
         }
     }
 
-    /// Attempt a fast forward pull of the repo in [crate::config::ContentConfig::path]
-    ///   clone if it is not there. Does nothing if [crate::config::GitConfig] is none
-    pub fn pull(config: &Config) -> Option<HeadInfo>
+    /// Attempt a fast forward pull of the repo in [crate::config::ContentConfig::path], or
+    ///   [crate::config::BuildConfig::path] if building, clone if it is not there. Does nothing if [crate::config::GitConfig] is none.
+    ///   If the branch has diverged [crate::config::GitConfig::on_divergence] is applied, see [resolve_divergence]
+    pub fn pull(config: &Config) -> (Option<HeadInfo>, Option<Divergence>)
     {
         
         if config.git.is_some()
         {
             let git = config.git.clone().unwrap();
-            let path = Path::new(&config.content.path);
+            let checkout = checkout_path(config);
+            let path = Path::new(&checkout);
             if path.is_dir()
             {
                 let result = match Repository::open(path)
                 {
-                    Ok(repo) => fast_forward_pull(repo, git),
+                    Ok(repo) => fast_forward_pull(repo, git.clone()),
                     Err(e) =>
                     {
                         crate::debug(format!("{}, {:?} is not a git repo", e, path), Some("GIT"));
-                        match clean_and_clone(&config.content.path, git.clone())
+                        match clean_and_clone(&checkout, git.clone())
                         {
                             Ok(_) => Ok(None),
                             Err(e) => Err(e)
@@ -61,18 +70,20 @@ impl GitRefreshTask
                     }
                 };
 
-                if result.is_err()
+                match result
                 {
-                    crate::debug(format!("{:?}", result.err()), Some("GIT"));
-                }
-                else
-                {
-                    return result.unwrap()
+                    Ok(info) => return (info, None),
+                    Err(e) if e.why == CANNOT_FAST_FORWARD =>
+                    {
+                        let divergence = resolve_divergence(&checkout, &git);
+                        return (divergence.head.clone(), Some(divergence))
+                    },
+                    Err(e) => crate::debug(format!("{:?}", e), Some("GIT"))
                 }
             }
             else
             {
-                let result = match clean_and_clone(&config.content.path, git.clone())
+                let result = match clean_and_clone(&checkout, git.clone())
                 {
                     Ok(repo) => fast_forward_pull(repo, git),
                     Err(e) => Err(e)
@@ -83,7 +94,100 @@ impl GitRefreshTask
                 }
             }
         }
-        None
+        (None, None)
+    }
+
+    /// Pull, or deploy if [crate::config::GitConfig::releases] is configured, and notify
+    ///   of new commits, diverged branches (once each, see [Divergence::record]) and failed deploys. If [crate::config::GitConfig::build] is configured
+    ///   unbuilt commits are built and the output published, see [build_and_publish] and [needs_build].
+    ///   Git and builds block, so run on tokio's blocking threads
+    pub async fn refresh(config: &Config)
+    {
+        let owned = config.clone();
+        let refreshed = match tokio::task::spawn_blocking(move || Self::update(&owned)).await
+        {
+            Ok(refreshed) => refreshed,
+            Err(e) =>
+            {
+                crate::debug(format!("Refresh task failed: {}", e), Some("GIT"));
+                return
+            }
+        };
+
+        match refreshed
+        {
+            Refreshed::Deployed(Ok(info)) => Self::notify_pull(info, config).await,
+            Refreshed::Deployed(Err(e)) =>
+            {
+                crate::debug(format!("Deploy failed: {}", e), Some("GIT"));
+                try_post
+                (
+                    config.notification_endpoint.clone(),
+                    &format!("Deploy failed for {}:\n```\n{}\n```", config.domain, e)
+                ).await;
+            },
+            Refreshed::Pulled { info, divergence, build } =>
+            {
+                if let Some(ref divergence) = divergence
+                {
+                    Self::notify_divergence(divergence, config).await;
+                }
+                if let Some(Err(e)) = build
+                {
+                    Self::notify_build_failure(&e, config).await;
+                    return
+                }
+                if divergence.is_none()
+                {
+                    Self::notify_pull(info, config).await
+                }
+            }
+        }
+    }
+
+    /// The blocking part of [GitRefreshTask::refresh]
+    fn update(config: &Config) -> Refreshed
+    {
+        if let Some(releases) = Releases::new(config)
+        {
+            return Refreshed::Deployed(releases.deploy(config))
+        }
+        let (info, divergence) = Self::pull(config);
+        let checkout = checkout_path(config);
+        // a refused divergence is found again on every pull, notify it once
+        let divergence = divergence.filter(|d| d.record(&checkout));
+        let build = if needs_build(config, &checkout)
+        {
+            Some(build_and_publish(config, &checkout).map(|_| ()))
+        }
+        else
+        {
+            None
+        };
+        Refreshed::Pulled { info, divergence: divergence.map(Box::new), build }
+    }
+
+    /// Send a discord message with the local and remote commits of a diverged branch
+    pub async fn notify_divergence(divergence: &Divergence, config: &Config)
+    {
+        let msg = divergence.message(&config.domain);
+        crate::debug(msg.clone(), Some("GIT"));
+        try_post
+        (
+            config.notification_endpoint.clone(),
+            &msg
+        ).await;
+    }
+
+    /// Send a discord message with the failed build's output
+    pub async fn notify_build_failure(error: &BuildError, config: &Config)
+    {
+        crate::debug(format!("Build failed: {}\n{}", error, error.output), Some("BUILD"));
+        try_post
+        (
+            config.notification_endpoint.clone(),
+            &format!("Build failed for {}, {}:\n```\n{}\n```", config.domain, error, error.tail(NOTIFY_LINES))
+        ).await;
     }
 
     /// Send a discord message with [HeadInfo] if it is Some
@@ -105,16 +209,23 @@ impl GitRefreshTask
         
     }
 
-    /// Format a notification from a head info object and config. None if info is None
+    /// Format a notification from a head info object and config, naming the version if a tag
+    ///   was checked out. None if info is None
     pub fn head_info_to_message(info: Option<HeadInfo>, config: &Config) -> Option<String>
     {
         match info
         {
             Some(info) =>
             {
+                let checked_out = match info.tag
+                {
+                    Some(ref tag) => format!("Deployed version {}", tag),
+                    None => "Checked out new commit".to_string()
+                };
                 Some(format!
                 (
-                    "Checked out new commit for {}:\n```\n {}\n by {}\n at {}\n```",
+                    "{} for {}:\n```\n {}\n by {}\n at {}\n```",
+                    checked_out,
                     config.domain,
                     info.hash,
                     info.author_name,
@@ -133,7 +244,7 @@ impl Task for GitRefreshTask
     {
         let mut time = self.lock.lock().await;
         let config = Config::load_or_default(CONFIG_PATH);
-        GitRefreshTask::notify_pull(GitRefreshTask::pull(&config), &config).await;
+        GitRefreshTask::refresh(&config).await;
         *time = SystemTime::now();
 
         self.schedule = schedule_from_option(config.stats.save_schedule.clone());
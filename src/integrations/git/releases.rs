@@ -0,0 +1,338 @@
+use std::{panic::{catch_unwind, AssertUnwindSafe}, path::Path};
+
+use chrono::Utc;
+use git2::Repository;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::{BuildConfig, Config}, content::sitemap::SiteMap};
+
+use super::{tags::head_tag, build::{output_path, run_build, NOTIFY_LINES}, clean_and_clone, fast_forward_pull, head_info, remove_repository, resolve_divergence, GitError, HeadInfo, CANNOT_FAST_FORWARD};
+
+pub const DEFAULT_PATH: &str = "releases";
+pub const DEFAULT_KEEP: usize = 5;
+/// The clone tracking [crate::config::GitConfig::branch], within [Releases::path]
+pub const REPOSITORY: &str = "repository";
+/// Records the last commit which failed to release, within [Releases::path]
+pub const FAILED: &str = "failed";
+
+/// A release directory, named by its creation time and commit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Release
+{
+    pub name: String,
+    pub commit: String,
+    pub active: bool
+}
+
+/// Releases of content, see [crate::config::ReleasesConfig]. Commits are pulled into
+///   a tracking clone, then each new commit is cloned to a release directory which
+///   is checked and activated by swapping the symlink at [crate::config::ContentConfig::path].
+///   The content path is never checked out over, or removed, while served. If
+///   [crate::config::GitConfig::build] is configured each release is built, and its output served
+#[derive(Clone)]
+pub struct Releases
+{
+    pub path: String,
+    link: String,
+    keep: usize,
+    check_links: bool,
+    build: Option<BuildConfig>
+}
+
+impl Releases
+{
+    /// Releases if configured, and not serving git objects
+    pub fn new(config: &Config) -> Option<Releases>
+    {
+        let git = config.git.as_ref().filter(|g| !g.serves_objects())?;
+        let releases = git.releases.as_ref()?;
+        Some(Releases
+        {
+            path: releases.path.clone().unwrap_or(DEFAULT_PATH.to_string()),
+            link: config.content.path.trim_end_matches('/').to_string(),
+            keep: releases.keep.unwrap_or(DEFAULT_KEEP).max(1),
+            check_links: releases.check_links.is_some_and(|c| c),
+            build: git.build.clone()
+        })
+    }
+
+    /// The tracking clone's path
+    pub fn repository(&self) -> String
+    {
+        format!("{}/{}", self.path, REPOSITORY)
+    }
+
+    /// All releases, oldest first
+    pub fn list(&self) -> Vec<Release>
+    {
+        let pattern = Regex::new(r"^\d{8}T\d{9}-([0-9a-f]+)$").unwrap();
+        let active = self.active();
+        let mut releases: Vec<Release> = match std::fs::read_dir(&self.path)
+        {
+            Ok(entries) => entries.flatten()
+                .filter(|e| e.path().is_dir())
+                .filter_map(|e|
+                {
+                    let name = e.file_name().to_string_lossy().to_string();
+                    let commit = pattern.captures(&name)?[1].to_string();
+                    Some(Release { active: active.as_ref() == Some(&name), name, commit })
+                })
+                .collect(),
+            Err(_) => vec![]
+        };
+        releases.sort_by(|a, b| a.name.cmp(&b.name));
+        releases
+    }
+
+    /// The name of the release the content path links to
+    pub fn active(&self) -> Option<String>
+    {
+        let target = std::fs::read_link(&self.link).ok()?;
+        let release = target.strip_prefix(std::fs::canonicalize(&self.path).ok()?).ok()?.components().next()?;
+        Some(release.as_os_str().to_string_lossy().to_string())
+    }
+
+    /// The directory served for the release name, its build output if building
+    pub fn served_path(&self, name: &str) -> String
+    {
+        let dir = format!("{}/{}", self.path, name);
+        match self.build
+        {
+            Some(ref build) => output_path(build, &dir),
+            None => dir
+        }
+    }
+
+    /// Serve the release name, by atomically replacing the content path's symlink
+    pub fn activate(&self, name: &str) -> Result<(), GitError>
+    {
+        if !self.list().iter().any(|r| r.name == name)
+        {
+            return Err(GitError { why: format!("No release {} in {}", name, self.path) })
+        }
+        let target = std::fs::canonicalize(self.served_path(name))?;
+        swap_link(&target, &self.link)?;
+        crate::debug(format!("Activated release {}", name), Some("GIT"));
+        Ok(())
+    }
+
+    /// Activate the release before the active release, returning its name
+    pub fn rollback(&self) -> Result<String, GitError>
+    {
+        let releases = self.list();
+        match releases.iter().position(|r| r.active)
+        {
+            Some(i) if i > 0 =>
+            {
+                self.activate(&releases[i-1].name)?;
+                Ok(releases[i-1].name.clone())
+            },
+            _ => Err(GitError { why: "No release to roll back to".to_string() })
+        }
+    }
+
+    /// Remove the oldest inactive releases, keeping [crate::config::ReleasesConfig::keep]
+    pub fn prune(&self)
+    {
+        let releases = self.list();
+        let mut excess = releases.len().saturating_sub(self.keep);
+        for release in releases.iter().filter(|r| !r.active)
+        {
+            if excess == 0 { break }
+            match remove_repository(&format!("{}/{}", self.path, release.name))
+            {
+                Ok(_) => excess -= 1,
+                Err(e) => crate::debug(format!("Error {} removing release {}", e, release.name), Some("GIT"))
+            }
+        }
+    }
+
+    /// Pull the tracking clone (cloning if needed) and release its head if it has not
+    ///   been released, nor the last to fail. A diverged branch is resolved by [crate::config::GitConfig::on_divergence],
+    ///   an error if refused. A release failing [check_release] is removed, leaving the active
+    ///   release served. Returns the head if a release is activated
+    pub fn deploy(&self, config: &Config) -> Result<Option<HeadInfo>, GitError>
+    {
+        let git = match config.git
+        {
+            Some(ref git) => git.clone(),
+            None => return Ok(None)
+        };
+
+        let repository = self.repository();
+        let repo = match Repository::open(&repository)
+        {
+            Ok(repo) =>
+            {
+                match fast_forward_pull(repo, git.clone())
+                {
+                    Err(e) if e.why == CANNOT_FAST_FORWARD =>
+                    {
+                        let divergence = resolve_divergence(&repository, &git);
+                        if !divergence.resolved()
+                        {
+                            return Err(GitError { why: format!("Remote branch diverged, {}:\n{}", divergence.outcome(), divergence.commits()) })
+                        }
+                    },
+                    result => { result?; }
+                }
+                Repository::open(&repository)?
+            },
+            Err(_) =>
+            {
+                std::fs::create_dir_all(&self.path)?;
+                clean_and_clone(&repository, git.clone())?
+            }
+        };
+
+        let tag = git.tags.as_ref().and_then(|tags| head_tag(&repo, tags));
+        let head = match head_info(&repo)
+        {
+            Some(head) => HeadInfo { tag, ..head },
+            None => return Err(GitError { why: format!("No head commit in {}", repository) })
+        };
+
+        // a released commit is not released again, e.g. after a rollback
+        if let Some(release) = self.list().into_iter().rev().find(|r| r.commit == head.hash)
+        {
+            if self.active().is_none() { self.activate(&release.name)?; }
+            return Ok(None)
+        }
+
+        // a failed head is not retried until the branch moves on
+        if self.failed().as_ref() == Some(&head.hash)
+        {
+            crate::debug(format!("Skipping {} which failed to deploy", head.hash), Some("GIT"));
+            return Ok(None)
+        }
+
+        match self.release(config, &head)
+        {
+            Ok(_) =>
+            {
+                let _ = std::fs::remove_file(self.failed_record());
+                Ok(Some(head))
+            },
+            Err(e) =>
+            {
+                if let Err(write) = std::fs::write(self.failed_record(), &head.hash)
+                {
+                    crate::debug(format!("Could not record failed release {}, {}", head.hash, write), Some("GIT"));
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Where the commit of the last failed release is recorded
+    fn failed_record(&self) -> String
+    {
+        format!("{}/{}", self.path, FAILED)
+    }
+
+    /// The commit of the last failed release, see [Releases::deploy]
+    pub fn failed(&self) -> Option<String>
+    {
+        std::fs::read_to_string(self.failed_record()).ok().map(|c| c.trim().to_string())
+    }
+
+    /// Clone, build and check the tracking clone's head to a new release, activating it
+    fn release(&self, config: &Config, head: &HeadInfo) -> Result<(), GitError>
+    {
+        let repository = self.repository();
+        let repo = Repository::open(&repository)?;
+        let name = format!("{}-{}", Utc::now().format("%Y%m%dT%H%M%S%3f"), head.hash);
+        let dir = format!("{}/{}", self.path, name);
+        let source = std::fs::canonicalize(&repository)?;
+        let release = git2::build::RepoBuilder::new().clone(&source.to_string_lossy(), Path::new(&dir))?;
+        // a checked out tag, see [crate::config::GitConfig::tags]
+        if repo.head_detached()?
+        {
+            if let Some(oid) = repo.head()?.target()
+            {
+                release.set_head_detached(oid)?;
+                release.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
+            }
+        }
+
+        if let Some(ref build) = self.build
+        {
+            if let Err(e) = run_build(build, &dir)
+            {
+                remove_repository(&dir)?;
+                return Err(GitError { why: format!("Release {} build failed, {}:\n{}", name, e, e.tail(NOTIFY_LINES)) })
+            }
+        }
+
+        if let Err(why) = check_release(config, &self.served_path(&name), self.check_links)
+        {
+            remove_repository(&dir)?;
+            return Err(GitError { why: format!("Release {} failed checks, {}", name, why) })
+        }
+
+        self.activate(&name)?;
+        self.prune();
+        Ok(())
+    }
+}
+
+/// Check content at dir before it is served, the home page must exist, the
+///   sitemap must build and, if check_links, there must be no broken links
+pub fn check_release(config: &Config, dir: &str, check_links: bool) -> Result<(), String>
+{
+    let mut release = config.clone();
+    release.content = config.content.relocated(dir);
+    release.content.check_links = Some(check_links);
+    release.git = None;
+
+    if !Path::new(&release.content.home).is_file()
+    {
+        return Err(format!("home page {} does not exist", release.content.home))
+    }
+
+    let sitemap = match catch_unwind(AssertUnwindSafe(|| SiteMap::build(&release, false, true)))
+    {
+        Ok(sitemap) => sitemap,
+        Err(_) => return Err("sitemap build failed".to_string())
+    };
+
+    let broken = sitemap.link_report().read().unwrap().message(&config.domain, 3);
+    match broken
+    {
+        Some(broken) => Err(broken),
+        None => Ok(())
+    }
+}
+
+/// Point link at target, replacing link atomically where the platform allows.
+///   A directory at link, e.g. a clone from before releases were used, is removed
+fn swap_link(target: &Path, link: &str) -> Result<(), GitError>
+{
+    let swap = format!("{}.swap", link);
+    if std::fs::symlink_metadata(&swap).is_ok()
+    {
+        remove_link(&swap)?;
+    }
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, &swap)?;
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_dir(target, &swap)?;
+
+    match std::fs::symlink_metadata(link)
+    {
+        Ok(m) if m.is_dir() => remove_repository(link)?,
+        // a directory link cannot be renamed over on windows
+        Ok(m) if cfg!(windows) && m.is_symlink() => remove_link(link)?,
+        _ => {}
+    }
+
+    std::fs::rename(&swap, link)?;
+    Ok(())
+}
+
+fn remove_link(link: &str) -> Result<(), std::io::Error>
+{
+    if cfg!(windows) { std::fs::remove_dir(link) } else { std::fs::remove_file(link) }
+}
@@ -0,0 +1,97 @@
+use git2::{FetchOptions, FetchPrune, Repository};
+use regex::Regex;
+use semver::{Version, VersionReq};
+
+use crate::config::{GitConfig, TagsConfig};
+
+use super::{build_fetch_option, head_info, GitError, HeadInfo};
+
+/// Tags match this by default, the version follows, e.g. ```v1.2.3```
+pub const DEFAULT_PATTERN: &str = "^v?";
+
+/// The version of tag, if it matches [TagsConfig::pattern] and [TagsConfig::version]
+pub fn tag_version(tag: &str, config: &TagsConfig) -> Option<Version>
+{
+    let pattern = match Regex::new(config.pattern.as_deref().unwrap_or(DEFAULT_PATTERN))
+    {
+        Ok(p) => p,
+        Err(e) => { crate::debug(format!("Invalid tag pattern, {}", e), Some("GIT")); return None }
+    };
+    let requirement = match VersionReq::parse(config.version.as_deref().unwrap_or("*"))
+    {
+        Ok(r) => r,
+        Err(e) => { crate::debug(format!("Invalid tag version requirement, {}", e), Some("GIT")); return None }
+    };
+    let matched = pattern.find(tag)?;
+    let version = Version::parse(&tag[matched.end()..]).ok()?;
+    if requirement.matches(&version) { Some(version) } else { None }
+}
+
+/// The tag with the highest matching version, see [tag_version]
+pub fn highest_tag(repo: &Repository, config: &TagsConfig) -> Option<(String, Version)>
+{
+    let tags = repo.tag_names(None).ok()?;
+    tags.iter().flatten()
+        .filter_map(|tag| Some((tag.to_string(), tag_version(tag, config)?)))
+        .max_by(|a, b| a.1.cmp(&b.1))
+}
+
+/// The highest matching tag of the head commit, see [tag_version]
+pub fn head_tag(repo: &Repository, config: &TagsConfig) -> Option<String>
+{
+    let head = repo.head().ok()?.target()?;
+    let tags = repo.tag_names(None).ok()?;
+    tags.iter().flatten()
+        .filter(|tag| repo.revparse_single(&format!("refs/tags/{}", tag)).ok().and_then(|t| t.peel_to_commit().ok()).is_some_and(|c| c.id() == head))
+        .filter_map(|tag| Some((tag.to_string(), tag_version(tag, config)?)))
+        .max_by(|a, b| a.1.cmp(&b.1))
+        .map(|(tag, _)| tag)
+}
+
+/// Fetch all tags from origin, moving any which were re-tagged and deleting
+///   any deleted from origin, so a withdrawn version is not deployed
+pub fn fetch_tags(repo: &Repository, git: &GitConfig) -> Result<(), GitError>
+{
+    let refspecs = ["+refs/tags/*:refs/tags/*"];
+    let mut options = match git.auth
+    {
+        Some(ref auth) => build_fetch_option(auth),
+        None => FetchOptions::new()
+    };
+    options.prune(FetchPrune::On);
+    repo.find_remote("origin")?.fetch(&refspecs, Some(&mut options), None)?;
+    Ok(())
+}
+
+/// Check out the highest matching tag detached, see [highest_tag]. The
+///   [HeadInfo] (with its tag) is returned if the head moved
+pub fn checkout_tag(repo: &Repository, config: &TagsConfig) -> Result<Option<HeadInfo>, GitError>
+{
+    let tag = match highest_tag(repo, config)
+    {
+        Some((tag, _)) => tag,
+        None => return Err(GitError { why: "No tag matches the tag pattern and version".to_string() })
+    };
+    let commit = repo.revparse_single(&format!("refs/tags/{}", tag))?.peel_to_commit()?;
+    if repo.head().ok().and_then(|h| h.target()) == Some(commit.id())
+    {
+        return Ok(None)
+    }
+
+    repo.set_head_detached(commit.id())?;
+    // bare repositories serve content from objects, see [super::objects::GitSource]
+    if !repo.is_bare()
+    {
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
+    }
+    crate::debug(format!("Checked out tag {}", tag), Some("GIT"));
+    Ok(head_info(repo).map(|info| HeadInfo { tag: Some(tag), ..info }))
+}
+
+/// Fetch tags and check out the highest matching tag, see [checkout_tag]
+pub fn pull_tag(repo: &Repository, git: &GitConfig, config: &TagsConfig) -> Result<Option<HeadInfo>, GitError>
+{
+    crate::debug(format!("Fetching tags of {}", git.remote), Some("GIT"));
+    fetch_tags(repo, git)?;
+    checkout_tag(repo, config)
+}
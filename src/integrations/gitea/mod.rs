@@ -0,0 +1,64 @@
+use axum::{async_trait, body::Bytes, http::HeaderMap};
+use reqwest::StatusCode;
+
+use super::forge::{parse_body, ForgeWebhook};
+
+/// Gitea and Forgejo push webhooks, signed in x-gitea-signature
+///   or x-forgejo-signature
+pub struct Gitea;
+
+#[async_trait]
+impl ForgeWebhook for Gitea
+{
+    const NAME: &'static str = "GITEA";
+
+    /// x-forgejo-event or x-gitea-event is push
+    async fn is_push(headers: &HeaderMap) -> StatusCode
+    {
+        let event = match headers.get("x-forgejo-event").or(headers.get("x-gitea-event"))
+        {
+            Some(e) => e,
+            None => return StatusCode::CONTINUE
+        };
+        match event.to_str()
+        {
+            Ok("push") =>
+            {
+                crate::debug("Recieving gitea push event".to_string(), Some(Self::NAME));
+                StatusCode::OK
+            },
+            Ok(_) => StatusCode::CONTINUE,
+            Err(e) =>
+            {
+                crate::debug(format!("Invalid utf8 in gitea event, {}", e), Some(Self::NAME));
+                StatusCode::BAD_REQUEST
+            }
+        }
+    }
+
+    /// The url in json path ["repository"]["clone_url"], ["repository"]["ssh_url"]
+    ///   or ["repository"]["html_url"]
+    fn is_watched_repo(body: &Bytes, url: &str) -> bool
+    {
+        match parse_body(body, Self::NAME)
+        {
+            Some(parsed) => ["clone_url", "ssh_url", "html_url"].iter().any(|key| parsed["repository"][key].as_str() == Some(url)),
+            None => false
+        }
+    }
+
+    fn is_tracked_branch(body: &Bytes, branch: &str) -> bool
+    {
+        match parse_body(body, Self::NAME)
+        {
+            Some(parsed) => parsed["ref"].as_str() == Some(&format!("refs/heads/{}", branch)),
+            None => false
+        }
+    }
+
+    fn is_authentic(headers: &HeaderMap, token: String, body: &Bytes) -> StatusCode
+    {
+        let key = if headers.contains_key("x-forgejo-signature") { "x-forgejo-signature" } else { "x-gitea-signature" };
+        super::is_authentic(headers, key, token, body)
+    }
+}
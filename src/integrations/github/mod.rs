@@ -1,45 +1,55 @@
 use std::{collections::HashMap, sync::Arc, time::SystemTime};
 
-use axum::{body::Bytes, extract::State, http::{HeaderMap, Request}, middleware::Next, response::{IntoResponse, Response}};
+use axum::{async_trait, body::{Body, Bytes}, extract::State, http::{HeaderMap, Request}, middleware::Next, response::{IntoResponse, Response}};
 use regex::Regex;
 use reqwest::StatusCode;
 use tokio::sync::Mutex;
 
-use crate::{config::{Config, CONFIG_PATH}, util::{extract_bytes, strip_control_characters}};
+use crate::{config::{Config, GitConfig, PreviewConfig, CONFIG_PATH}, server::previews::{is_previewed_branch, preview_name, PreviewEvent, Previews}, util::{extract_bytes, strip_control_characters}};
 
-use super::git::refresh::GitRefreshTask;
+use super::forge::{filter_push, ForgeWebhook};
+
+/// Github push webhooks, signed in x-hub-signature-256
+pub struct Github;
+
+#[async_trait]
+impl ForgeWebhook for Github
+{
+    const NAME: &'static str = "GITHUB";
+
+    /// Pushes, and tags created or released, see [crate::config::GitConfig::tags]
+    async fn is_push(headers: &HeaderMap) -> StatusCode
+    {
+        match is_push(headers).await
+        {
+            StatusCode::CONTINUE if is_tag_event(headers) => StatusCode::OK,
+            status => status
+        }
+    }
+
+    fn is_watched_repo(body: &Bytes, url: &str) -> bool { is_watched_repo(body, url) }
+
+    /// Any pushed branch pulls [GitConfig::branch]
+    fn is_tracked_branch(_body: &Bytes, _branch: &str) -> bool { true }
+
+    fn is_authentic(headers: &HeaderMap, token: String, body: &Bytes) -> StatusCode
+    {
+        super::is_authentic(headers, "x-hub-signature-256", token, body)
+    }
+}
 
 /// If user-agent is GitHub-Hookshot, check if
 ///  x-github-event is push. If so pull the repo if
 ///  [crate::config::GitConfig] is not None
 pub async fn filter_github
 (
-    State(repo_lock): State<Arc<Mutex<SystemTime>>>,
+    state: State<Arc<Mutex<SystemTime>>>,
     headers: HeaderMap,
     request: Request<axum::body::Body>,
     next: Next
 ) -> Result<Response, StatusCode>
 {
-    let config = Config::load_or_default(CONFIG_PATH);
-    let remote = match config.git
-    {
-        Some(git) => git.remote,
-        None => {return Ok(next.run(request).await)}
-    };
-    let token = get_token();
-    if token.is_none()
-    {
-        return Ok(next.run(request).await)
-    }
-    match is_push(&headers).await
-    {
-        StatusCode::CONTINUE => Ok(next.run(request).await),
-        StatusCode::OK =>
-        {
-            Ok(handle_push(repo_lock, headers, request, remote, token.unwrap()).await.into_response())
-        },
-        status => Ok(status.into_response())
-    }
+    filter_push::<Github>(state, headers, request, next).await
 }
 
 /// Checks the Github webhook event is authentic and
@@ -53,36 +63,7 @@ pub async fn handle_push
     token: String
 ) -> StatusCode
 {
-    let bytes = match extract_bytes(request).await
-    {
-        Ok(b) => b,
-        Err(_) => return StatusCode::BAD_REQUEST
-    };
-
-    if !is_watched_repo(&bytes, &remote)
-    {
-        return StatusCode::OK;
-    }
-
-    match super::is_authentic
-    (
-        &headers, "x-hub-signature-256",
-        token,
-        &bytes
-    )
-    {
-        StatusCode::ACCEPTED =>
-        {
-            crate::debug("Github push event is authentic".to_string(), Some("GITHUB"));
-            pull(repo_lock).await;
-            return StatusCode::OK;
-        },
-        status =>
-        {
-            crate::debug(format!("Authentication error: {}", status), Some("GITHUB"));
-            return status;
-        }
-    }
+    super::forge::handle_push::<Github>(repo_lock, headers, request, &remote, "", token).await
 }
 
 fn get_token() -> Option<String>
@@ -98,15 +79,6 @@ fn get_token() -> Option<String>
     }
 }
 
-/// Perform the pull updating the mutex
-async fn pull(repo_lock: Arc<Mutex<SystemTime>>)
-{
-    let mut lock = repo_lock.lock().await;
-    let config = Config::load_or_default(CONFIG_PATH);
-    GitRefreshTask::notify_pull(GitRefreshTask::pull(&config), &config).await;
-    *lock = SystemTime::now();
-}
-
 /// Check the url in json path ["repository"]["html_url"] or
 ///   ["repository"]["ssh_url"] of a [Bytes] body matches url
 pub fn is_watched_repo(body: &Bytes, url: &str) -> bool
@@ -185,4 +157,119 @@ pub async fn is_push(headers: &HeaderMap) -> StatusCode
         }
     }
     StatusCode::CONTINUE
-}
\ No newline at end of file
+}
+
+/// Check if the headers are a Github create (e.g. a tag) or release event,
+///   these trigger a pull so new tags are deployed
+pub fn is_tag_event(headers: &HeaderMap) -> bool
+{
+    match github_event(headers)
+    {
+        Some(event) =>
+        {
+            let tagged = event == "create" || event == "release";
+            if tagged { crate::debug(format!("Recieving github {} event", event), Some("GITHUB")); }
+            tagged
+        },
+        None => false
+    }
+}
+
+/// The x-github-event of a Github webhook request (user-agent GitHub-Hookshot)
+pub fn github_event(headers: &HeaderMap) -> Option<String>
+{
+    let user_agent = headers.get("user-agent")?.to_str().ok()?;
+    if !user_agent.contains("GitHub-Hookshot") { return None }
+    Some(headers.get("x-github-event")?.to_str().ok()?.to_lowercase())
+}
+
+/// The [PreviewEvent] of a Github push or pull_request event body, if previewed.
+///   Pushes to [GitConfig::branch] are not previewed
+pub fn preview_event(event: &str, body: &Bytes, git: &GitConfig, previews: &PreviewConfig) -> Option<PreviewEvent>
+{
+    let parsed: serde_json::Value = serde_json::from_str(&strip_control_characters(std::str::from_utf8(body).ok()?.to_owned())).ok()?;
+    match event
+    {
+        "push" =>
+        {
+            let branch = parsed["ref"].as_str()?.strip_prefix("refs/heads/")?;
+            if branch == git.branch || !is_previewed_branch(previews, branch) { return None }
+            let name = preview_name(branch);
+            match parsed["deleted"].as_bool()
+            {
+                Some(true) => Some(PreviewEvent::Remove { name }),
+                _ => Some(PreviewEvent::Update { name, refspec: format!("refs/heads/{}", branch) })
+            }
+        },
+        "pull_request" if previews.pull_requests.is_some_and(|p| p) =>
+        {
+            let number = parsed["number"].as_u64()?;
+            let name = format!("pr-{}", number);
+            match parsed["action"].as_str()?
+            {
+                "opened" | "reopened" | "synchronize" => Some(PreviewEvent::Update { name, refspec: format!("refs/pull/{}/head", number) }),
+                "closed" => Some(PreviewEvent::Remove { name }),
+                _ => None
+            }
+        },
+        _ => None
+    }
+}
+
+/// Create, update or remove [Previews] from authentic Github push events on
+///   previewed branches and pull request events, see [preview_event]. [StatusCode::OK]
+///   is returned without waiting for [Previews::handle]. Other requests are passed on,
+///   e.g. to [filter_github]
+pub async fn filter_github_previews
+(
+    State(previews): State<Previews>,
+    headers: HeaderMap,
+    request: Request<axum::body::Body>,
+    next: Next
+) -> Result<Response, StatusCode>
+{
+    let event = match github_event(&headers)
+    {
+        Some(e) if e == "push" || e == "pull_request" => e,
+        _ => return Ok(next.run(request).await)
+    };
+    let config = Config::load_or_default(CONFIG_PATH);
+    let (git, preview_config, token) = match (config.git.clone(), config.previews.clone(), get_token())
+    {
+        (Some(git), Some(preview_config), Some(token)) => (git, preview_config, token),
+        _ => return Ok(next.run(request).await)
+    };
+
+    let (parts, body) = request.into_parts();
+    let bytes = match extract_bytes(Request::from_parts(parts.clone(), body)).await
+    {
+        Ok(b) => b,
+        Err(_) => return Err(StatusCode::BAD_REQUEST)
+    };
+
+    let preview = match preview_event(&event, &bytes, &git, &preview_config)
+    {
+        Some(p) if is_watched_repo(&bytes, &git.remote) => p,
+        _ => return Ok(next.run(Request::from_parts(parts, Body::from(bytes))).await)
+    };
+
+    match super::is_authentic(&headers, "x-hub-signature-256", token, &bytes)
+    {
+        StatusCode::ACCEPTED => {},
+        status =>
+        {
+            crate::debug(format!("Authentication error: {}", status), Some("GITHUB"));
+            return Ok(status.into_response())
+        }
+    }
+
+    // respond before Github's delivery times out, checkouts and builds may be slow
+    tokio::spawn(async move
+    {
+        if let Err(e) = previews.handle(preview, &config).await
+        {
+            crate::debug(format!("Preview error: {}", e), Some("GITHUB"));
+        }
+    });
+    Ok(StatusCode::OK.into_response())
+}
@@ -0,0 +1,117 @@
+use std::{sync::Arc, time::SystemTime};
+
+use axum::{async_trait, body::Bytes, extract::State, http::{HeaderMap, Request}, middleware::Next, response::Response};
+use openssl::memcmp;
+use reqwest::StatusCode;
+use tokio::sync::Mutex;
+
+use crate::config::GitConfig;
+
+use super::forge::{filter_push, parse_body, ForgeWebhook};
+
+/// Gitlab push hooks, authenticated by the x-gitlab-token secret token
+pub struct Gitlab;
+
+#[async_trait]
+impl ForgeWebhook for Gitlab
+{
+    const NAME: &'static str = "GITLAB";
+
+    async fn is_push(headers: &HeaderMap) -> StatusCode { is_push(headers) }
+
+    fn is_watched_repo(body: &Bytes, url: &str) -> bool { is_watched_repo(body, url) }
+
+    fn is_tracked_branch(body: &Bytes, branch: &str) -> bool { is_tracked_branch(body, branch) }
+
+    fn is_authentic(headers: &HeaderMap, token: String, _body: &Bytes) -> StatusCode { is_authentic(headers, &token) }
+}
+
+/// If x-gitlab-event is Push Hook pull the repo if
+///  [crate::config::GitConfig] and its remote_webhook_token are not None
+pub async fn filter_gitlab
+(
+    state: State<Arc<Mutex<SystemTime>>>,
+    headers: HeaderMap,
+    request: Request<axum::body::Body>,
+    next: Next
+) -> Result<Response, StatusCode>
+{
+    filter_push::<Gitlab>(state, headers, request, next).await
+}
+
+/// Checks the Gitlab webhook event is authentic, matches [GitConfig::remote]
+///   and pushes to [GitConfig::branch]. If so tries to pull
+pub async fn handle_push
+(
+    repo_lock: Arc<Mutex<SystemTime>>,
+    headers: HeaderMap,
+    request: Request<axum::body::Body>,
+    git: GitConfig,
+    token: String
+) -> StatusCode
+{
+    super::forge::handle_push::<Gitlab>(repo_lock, headers, request, &git.remote, &git.branch, token).await
+}
+
+/// Compare x-gitlab-token with token in constant time
+pub fn is_authentic(headers: &HeaderMap, token: &str) -> StatusCode
+{
+    let sent = match headers.get("x-gitlab-token")
+    {
+        Some(t) => t.as_bytes(),
+        None =>
+        {
+            crate::debug("no x-gitlab-token".to_string(), Some("GITLAB"));
+            return StatusCode::UNAUTHORIZED
+        }
+    };
+
+    match !token.is_empty() && sent.len() == token.len() && memcmp::eq(sent, token.as_bytes())
+    {
+        true => StatusCode::ACCEPTED,
+        false => StatusCode::UNAUTHORIZED
+    }
+}
+
+/// Check the url in json path ["project"]["git_http_url"] or
+///   ["project"]["git_ssh_url"] of a [Bytes] body matches url
+pub fn is_watched_repo(body: &Bytes, url: &str) -> bool
+{
+    let parsed = match parse_body(body, "GITLAB")
+    {
+        Some(p) => p,
+        None => return false
+    };
+    ["git_http_url", "git_ssh_url"].iter().any(|key| parsed["project"][key].as_str() == Some(url))
+}
+
+/// Check the ["ref"] of a [Bytes] push body is refs/heads/branch
+pub fn is_tracked_branch(body: &Bytes, branch: &str) -> bool
+{
+    match parse_body(body, "GITLAB")
+    {
+        Some(parsed) => parsed["ref"].as_str() == Some(&format!("refs/heads/{}", branch)),
+        None => false
+    }
+}
+
+/// Check if the headers conform to a gitlab push webhook event
+///  without checking it is legitimate
+pub fn is_push(headers: &HeaderMap) -> StatusCode
+{
+    match headers.get("x-gitlab-event").map(|e| e.to_str())
+    {
+        None => StatusCode::CONTINUE,
+        Some(Ok("Push Hook")) =>
+        {
+            crate::debug("Recieving gitlab push event".to_string(), Some("GITLAB"));
+            StatusCode::OK
+        },
+        Some(Ok(_)) => StatusCode::CONTINUE,
+        Some(Err(e)) =>
+        {
+            crate::debug(format!("Invalid utf8 in x-gitlab-event, {}", e), Some("GITLAB"));
+            StatusCode::BAD_REQUEST
+        }
+    }
+}
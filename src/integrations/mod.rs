@@ -8,8 +8,12 @@ use regex::Regex;
 
 use crate::util::{read_bytes, dump_bytes};
 
+pub mod bitbucket;
 pub mod discord;
+pub mod forge;
+pub mod gitea;
 pub mod github;
+pub mod gitlab;
 pub mod git;
 pub mod webhook;
 
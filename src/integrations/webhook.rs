@@ -1,11 +1,23 @@
+use core::fmt;
+
 use serde::{Serialize, Deserialize};
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+use crate::config::REDACTED;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Webhook 
 {
     addr: String
 }
 
+/// The url is a secret, it is not printed
+impl fmt::Debug for Webhook
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Webhook {{ addr: {} }}", REDACTED)
+    }
+}
+
 /// Wraps a webhook url
 impl Webhook
 {
@@ -1,9 +1,14 @@
 use std::time::Duration;
 
 use busser::config::{read_config, Config, CONFIG_PATH};
+use busser::content::links::{notify_broken_links, BrokenLink};
 use busser::content::sitemap::SiteMap;
+use busser::content::watcher::{ContentWatcher, DEFAULT_DEBOUNCE};
 use busser::integrations::discord::post::try_post;
 use busser::integrations::git::clean_and_clone;
+use busser::integrations::git::build::{build_and_publish, checkout_path};
+use busser::integrations::git::refresh::GitRefreshTask;
+use busser::integrations::git::releases::Releases;
 use busser::server::http::ServerHttp;
 use busser::server::https::Server;
 use busser::util::formatted_differences;
@@ -31,6 +36,16 @@ async fn main() {
         unsafe { busser::OPTIONS.debug_timestamp = true; }
     }
 
+    if args.iter().any(|x| x == "--dump-config")
+    {
+        match read_config(CONFIG_PATH)
+        {
+            Some(c) => println!("{}", c.dump()),
+            None => println!("No valid config found at {}", CONFIG_PATH)
+        }
+        std::process::exit(0);
+    }
+
     let insert_tag = if args.iter().any(|x| x == "--no-tagging")
     {
         false
@@ -47,9 +62,17 @@ async fn main() {
     {
         Some(c) =>
         {
-            if c.git.is_some()
+            if let Some(releases) = Releases::new(&c)
+            {
+                if let Err(e) = releases.deploy(&c)
+                {
+                    busser::debug(format!("Inital deploy: {}", e), None);
+                    if releases.active().is_none() { std::process::exit(1); }
+                }
+            }
+            else if c.git.is_some()
             {
-                match clean_and_clone(&c.content.path, c.git.unwrap())
+                match clean_and_clone(&checkout_path(&c), c.git.clone().unwrap())
                 {
                     Ok(_) => (),
                     Err(e) =>
@@ -58,6 +81,12 @@ async fn main() {
                         std::process::exit(1);
                     }
                 }
+                // the previous build is served if this one fails
+                if let Err(e) = build_and_publish(&c, &checkout_path(&c))
+                {
+                    GitRefreshTask::notify_build_failure(&e, &c).await;
+                    if !std::path::Path::new(&c.content.path).exists() { std::process::exit(1); }
+                }
             }
         },
         None =>
@@ -67,11 +96,22 @@ async fn main() {
         }
     };
 
+    let config = Config::load_or_default(CONFIG_PATH);
     if args.iter().any(|x| x == "--static-sitemap")
     {
         busser::debug(format!("Serving with static sitemap"), None);
         serve(insert_tag).await;
     }
+    else if config.content.watch.is_some_and(|w| w) && config.git.as_ref().is_some_and(|g| !g.is_watchable())
+    {
+        println!("Content cannot be watched with git objects, releases or builds. Serving with dynamic sitemap");
+        serve_observed(insert_tag).await;
+    }
+    else if config.content.watch.is_some_and(|w| w)
+    {
+        busser::debug(format!("Serving with watched sitemap"), None);
+        serve_watched(insert_tag).await;
+    }
     else
     {
         busser::debug(format!("Serving with dynamic sitemap"), None);
@@ -79,6 +119,70 @@ async fn main() {
     }
 }
 
+/// Serve by watching the site content found at the path [busser::config::ContentConfig]
+///  for file system events (see [ContentWatcher]). Changed files are applied to the
+///  sitemap incrementally (see [SiteMap::apply_changes]), edits are served in place and
+///  the server is only re-served if uris are added or removed.
+///
+///  Falls back to [serve_observed] if the content path cannot be watched.
+async fn serve_watched(insert_tag: bool)
+{
+    let config = Config::load_or_default(CONFIG_PATH);
+
+    let mut watcher = match ContentWatcher::new(&config.content.path, DEFAULT_DEBOUNCE)
+    {
+        Ok(w) => w,
+        Err(e) =>
+        {
+            busser::debug(format!("Could not watch content, {}. Serving with dynamic sitemap", e), None);
+            return serve_observed(insert_tag).await;
+        }
+    };
+
+    let mut sitemap = SiteMap::build(&config, insert_tag, false);
+    let mut broken = report_links(&sitemap, vec![], &config).await;
+
+    if let Some(true) = config.content.static_content
+    {
+        sitemap.refresh_all().await;
+    }
+
+    let (server, tasks) = Server::new(0,0,0,0,sitemap.clone());
+    let mut server_handle = server.get_handle();
+    let mut thread_handle = spawn(async move {server.serve()}.await);
+    let mut task_handle = spawn(async move {tasks.run()}.await);
+
+    while let Some(paths) = watcher.next().await
+    {
+        let uris = sitemap.collect_uris();
+
+        let changed = sitemap.apply_changes(paths).await;
+        broken = report_links(&sitemap, broken, &config).await;
+
+        if changed
+        {
+            busser::debug(format!("Sitemap changed, shutting down"), None);
+            server_handle.shutdown();
+            thread_handle.abort();
+            task_handle.abort();
+
+            let diffs = formatted_differences(sitemap.collect_uris(), uris);
+
+            let (server, tasks) = Server::new(0,0,0,0,sitemap.clone());
+            server_handle = server.get_handle();
+            thread_handle = spawn(async move {server.serve()}.await);
+            task_handle = spawn(async move {tasks.run()}.await);
+            busser::debug(format!("Re-served\n Diffs:\n{}", diffs), None);
+            if config.content.message_on_sitemap_reload.is_some_and(|x|x)
+            {
+                try_post(config.notification_endpoint.clone(), &format!("The sitemap was refreshed with diffs:\n```{}```", diffs)).await;
+            }
+        }
+    }
+
+    busser::debug(format!("Content watcher stopped"), None);
+}
+
 /// Serve by observing the site content found at the path [busser::config::ContentConfig]
 ///  every [busser::config::ContentConfig::server_cache_period_seconds] the sitemap
 ///  hash (see [busser::content::sitemap::SiteMap::get_hash]) is checked, if it is
@@ -91,6 +195,7 @@ async fn serve_observed(insert_tag: bool)
     let config = Config::load_or_default(CONFIG_PATH);
     let mut sitemap = SiteMap::build(&config, insert_tag, false);
     let mut hash = sitemap.get_hash();
+    let mut broken = report_links(&sitemap, vec![], &config).await;
 
     if let Some(true) = config.content.static_content
     {
@@ -110,6 +215,7 @@ async fn serve_observed(insert_tag: bool)
 
         let new_sitemap = SiteMap::build(&config, insert_tag, false);
         let sitemap_hash = new_sitemap.get_hash();
+        broken = report_links(&new_sitemap, broken, &config).await;
 
         if sitemap_hash != hash
         {
@@ -136,6 +242,20 @@ async fn serve_observed(insert_tag: bool)
                 try_post(config.notification_endpoint.clone(), &format!("The sitemap was refreshed with diffs:\n```{}```", diffs)).await;
             }
         }
+        else
+        {
+            // same uris, but e.g. lastmods, feed entries and page text may have changed
+            sitemap.generated().replace_with(new_sitemap.generated());
+            let index = new_sitemap.search_index().read().unwrap().clone();
+            *sitemap.search_index().write().unwrap() = index;
+            let report = new_sitemap.link_report().read().unwrap().clone();
+            *sitemap.link_report().write().unwrap() = report;
+            // e.g. a pull moved the branch head, served at once
+            if let (Some(served), Some(new)) = (sitemap.git_source(), new_sitemap.git_source())
+            {
+                served.set_commit(new.commit());
+            }
+        }
     }
 }
 
@@ -144,6 +264,7 @@ async fn serve(insert_tag: bool)
 {
     let config = Config::load_or_default(CONFIG_PATH);
     let sitemap = SiteMap::build(&config, insert_tag, false);
+    report_links(&sitemap, vec![], &config).await;
     if let Some(true) = config.content.static_content
     {
         sitemap.refresh_all().await;
@@ -151,4 +272,13 @@ async fn serve(insert_tag: bool)
     let (server, tasks) = Server::new(0,0,0,0,sitemap);
     server.serve().await;
     let _ = spawn(async move {tasks.run()}.await);
-}
\ No newline at end of file
+}
+
+/// Notify broken links in the sitemap's [busser::content::links::LinkReport] if they
+///  differ from those previously notified, returns them
+async fn report_links(sitemap: &SiteMap, previous: Vec<BrokenLink>, config: &Config) -> Vec<BrokenLink>
+{
+    let report = sitemap.link_report().read().unwrap().clone();
+    notify_broken_links(&report, &previous, config).await;
+    report.broken
+}
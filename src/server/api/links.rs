@@ -0,0 +1,143 @@
+use std::str::from_utf8;
+
+use axum::{body::Bytes, extract::State, http::{HeaderMap, Request}, middleware::Next, response::{IntoResponse, Response}};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::{config::{read_config, CONFIG_PATH}, content::links::SharedLinkReport, integrations::{discord::post::try_post, is_authentic}, util::extract_bytes};
+
+use super::ApiRequest;
+
+/// Payload for [LinkCheck] Api request, the body may be empty
+///  - ```post_discord```: whether to also post the broken links to discord
+#[derive(Deserialize, Default)]
+pub struct LinkCheckPayload
+{
+    #[serde(default)]
+    post_discord: bool
+}
+
+/// Respond with the [crate::content::links::LinkReport] from the last sitemap
+///   build as JSON
+pub struct LinkCheck
+{
+    payload: LinkCheckPayload
+}
+
+impl LinkCheck
+{
+    pub fn new() -> LinkCheck
+    {
+        LinkCheck { payload: LinkCheckPayload::default() }
+    }
+}
+
+impl Default for LinkCheck
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+impl ApiRequest for LinkCheck
+{
+    type State = SharedLinkReport;
+
+    fn is_authentic(headers: HeaderMap, body: Bytes) -> StatusCode
+    {
+        let config = match read_config(CONFIG_PATH)
+        {
+            Some(c) => c,
+            None => return StatusCode::INTERNAL_SERVER_ERROR
+        };
+
+        match config.api_token
+        {
+            Some(token) => is_authentic(&headers, "busser-token", token, &body),
+            None => StatusCode::ACCEPTED
+        }
+    }
+
+    fn deserialise_payload(&mut self, _headers: HeaderMap, body: Bytes) -> StatusCode
+    {
+        if body.iter().all(|b| b.is_ascii_whitespace())
+        {
+            self.payload = LinkCheckPayload::default();
+            return StatusCode::OK
+        }
+
+        self.payload = match from_utf8(&body).map_err(|e| e.to_string()).and_then(|s| serde_json::from_str(s).map_err(|e| e.to_string()))
+        {
+            Ok(p) => p,
+            Err(e) =>
+            {
+                crate::debug(format!("{} deserialising POST payload", e), Some("Link Check"));
+                return StatusCode::BAD_REQUEST
+            }
+        };
+
+        StatusCode::OK
+    }
+
+    async fn respond(&self, report: Self::State) -> (Option<String>, StatusCode)
+    {
+        let report = report.read().unwrap().clone();
+
+        if self.payload.post_discord
+        {
+            if let Some(config) = read_config(CONFIG_PATH)
+            {
+                let msg = report.message(&config.domain, 20).unwrap_or(format!("No broken links on {}", config.domain));
+                try_post(config.notification_endpoint, &msg).await;
+            }
+        }
+
+        match serde_json::to_string(&report)
+        {
+            Ok(json) => (Some(json), StatusCode::OK),
+            Err(_) => (None, StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+
+    async fn filter
+    (
+        State(report): State<SharedLinkReport>,
+        headers: HeaderMap,
+        request: Request<axum::body::Body>,
+        next: Next
+    ) -> Result<Response, StatusCode>
+    {
+        match headers.get("api").map(|a| a.as_bytes())
+        {
+            Some(b"LinkCheck") => {},
+            _ => return Ok(next.run(request).await)
+        }
+
+        let bytes = match extract_bytes(request).await
+        {
+            Ok(b) => b,
+            Err(_) => return Err(StatusCode::BAD_REQUEST)
+        };
+
+        match LinkCheck::is_authentic(headers.clone(), bytes.clone())
+        {
+            StatusCode::ACCEPTED => {},
+            e => { return Ok(e.into_response()) }
+        }
+
+        let mut request = LinkCheck::new();
+
+        match request.deserialise_payload(headers, bytes)
+        {
+            StatusCode::OK => {},
+            e => { return Ok(e.into_response()) }
+        }
+
+        match request.respond(report).await
+        {
+            (Some(json), _) => Ok(([("content-type", "application/json")], json).into_response()),
+            (None, status) => Err(status)
+        }
+    }
+}
@@ -1,19 +1,23 @@
 pub mod stats;
-
-use std::sync::Arc;
+pub mod links;
+pub mod short_links;
+pub mod pin;
+pub mod releases;
 
 use axum::{
     body::Bytes, extract::State, http::{HeaderMap, Request}, middleware::Next, response::Response
 };
 use reqwest::StatusCode;
-use tokio::sync::Mutex;
 
-use super::stats::hits::HitStats;
+use crate::integrations::is_authentic;
 
 /// A trait representing an API request to the server
 ///  - For example [crate::server::api::stats::StatsDigest]
 pub trait ApiRequest
 {
+    /// Server state the request is answered from, e.g. [crate::server::stats::hits::HitStats]
+    type State: Clone + Send + Sync + 'static;
+
     /// Validate a request's hmac given a token read from config.json
     ///   - See [crate::config::Config] and [crate::integrations::is_authentic]
     fn is_authentic(headers: HeaderMap, body: Bytes) -> StatusCode;
@@ -21,7 +25,7 @@ pub trait ApiRequest
     fn deserialise_payload(&mut self, headers: HeaderMap, body: Bytes) -> StatusCode;
     /// Formulate a response form the server returned as a String
     ///   - Also perform any actions inherent to this Api call
-    fn into_response(&self, stats: Option<HitStats>) -> impl std::future::Future<Output = (Option<String>, StatusCode)> + Send;
+    fn respond(&self, state: Self::State) -> impl std::future::Future<Output = (Option<String>, StatusCode)> + Send;
     /// Axum middleware to
     ///     1. check headers for an api request type
     ///     2. authenticate the request (HMAC)
@@ -29,10 +33,21 @@ pub trait ApiRequest
     ///     4. continue on to the next reqeust
     fn filter
     (
-        stats: State<Option<Arc<Mutex<HitStats>>>>,
+        state: State<Self::State>,
         headers: HeaderMap,
         request: Request<axum::body::Body>,
         next: Next
     ) -> impl std::future::Future<Output = Result<Response, StatusCode>> + Send;
 
-}
\ No newline at end of file
+}
+
+/// Authenticate a request which changes what is served, e.g. [short_links::ShortLink],
+///   with [crate::config::Config::api_token]. Without a token these requests are refused
+pub fn require_token(token: Option<String>, headers: &HeaderMap, body: &Bytes) -> StatusCode
+{
+    match token
+    {
+        Some(token) => is_authentic(headers, "busser-token", token, body),
+        None => StatusCode::UNAUTHORIZED
+    }
+}
@@ -0,0 +1,150 @@
+use std::str::from_utf8;
+
+use axum::{body::Bytes, extract::State, http::{HeaderMap, Request}, middleware::Next, response::{IntoResponse, Response}};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::{read_config, CONFIG_PATH}, integrations::git::{objects::GitSource, short_oid}, util::extract_bytes};
+
+use super::{require_token, ApiRequest};
+
+/// Payload for [PinCommit] Api request
+///  - ```commit```: the (short) hash, or other revision, to serve. If absent or null
+///    the pin is released and the branch head is served
+#[derive(Deserialize, Default)]
+pub struct PinCommitPayload
+{
+    commit: Option<String>
+}
+
+/// Response to [PinCommit], the served commit and the pinned commit if any
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PinStatus
+{
+    pub commit: String,
+    pub pinned: Option<String>
+}
+
+/// Pin the commit content is served from, or release the pin, see [GitSource::pin].
+///   The switch applies to the next request for each page
+pub struct PinCommit
+{
+    payload: PinCommitPayload
+}
+
+impl PinCommit
+{
+    pub fn new() -> PinCommit
+    {
+        PinCommit { payload: PinCommitPayload::default() }
+    }
+}
+
+impl Default for PinCommit
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+impl ApiRequest for PinCommit
+{
+    type State = GitSource;
+
+    fn is_authentic(headers: HeaderMap, body: Bytes) -> StatusCode
+    {
+        let config = match read_config(CONFIG_PATH)
+        {
+            Some(c) => c,
+            None => return StatusCode::INTERNAL_SERVER_ERROR
+        };
+
+        require_token(config.api_token, &headers, &body)
+    }
+
+    fn deserialise_payload(&mut self, _headers: HeaderMap, body: Bytes) -> StatusCode
+    {
+        self.payload = match from_utf8(&body).map_err(|e| e.to_string()).and_then(|s| serde_json::from_str(s).map_err(|e| e.to_string()))
+        {
+            Ok(p) => p,
+            Err(e) =>
+            {
+                crate::debug(format!("{} deserialising POST payload", e), Some("Pin Commit"));
+                return StatusCode::BAD_REQUEST
+            }
+        };
+
+        StatusCode::OK
+    }
+
+    async fn respond(&self, git: Self::State) -> (Option<String>, StatusCode)
+    {
+        let result = match self.payload.commit
+        {
+            Some(ref commit) => git.pin(commit),
+            None => git.unpin()
+        };
+
+        match result
+        {
+            Ok(commit) =>
+            {
+                crate::debug(format!("Serving commit {}, pinned {:?}", commit, self.payload.commit), Some("GIT"));
+                let status = PinStatus { commit: short_oid(commit), pinned: git.pinned() };
+                match serde_json::to_string(&status)
+                {
+                    Ok(json) => (Some(json), StatusCode::OK),
+                    Err(_) => (None, StatusCode::INTERNAL_SERVER_ERROR)
+                }
+            },
+            Err(e) =>
+            {
+                crate::debug(format!("Could not pin {:?}, {}", self.payload.commit, e), Some("GIT"));
+                (Some(e.why), StatusCode::BAD_REQUEST)
+            }
+        }
+    }
+
+    async fn filter
+    (
+        State(git): State<GitSource>,
+        headers: HeaderMap,
+        request: Request<axum::body::Body>,
+        next: Next
+    ) -> Result<Response, StatusCode>
+    {
+        match headers.get("api").map(|a| a.as_bytes())
+        {
+            Some(b"PinCommit") => {},
+            _ => return Ok(next.run(request).await)
+        }
+
+        let bytes = match extract_bytes(request).await
+        {
+            Ok(b) => b,
+            Err(_) => return Err(StatusCode::BAD_REQUEST)
+        };
+
+        match PinCommit::is_authentic(headers.clone(), bytes.clone())
+        {
+            StatusCode::ACCEPTED => {},
+            e => { return Ok(e.into_response()) }
+        }
+
+        let mut request = PinCommit::new();
+
+        match request.deserialise_payload(headers, bytes)
+        {
+            StatusCode::OK => {},
+            e => { return Ok(e.into_response()) }
+        }
+
+        match request.respond(git).await
+        {
+            (Some(body), StatusCode::OK) => Ok(([("content-type", "application/json")], body).into_response()),
+            (Some(body), status) => Ok((status, body).into_response()),
+            (None, status) => Err(status)
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::str::from_utf8;
+
+use axum::{body::Bytes, extract::State, http::{HeaderMap, Request}, middleware::Next, response::{IntoResponse, Response}};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::{config::{read_config, CONFIG_PATH}, integrations::git::releases::Releases, util::extract_bytes};
+
+use super::{require_token, ApiRequest};
+
+/// Payload for [Rollback] Api request, by ```action```
+///  - ```{"action": "rollback"}```: activate the release before the active release
+///  - ```{"action": "activate", "name": "20240601T120000000-abc1234"}```: activate any kept release
+///  - ```{"action": "list"}```
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum RollbackPayload
+{
+    Rollback,
+    Activate { name: String },
+    List
+}
+
+/// Roll back, or forward, to a kept release, see [Releases]. Responds with all
+///   releases as JSON
+pub struct Rollback
+{
+    payload: RollbackPayload
+}
+
+impl Rollback
+{
+    pub fn new() -> Rollback
+    {
+        Rollback { payload: RollbackPayload::List }
+    }
+}
+
+impl Default for Rollback
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+impl ApiRequest for Rollback
+{
+    type State = Releases;
+
+    fn is_authentic(headers: HeaderMap, body: Bytes) -> StatusCode
+    {
+        let config = match read_config(CONFIG_PATH)
+        {
+            Some(c) => c,
+            None => return StatusCode::INTERNAL_SERVER_ERROR
+        };
+
+        require_token(config.api_token, &headers, &body)
+    }
+
+    fn deserialise_payload(&mut self, _headers: HeaderMap, body: Bytes) -> StatusCode
+    {
+        self.payload = match from_utf8(&body).map_err(|e| e.to_string()).and_then(|s| serde_json::from_str(s).map_err(|e| e.to_string()))
+        {
+            Ok(p) => p,
+            Err(e) =>
+            {
+                crate::debug(format!("{} deserialising POST payload", e), Some("Rollback"));
+                return StatusCode::BAD_REQUEST
+            }
+        };
+
+        StatusCode::OK
+    }
+
+    async fn respond(&self, releases: Self::State) -> (Option<String>, StatusCode)
+    {
+        let result = match &self.payload
+        {
+            RollbackPayload::Rollback => releases.rollback().map(|_| ()),
+            RollbackPayload::Activate { name } => releases.activate(name),
+            RollbackPayload::List => Ok(())
+        };
+
+        match result
+        {
+            Ok(_) => match serde_json::to_string(&releases.list())
+            {
+                Ok(json) => (Some(json), StatusCode::OK),
+                Err(_) => (None, StatusCode::INTERNAL_SERVER_ERROR)
+            },
+            Err(e) =>
+            {
+                crate::debug(e.to_string(), Some("Rollback"));
+                (Some(e.why), StatusCode::BAD_REQUEST)
+            }
+        }
+    }
+
+    async fn filter
+    (
+        State(releases): State<Releases>,
+        headers: HeaderMap,
+        request: Request<axum::body::Body>,
+        next: Next
+    ) -> Result<Response, StatusCode>
+    {
+        match headers.get("api").map(|a| a.as_bytes())
+        {
+            Some(b"Rollback") => {},
+            _ => return Ok(next.run(request).await)
+        }
+
+        let bytes = match extract_bytes(request).await
+        {
+            Ok(b) => b,
+            Err(_) => return Err(StatusCode::BAD_REQUEST)
+        };
+
+        match Rollback::is_authentic(headers.clone(), bytes.clone())
+        {
+            StatusCode::ACCEPTED => {},
+            e => { return Ok(e.into_response()) }
+        }
+
+        let mut request = Rollback::new();
+
+        match request.deserialise_payload(headers, bytes)
+        {
+            StatusCode::OK => {},
+            e => { return Ok(e.into_response()) }
+        }
+
+        match request.respond(releases).await
+        {
+            (Some(body), status) if status.is_success() => Ok((status, [("content-type", "application/json")], body).into_response()),
+            (Some(body), status) => Ok((status, body).into_response()),
+            (None, status) => Err(status)
+        }
+    }
+}
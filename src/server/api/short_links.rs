@@ -0,0 +1,148 @@
+use std::str::from_utf8;
+
+use axum::{body::Bytes, extract::State, http::{HeaderMap, Request}, middleware::Next, response::{IntoResponse, Response}};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::{config::{read_config, ShortLinkConfig, CONFIG_PATH}, server::short_links::ShortLinks, util::extract_bytes};
+
+use super::{require_token, ApiRequest};
+
+/// Payload for [ShortLink] Api request, by ```action```
+///  - ```{"action": "create", "name": "abc", "target": "/a/long/uri", "expires_utc": "2025-01-01T00:00:00Z"}```,
+///    ```expires_utc``` is optional. An existing link is replaced
+///  - ```{"action": "delete", "name": "abc"}```
+///  - ```{"action": "list"}```
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum ShortLinkPayload
+{
+    Create { name: String, target: String, expires_utc: Option<String> },
+    Delete { name: String },
+    List
+}
+
+/// Create, delete or list [ShortLinks], responds with all links as JSON
+pub struct ShortLink
+{
+    payload: ShortLinkPayload
+}
+
+impl ShortLink
+{
+    pub fn new() -> ShortLink
+    {
+        ShortLink { payload: ShortLinkPayload::List }
+    }
+}
+
+impl Default for ShortLink
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+impl ApiRequest for ShortLink
+{
+    type State = ShortLinks;
+
+    fn is_authentic(headers: HeaderMap, body: Bytes) -> StatusCode
+    {
+        let config = match read_config(CONFIG_PATH)
+        {
+            Some(c) => c,
+            None => return StatusCode::INTERNAL_SERVER_ERROR
+        };
+
+        require_token(config.api_token, &headers, &body)
+    }
+
+    fn deserialise_payload(&mut self, _headers: HeaderMap, body: Bytes) -> StatusCode
+    {
+        self.payload = match from_utf8(&body).map_err(|e| e.to_string()).and_then(|s| serde_json::from_str(s).map_err(|e| e.to_string()))
+        {
+            Ok(p) => p,
+            Err(e) =>
+            {
+                crate::debug(format!("{} deserialising POST payload", e), Some("Short Link"));
+                return StatusCode::BAD_REQUEST
+            }
+        };
+
+        StatusCode::OK
+    }
+
+    async fn respond(&self, links: Self::State) -> (Option<String>, StatusCode)
+    {
+        let result = match &self.payload
+        {
+            ShortLinkPayload::Create { name, target, expires_utc } =>
+            {
+                links.insert(name, ShortLinkConfig { target: target.clone(), expires_utc: expires_utc.clone() }).map(|_| StatusCode::CREATED)
+            },
+            ShortLinkPayload::Delete { name } =>
+            {
+                links.remove(name).map(|removed| if removed { StatusCode::OK } else { StatusCode::NOT_FOUND })
+            },
+            ShortLinkPayload::List => Ok(StatusCode::OK)
+        };
+
+        match result
+        {
+            Ok(status) => match serde_json::to_string(&links.all())
+            {
+                Ok(json) => (Some(json), status),
+                Err(_) => (None, StatusCode::INTERNAL_SERVER_ERROR)
+            },
+            Err(e) =>
+            {
+                crate::debug(e.to_string(), Some("Short Link"));
+                (Some(e.why), StatusCode::BAD_REQUEST)
+            }
+        }
+    }
+
+    async fn filter
+    (
+        State(links): State<ShortLinks>,
+        headers: HeaderMap,
+        request: Request<axum::body::Body>,
+        next: Next
+    ) -> Result<Response, StatusCode>
+    {
+        match headers.get("api").map(|a| a.as_bytes())
+        {
+            Some(b"ShortLink") => {},
+            _ => return Ok(next.run(request).await)
+        }
+
+        let bytes = match extract_bytes(request).await
+        {
+            Ok(b) => b,
+            Err(_) => return Err(StatusCode::BAD_REQUEST)
+        };
+
+        match ShortLink::is_authentic(headers.clone(), bytes.clone())
+        {
+            StatusCode::ACCEPTED => {},
+            e => { return Ok(e.into_response()) }
+        }
+
+        let mut request = ShortLink::new();
+
+        match request.deserialise_payload(headers, bytes)
+        {
+            StatusCode::OK => {},
+            e => { return Ok(e.into_response()) }
+        }
+
+        match request.respond(links).await
+        {
+            (Some(body), status) if status.is_success() => Ok((status, [("content-type", "application/json")], body).into_response()),
+            (Some(body), status) => Ok((status, body).into_response()),
+            (None, status) => Err(status)
+        }
+    }
+}
@@ -48,6 +48,8 @@ impl StatsDigest
 
 impl ApiRequest for StatsDigest
 {
+    type State = Option<Arc<Mutex<HitStats>>>;
+
     fn is_authentic(headers: HeaderMap, body: Bytes) -> StatusCode
     {
 
@@ -100,8 +102,14 @@ impl ApiRequest for StatsDigest
         StatusCode::OK
     }
 
-    async fn into_response(&self, stats: Option<HitStats>) -> (Option<String>, StatusCode)
+    async fn respond(&self, stats: Self::State) -> (Option<String>, StatusCode)
     {
+        let stats = match stats
+        {
+            Some(stats) => Some(stats.lock().await.to_owned()),
+            None => None
+        };
+
         let config = match read_config(CONFIG_PATH)
         {
             Some(c) => c,
@@ -210,18 +218,7 @@ impl ApiRequest for StatsDigest
             e => { return Ok(e.into_response()) }
         }
 
-        let (result, status) = if stats.is_none()
-        {
-            response.into_response(None).await
-        }
-        else
-        {
-            let stats_unwrapped = stats.unwrap();
-            let stats_lock = stats_unwrapped.lock().await;
-            let s = stats_lock.to_owned();
-            response.into_response(Some(s)).await
-        };
-        
+        let (result, status) = response.respond(stats).await;
 
         match result
         {
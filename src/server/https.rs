@@ -1,6 +1,6 @@
 use crate::
 {
-    config::{read_config, CONFIG_PATH}, content::{error_page::ErrorPage, sitemap::SiteMap}, integrations::{git::refresh::GitRefreshTask, github::filter_github}, server::throttle::{handle_throttle, IpThrottler}, task::{schedule_from_option, TaskPool}, CRAB
+    config::{read_config, CONFIG_PATH}, content::{error_page::ErrorPage, sitemap::SiteMap}, integrations::{git::{refresh::GitRefreshTask, releases::Releases}, github::{filter_github, filter_github_previews}, gitlab::filter_gitlab, forge::filter_push, gitea::Gitea, bitbucket::Bitbucket}, server::throttle::{handle_throttle, IpThrottler}, task::{schedule_from_option, TaskPool}, CRAB
 };
 
 use core::time;
@@ -16,7 +16,7 @@ use axum::
 };
 use axum_server::{tls_rustls::RustlsConfig, Handle};
 
-use super::{api::{stats::StatsDigest, ApiRequest}, relay::request::filter_relay, stats::{hits::{log_stats, HitStats}, StatsDigestTask, StatsSaveTask}};
+use super::{api::{links::LinkCheck, pin::PinCommit, releases::Rollback, short_links::ShortLink, stats::StatsDigest, ApiRequest}, previews::Previews, relay::request::filter_relay, short_links::ShortLinks, stats::{hits::{log_stats, HitStats}, StatsDigestTask, StatsSaveTask}};
 
 /// An https server that reads a directory configured with [Config]
 /// ```.html``` pages and resources, then serves them.
@@ -78,8 +78,22 @@ impl Server
 
         let throttle_state = Arc::new(Mutex::new(requests));
 
+        let link_report = sitemap.link_report().clone();
+        let git_source = sitemap.git_source().cloned();
         let mut router: Router = sitemap.into();
 
+        let error_page = ErrorPage::from(&config);
+        let short_links = config.short_links.as_ref().map(ShortLinks::new);
+        if let Some(ref links) = short_links
+        {
+            router = router.route(&links.uri_pattern(), links.route(error_page.expand_error_code("404")));
+        }
+        let previews = Previews::new(&config);
+        if let Some(ref previews) = previews
+        {
+            router = router.route(&previews.uri_pattern(), previews.route(error_page.expand_error_code("404")));
+        }
+
         let stats = Arc::new(Mutex::new(
             HitStats::new()
         ));
@@ -88,13 +102,32 @@ impl Server
         router = router.layer(middleware::from_fn_with_state(throttle_state.clone(), handle_throttle));
 
         router = router.layer(middleware::from_fn_with_state(Some(stats.clone()), StatsDigest::filter));
+        router = router.layer(middleware::from_fn_with_state(link_report, LinkCheck::filter));
+        if let Some(links) = short_links
+        {
+            router = router.layer(middleware::from_fn_with_state(links, ShortLink::filter));
+        }
+        if let Some(git) = git_source
+        {
+            router = router.layer(middleware::from_fn_with_state(git, PinCommit::filter));
+        }
+        if let Some(releases) = Releases::new(&config)
+        {
+            router = router.layer(middleware::from_fn_with_state(releases, Rollback::filter));
+        }
 
         let repo_mutex = Arc::new(Mutex::new(SystemTime::now()));
 
         router = router.layer(middleware::from_fn_with_state(repo_mutex.clone(), filter_github));
+        router = router.layer(middleware::from_fn_with_state(repo_mutex.clone(), filter_gitlab));
+        router = router.layer(middleware::from_fn_with_state(repo_mutex.clone(), filter_push::<Gitea>));
+        router = router.layer(middleware::from_fn_with_state(repo_mutex.clone(), filter_push::<Bitbucket>));
+        if let Some(previews) = previews
+        {
+            router = router.layer(middleware::from_fn_with_state(previews, filter_github_previews));
+        }
         router = router.layer(middleware::from_fn(filter_relay));
 
-        let error_page = ErrorPage::from(&config);
         router = router.fallback(Html(error_page.expand_error_code("404")));
 
         let server = Server
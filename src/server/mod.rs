@@ -3,4 +3,6 @@ pub mod http;
 pub mod api;
 pub mod throttle;
 pub mod stats;
-pub mod relay;
\ No newline at end of file
+pub mod relay;
+pub mod short_links;
+pub mod previews;
\ No newline at end of file
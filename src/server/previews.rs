@@ -0,0 +1,288 @@
+use std::{collections::BTreeMap, path::Path, sync::{Arc, OnceLock, RwLock}};
+
+use axum::{extract::Request, http::{HeaderMap, HeaderValue, StatusCode}, response::{Html, IntoResponse, Redirect, Response}, routing::{any, MethodRouter}, Router};
+use openssl::{base64::decode_block, memcmp};
+use regex::Regex;
+use tokio::sync::Mutex;
+use tower::ServiceExt;
+
+use crate::{config::{Config, GitConfig, PreviewConfig}, content::sitemap::SiteMap, integrations::{discord::post::try_post, git::{checkout_ref, remove_repository, GitError, HeadInfo}}, util::{dump_bytes, hash, matches_one}};
+
+pub const DEFAULT_PREFIX: &str = "/_preview/";
+pub const DEFAULT_PATH: &str = "previews";
+
+/// A change to previews, e.g. from a webhook event
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreviewEvent
+{
+    /// Create, or update, the preview name from refspec
+    Update { name: String, refspec: String },
+    Remove { name: String }
+}
+
+/// Previews of branches and pull requests, each a [SiteMap] built from a working copy
+///   at [PreviewConfig::path]/name and served from [Previews::prefix] + name + /.
+///   Previews require the [PreviewConfig::token], are not in the site's sitemap and
+///   are not counted in stats. Clones share previews
+#[derive(Clone)]
+pub struct Previews
+{
+    pub prefix: String,
+    pub path: String,
+    token: String,
+    config: Config,
+    served: Arc<RwLock<BTreeMap<String, Router>>>,
+    lock: Arc<Mutex<()>>
+}
+
+impl Previews
+{
+    /// Previews if configured, including those already checked out
+    pub fn new(config: &Config) -> Option<Previews>
+    {
+        let preview_config = config.previews.as_ref()?;
+        let previews = Previews
+        {
+            prefix: prefix(preview_config),
+            path: preview_config.path.clone().unwrap_or(DEFAULT_PATH.to_string()),
+            token: preview_config.token.clone(),
+            config: config.clone(),
+            served: Arc::new(RwLock::new(BTreeMap::new())),
+            lock: Arc::new(Mutex::new(()))
+        };
+
+        if let Ok(entries) = std::fs::read_dir(&previews.path)
+        {
+            for entry in entries.flatten()
+            {
+                if entry.path().join(".git").is_dir()
+                {
+                    previews.build(&entry.file_name().to_string_lossy());
+                }
+            }
+        }
+
+        Some(previews)
+    }
+
+    /// Names of served previews
+    pub fn names(&self) -> Vec<String>
+    {
+        self.served.read().unwrap().keys().cloned().collect()
+    }
+
+    /// The url of a preview
+    pub fn url(&self, name: &str) -> String
+    {
+        let domain = if self.config.domain.starts_with("http") { self.config.domain.clone() } else { format!("https://{}", self.config.domain) };
+        format!("{}{}{}/", domain.trim_end_matches('/'), self.prefix, name)
+    }
+
+    /// Build, or rebuild, the preview name from its working copy
+    pub fn build(&self, name: &str)
+    {
+        let config = self.site_config(name);
+        let router: Router = SiteMap::build(&config, false, true).into();
+        self.served.write().unwrap().insert(name.to_string(), router);
+    }
+
+    /// The site config with content from the preview's working copy, without
+    ///   generated files, search, link checks or git
+    fn site_config(&self, name: &str) -> Config
+    {
+        let mut config = self.config.clone();
+        config.content = config.content.relocated(&format!("{}/{}", self.path, name));
+        config.content.generate_sitemap = Some(false);
+        config.content.robots = None;
+        config.content.feeds = None;
+        config.content.search = None;
+        config.content.check_links = None;
+        config.git = None;
+        config
+    }
+
+    /// Check out refspec into the preview name and (re)build it, true if it is new
+    pub fn update(&self, name: &str, git: &GitConfig, refspec: &str) -> Result<(bool, Option<HeadInfo>), GitError>
+    {
+        let created = !self.served.read().unwrap().contains_key(name);
+        let info = checkout_ref(&format!("{}/{}", self.path, name), git, refspec)?;
+        self.build(name);
+        Ok((created, info))
+    }
+
+    /// Stop serving the preview name and delete its working copy, false if there was no such preview
+    pub fn remove(&self, name: &str) -> Result<bool, GitError>
+    {
+        let removed = self.served.write().unwrap().remove(name).is_some();
+        let dir = format!("{}/{}", self.path, name);
+        if Path::new(&dir).is_dir()
+        {
+            remove_repository(&dir)?;
+        }
+        Ok(removed)
+    }
+
+    /// Apply an event, posting the preview's url to [Config::notification_endpoint] when it is created.
+    ///   Events are applied one at a time, checkouts and builds run on a blocking thread
+    pub async fn handle(&self, event: PreviewEvent, config: &Config) -> Result<(), GitError>
+    {
+        let git = match config.git
+        {
+            Some(ref git) => git.clone(),
+            None => return Err(GitError { why: "Previews require a git config".to_string() })
+        };
+
+        let _lock = self.lock.lock().await;
+        let previews = self.clone();
+        match event
+        {
+            PreviewEvent::Update { name, refspec } =>
+            {
+                let (created, info) =
+                {
+                    let (name, refspec) = (name.clone(), refspec.clone());
+                    blocking(move || previews.update(&name, &git, &refspec)).await?
+                };
+                crate::debug(format!("Updated preview {} from {}, {:?}", name, refspec, info), Some("PREVIEW"));
+                if created
+                {
+                    let commit = info.map_or(String::new(), |i| format!(" at commit {} by {}", i.hash, i.author_name));
+                    let msg = format!("Preview of {}{}:\n{}", refspec, commit, self.url(&name));
+                    try_post(config.notification_endpoint.clone(), &msg).await;
+                }
+            },
+            PreviewEvent::Remove { name } =>
+            {
+                let removed =
+                {
+                    let name = name.clone();
+                    blocking(move || previews.remove(&name)).await?
+                };
+                crate::debug(format!("Removed preview {}, {}", name, removed), Some("PREVIEW"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Serve a request for ```prefix/name/uri``` with the preview's router, if authorised.
+    ///   Responses are not cached or indexed, see [private]
+    pub async fn serve(&self, mut request: Request, not_found: &str) -> Response
+    {
+        if !is_authorised(request.headers(), &self.token)
+        {
+            return (StatusCode::UNAUTHORIZED, [("www-authenticate", "Basic realm=\"preview\"")]).into_response()
+        }
+
+        let path = request.uri().path().strip_prefix(self.prefix.as_str()).unwrap_or_default().to_string();
+        let (name, uri) = match path.split_once('/')
+        {
+            Some((name, uri)) => (name.to_string(), format!("/{}", uri)),
+            // relative links need the trailing /
+            None => return Redirect::permanent(&format!("{}{}/", self.prefix, path)).into_response()
+        };
+
+        let router = match self.served.read().unwrap().get(&name)
+        {
+            Some(router) => router.clone(),
+            None => return (StatusCode::NOT_FOUND, Html(not_found.to_string())).into_response()
+        };
+
+        let uri = match request.uri().query()
+        {
+            Some(query) => format!("{}?{}", uri, query),
+            None => uri
+        };
+        match uri.parse()
+        {
+            Ok(uri) => *request.uri_mut() = uri,
+            Err(_) => return StatusCode::BAD_REQUEST.into_response()
+        }
+
+        match router.oneshot(request).await
+        {
+            Ok(response) => private(response),
+            Err(never) => match never {}
+        }
+    }
+
+    /// A route for ```prefix/*path``` serving previews, or the not_found page
+    pub fn route(&self, not_found: String) -> MethodRouter
+    {
+        let previews = self.clone();
+        any(move |request: Request| async move
+        {
+            previews.serve(request, &not_found).await
+        })
+    }
+
+    /// The uri pattern for [Previews::route]
+    pub fn uri_pattern(&self) -> String
+    {
+        format!("{}*path", self.prefix)
+    }
+}
+
+/// Run a git checkout or build without blocking the runtime's workers
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T, GitError> + Send + 'static) -> Result<T, GitError>
+{
+    match tokio::task::spawn_blocking(f).await
+    {
+        Ok(result) => result,
+        Err(e) => Err(GitError { why: format!("Preview task failed: {}", e) })
+    }
+}
+
+/// Stop shared caches storing, and search engines indexing, an authorised preview response
+pub fn private(mut response: Response) -> Response
+{
+    let headers = response.headers_mut();
+    headers.insert("cache-control", HeaderValue::from_static("private, no-store"));
+    headers.insert("x-robots-tag", HeaderValue::from_static("noindex"));
+    response
+}
+
+/// [PreviewConfig::prefix] (or the default) with leading and trailing /
+pub fn prefix(config: &PreviewConfig) -> String
+{
+    let mut prefix = config.prefix.clone().unwrap_or(DEFAULT_PREFIX.to_string());
+    if !prefix.starts_with('/') { prefix = format!("/{}", prefix); }
+    if !prefix.ends_with('/') { prefix = format!("{}/", prefix); }
+    prefix
+}
+
+/// A preview name for a branch, characters other than letters, digits, - and _ are replaced by -
+///   and a short hash of the branch appended, so e.g. ```feature/x``` and ```feature-x``` differ
+pub fn preview_name(branch: &str) -> String
+{
+    static UNSAFE: OnceLock<Regex> = OnceLock::new();
+    let name = UNSAFE.get_or_init(|| Regex::new(r"[^A-Za-z0-9_-]").unwrap()).replace_all(branch, "-");
+    format!("{}-{}", name, dump_bytes(&hash(branch.as_bytes().to_vec()))[..8].to_lowercase())
+}
+
+/// If pushes to branch are previewed, see [PreviewConfig::branches]
+pub fn is_previewed_branch(config: &PreviewConfig, branch: &str) -> bool
+{
+    config.branches.as_ref().is_some_and(|b| matches_one(branch, b))
+}
+
+/// If the HTTP basic authorization header's password is token, an empty token authorises nothing
+pub fn is_authorised(headers: &HeaderMap, token: &str) -> bool
+{
+    let credentials = match headers.get("authorization").and_then(|a| a.to_str().ok()).and_then(|a| a.strip_prefix("Basic "))
+    {
+        Some(c) => c.trim(),
+        None => return false
+    };
+
+    let decoded = match decode_block(credentials).ok().and_then(|d| String::from_utf8(d).ok())
+    {
+        Some(d) => d,
+        None => return false
+    };
+
+    match decoded.split_once(':')
+    {
+        Some((_user, password)) => !token.is_empty() && password.len() == token.len() && memcmp::eq(password.as_bytes(), token.as_bytes()),
+        None => false
+    }
+}
@@ -0,0 +1,185 @@
+use std::{collections::BTreeMap, fmt, path::Path, sync::{Arc, RwLock}};
+
+use axum::{extract, http::StatusCode, response::{Html, IntoResponse, Redirect}, routing::{get, MethodRouter}};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+
+use crate::{config::{ShortLinkConfig, ShortLinksConfig}, filesystem::file::read_file_utf8};
+
+pub const DEFAULT_PREFIX: &str = "/s/";
+
+#[derive(Debug, Clone)]
+pub struct ShortLinkError
+{
+    pub why: String
+}
+
+impl fmt::Display for ShortLinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.why)
+    }
+}
+
+/// Short links by name, served as redirects from [ShortLinks::prefix] + name.
+///   Links defined in config are fixed, links created by the api are saved to
+///   [ShortLinksConfig::path]. Clones share links
+#[derive(Clone)]
+pub struct ShortLinks
+{
+    pub prefix: String,
+    defined: BTreeMap<String, ShortLinkConfig>,
+    stored: Arc<RwLock<BTreeMap<String, ShortLinkConfig>>>,
+    path: Option<String>
+}
+
+impl ShortLinks
+{
+    /// Links from config and the data file, if it exists
+    pub fn new(config: &ShortLinksConfig) -> ShortLinks
+    {
+        let stored = match config.path.as_deref().filter(|p| Path::new(p).is_file()).and_then(read_file_utf8)
+        {
+            Some(data) => match serde_json::from_str(&data)
+            {
+                Ok(links) => links,
+                Err(e) =>
+                {
+                    crate::debug(format!("Error {} loading short links from {:?}", e, config.path), None);
+                    BTreeMap::new()
+                }
+            },
+            None => BTreeMap::new()
+        };
+
+        ShortLinks
+        {
+            prefix: prefix(config),
+            defined: config.links.clone().unwrap_or_default().into_iter().collect(),
+            stored: Arc::new(RwLock::new(stored)),
+            path: config.path.clone()
+        }
+    }
+
+    /// The link, if it exists and has not expired
+    pub fn get(&self, name: &str) -> Option<ShortLinkConfig>
+    {
+        let link = match self.defined.get(name)
+        {
+            Some(link) => link.clone(),
+            None => self.stored.read().unwrap().get(name)?.clone()
+        };
+        if is_expired(&link, Utc::now()) { None } else { Some(link) }
+    }
+
+    /// All links (including expired links), by name
+    pub fn all(&self) -> BTreeMap<String, ShortLinkConfig>
+    {
+        let mut links = self.stored.read().unwrap().clone();
+        links.extend(self.defined.clone());
+        links
+    }
+
+    /// Create, or replace, a link and save the data file
+    pub fn insert(&self, name: &str, link: ShortLinkConfig) -> Result<(), ShortLinkError>
+    {
+        if !Regex::new(r"^[A-Za-z0-9_-]+$").unwrap().is_match(name)
+        {
+            return Err(ShortLinkError { why: format!("Invalid short link name {}, use letters, digits, - and _", name) })
+        }
+        if link.target.is_empty()
+        {
+            return Err(ShortLinkError { why: format!("Short link {} has no target", name) })
+        }
+        if let Some(ref expires) = link.expires_utc
+        {
+            if DateTime::parse_from_rfc3339(expires).is_err()
+            {
+                return Err(ShortLinkError { why: format!("Cannot parse expires_utc {} as RFC 3339", expires) })
+            }
+        }
+        self.check_writable(name)?;
+
+        let mut stored = self.stored.write().unwrap();
+        let mut links = stored.clone();
+        links.insert(name.to_string(), link);
+        self.save(&links)?;
+        *stored = links;
+        Ok(())
+    }
+
+    /// Delete a link and save the data file, false if there was no such link
+    pub fn remove(&self, name: &str) -> Result<bool, ShortLinkError>
+    {
+        self.check_writable(name)?;
+        let mut stored = self.stored.write().unwrap();
+        if !stored.contains_key(name) { return Ok(false) }
+        let mut links = stored.clone();
+        links.remove(name);
+        self.save(&links)?;
+        *stored = links;
+        Ok(true)
+    }
+
+    fn check_writable(&self, name: &str) -> Result<(), ShortLinkError>
+    {
+        if self.path.is_none()
+        {
+            return Err(ShortLinkError { why: "No short links path is configured".to_string() })
+        }
+        if self.defined.contains_key(name)
+        {
+            return Err(ShortLinkError { why: format!("Short link {} is defined in config", name) })
+        }
+        Ok(())
+    }
+
+    /// Write links to the data file, before they are served so a failed save changes nothing
+    fn save(&self, links: &BTreeMap<String, ShortLinkConfig>) -> Result<(), ShortLinkError>
+    {
+        let path = self.path.as_ref().unwrap();
+        let data = serde_json::to_string_pretty(links)
+            .map_err(|e| ShortLinkError { why: format!("Error {} saving short links to {}", e, path) })?;
+        std::fs::write(path, data).map_err(|e| ShortLinkError { why: format!("Error {} saving short links to {}", e, path) })
+    }
+
+    /// A route for ```prefix/:name``` redirecting to the link's target, or responding
+    ///   with the not_found page. Redirects are temporary so each click reaches
+    ///   the server and is counted in [crate::server::stats::hits::HitStats]
+    pub fn route(&self, not_found: String) -> MethodRouter
+    {
+        let links = self.clone();
+        get(move |extract::Path(name): extract::Path<String>| async move
+        {
+            match links.get(&name)
+            {
+                Some(link) => Redirect::temporary(&link.target).into_response(),
+                None => (StatusCode::NOT_FOUND, Html(not_found)).into_response()
+            }
+        })
+    }
+
+    /// The uri pattern for [ShortLinks::route]
+    pub fn uri_pattern(&self) -> String
+    {
+        format!("{}:name", self.prefix)
+    }
+}
+
+/// [ShortLinksConfig::prefix] (or the default) with leading and trailing /
+pub fn prefix(config: &ShortLinksConfig) -> String
+{
+    let mut prefix = config.prefix.clone().unwrap_or(DEFAULT_PREFIX.to_string());
+    if !prefix.starts_with('/') { prefix = format!("/{}", prefix); }
+    if !prefix.ends_with('/') { prefix = format!("{}/", prefix); }
+    prefix
+}
+
+/// Whether the link expired before now, links with unparseable expiry are expired
+pub fn is_expired(link: &ShortLinkConfig, now: DateTime<Utc>) -> bool
+{
+    match link.expires_utc
+    {
+        Some(ref expires) => DateTime::parse_from_rfc3339(expires).map_or(true, |t| t.to_utc() < now),
+        None => false
+    }
+}
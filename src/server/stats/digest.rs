@@ -2,7 +2,7 @@ use std::{cmp::{max, min}, collections::HashMap};
 
 use chrono::{DateTime, Timelike};
 
-use crate::{config::Config, content::is_page, util::matches_one};
+use crate::{config::Config, content::is_page, server::short_links, util::matches_one};
 
 use super::hits::{collect_hits, HitStats};
 
@@ -15,7 +15,9 @@ pub struct Digest
     pub top_resources: Vec<(String, usize)>,
     pub hits_by_hour_utc: [usize; 24],
     pub total_hits: usize,
-    pub unique_hits: usize
+    pub unique_hits: usize,
+    /// Clicks by short link name, most first, see [crate::server::short_links::ShortLinks]
+    pub short_link_clicks: Vec<(String, usize)>
 }
 
 impl Digest
@@ -29,7 +31,8 @@ impl Digest
             top_resources: vec![],
             hits_by_hour_utc: [0;24],
             total_hits: 0,
-            unique_hits: 0
+            unique_hits: 0,
+            short_link_clicks: vec![]
         }
     }
 }
@@ -61,6 +64,8 @@ pub fn process_hits
     let mut hitters: HashMap<String, usize> = HashMap::new();
     let mut pages: HashMap<String, usize> = HashMap::new();
     let mut resources: HashMap<String, usize> = HashMap::new();
+    let mut short_links: HashMap<String, usize> = HashMap::new();
+    let short_link_prefix = short_link_prefix(config);
 
     for hit in collect_hits(stats, from, to, &config)
     {
@@ -79,7 +84,12 @@ pub fn process_hits
             }
         }
 
-        if is_page(&hit.path, &domain)
+        if let Some(name) = short_link_prefix.as_ref().and_then(|p| hit.path.strip_prefix(p.as_str()))
+        {
+            let name = name.split(['?', '#']).next().unwrap_or_default().to_string();
+            *short_links.entry(name).or_default() += hit.count();
+        }
+        else if is_page(&hit.path, &domain)
         {
             match pages.contains_key(&hit.path)
             {
@@ -121,6 +131,9 @@ pub fn process_hits
         data.reverse();
     }
 
+    digest.short_link_clicks = short_links.into_iter().collect();
+    digest.short_link_clicks.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
     digest.top_hitters = (0..n).map(|_i| ("".to_string(), 0)).collect();
 
     for i in 0..n
@@ -163,6 +176,12 @@ pub fn process_hits
 
 }
 
+/// The uri prefix of short links, if [crate::config::Config::short_links] is configured
+pub fn short_link_prefix(config: &Config) -> Option<String>
+{
+    config.short_links.as_ref().map(short_links::prefix)
+}
+
 /// Post a [Digest] as a formatted message to Discord
 pub fn digest_message(digest: &Digest, from: Option<DateTime<chrono::Utc>>, to: Option<DateTime<chrono::Utc>>) -> String
 {
@@ -206,6 +225,11 @@ pub fn digest_message(digest: &Digest, from: Option<DateTime<chrono::Utc>>, to:
     }
     msg.push_str(format!("Top {n} pages:\n{}\n", top_pages).as_str());
     msg.push_str(format!("Top {n} resources:\n{}\n", top_resources).as_str());
+    if !digest.short_link_clicks.is_empty()
+    {
+        let clicks: String = digest.short_link_clicks.iter().map(|(name, count)| format!("  {} : {}\n", name, count)).collect();
+        msg.push_str(format!("Short link clicks:\n{}\n", clicks).as_str());
+    }
     msg.push_str(format!("Hits by hour (UTC):\n\n{}", hits_by_hour_text_graph(digest.hits_by_hour_utc, '-', 10)).as_str());
 
     msg
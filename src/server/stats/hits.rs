@@ -7,9 +7,9 @@ use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
-use crate::{config::{Config, CONFIG_PATH}, content::sitemap::SiteMap, filesystem::{file::read_file_utf8, folder::list_dir_by}, util::{date_to_rfc3339, dump_bytes}};
+use crate::{config::{Config, CONFIG_PATH}, content::sitemap::SiteMap, server::previews, filesystem::{file::read_file_utf8, folder::list_dir_by}, util::{date_to_rfc3339, dump_bytes}};
 
-use super::digest::Digest;
+use super::digest::{short_link_prefix, Digest};
 
 /// A hit defined by a uri and an ip (hashed) at given times
 /// - [Hit::times]   the unique hit times of equivalent events (controlled by [crate::config::StatsConfig::hit_cooloff_seconds])
@@ -86,6 +86,11 @@ pub async fn process_hit
 
     let config = Config::load_or_default(CONFIG_PATH);
 
+    if config.previews.as_ref().is_some_and(|p| uri.starts_with(&previews::prefix(p)))
+    {
+        return
+    }
+
     let mut stats = state.lock().await;
 
     let compute_start_time = Instant::now();
@@ -217,13 +222,16 @@ pub fn collect_hits
         None
     };
 
+    let short_link_prefix = short_link_prefix(config);
+
     for hit in hits_to_filter
     {
         // check the cached stats are within the time period, then add
         let mut times: Vec<String> = vec![];
         for i in 0..hit.times.len()
         {
-            if uris.as_ref().is_some_and(|u| !u.contains(&hit.path))
+            let short_link = short_link_prefix.as_ref().is_some_and(|p| hit.path.starts_with(p.as_str()));
+            if !short_link && uris.as_ref().is_some_and(|u| !u.contains(&hit.path))
             {
                 continue
             } 
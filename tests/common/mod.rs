@@ -1 +1,72 @@
-pub const BAD_UTF8: [u8; 2] = [0xC0, 0xC1]; 
\ No newline at end of file
+#![allow(dead_code)]
+
+use std::path::Path;
+
+use busser::{config::{Config, GitConfig}, filesystem::file::write_file_bytes};
+use git2::{Oid, Repository, Signature};
+
+pub const BAD_UTF8: [u8; 2] = [0xC0, 0xC1];
+
+/// Commit files (path, data) on top of HEAD, None data removes the file
+pub fn commit(repo: &Repository, files: &[(&str, Option<&str>)], message: &str) -> Oid
+{
+    let parent = repo.head().ok().and_then(|h| h.target());
+    commit_onto(repo, files, message, parent)
+}
+
+/// Commit files onto parent (None for a root commit) and move the branch HEAD
+///   points to, e.g. to rewrite history
+pub fn commit_onto(repo: &Repository, files: &[(&str, Option<&str>)], message: &str, parent: Option<Oid>) -> Oid
+{
+    let root = repo.workdir().unwrap().to_path_buf();
+    let mut index = repo.index().unwrap();
+    for (file, data) in files
+    {
+        match data
+        {
+            Some(data) =>
+            {
+                std::fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+                write_file_bytes(root.join(file).to_str().unwrap(), data.as_bytes());
+                index.add_path(Path::new(file)).unwrap();
+            },
+            None =>
+            {
+                std::fs::remove_file(root.join(file)).unwrap();
+                index.remove_path(Path::new(file)).unwrap();
+            }
+        }
+    }
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = Signature::now("jerboa", "j@b").unwrap();
+    let parent = parent.map(|p| repo.find_commit(p).unwrap());
+    let parents: Vec<_> = parent.iter().collect();
+    let oid = repo.commit(None, &signature, &signature, message, &tree, &parents).unwrap();
+    let branch = repo.find_reference("HEAD").unwrap().symbolic_target().unwrap().to_string();
+    repo.reference(&branch, oid, true, message).unwrap();
+    oid
+}
+
+/// A [GitConfig] tracking branch of remote
+pub fn git_config(remote: &str, branch: &str) -> GitConfig
+{
+    GitConfig { remote: remote.to_string(), branch: branch.to_string(), ..Default::default() }
+}
+
+/// The url of a local repository, to clone it
+pub fn local_remote(path: &str) -> String
+{
+    std::fs::canonicalize(path).unwrap().to_string_lossy().to_string()
+}
+
+/// A config serving path/site, without a sitemap, from git
+pub fn site_config(path: &str, git: GitConfig) -> Config
+{
+    let mut config = Config::load_or_default("tests/config.json");
+    config.content.path = format!("{}/site", path);
+    config.content.home = format!("{}/site/index.html", path);
+    config.content.generate_sitemap = Some(false);
+    config.git = Some(git);
+    config
+}
@@ -0,0 +1,142 @@
+mod common;
+
+#[cfg(test)]
+#[cfg(unix)]
+mod build
+{
+    use std::{collections::HashMap, path::Path, time::Instant};
+
+    use busser::{config::{BuildConfig, Config, GitConfig, ReleasesConfig, REDACTED}, filesystem::file::{read_file_utf8, write_file_bytes}, integrations::git::{build::{build_and_publish, built_commit, checkout_path, needs_build, run_build}, refresh::GitRefreshTask, releases::Releases}};
+    use git2::Repository;
+
+    use crate::common::{commit, git_config, local_remote, site_config};
+
+    fn build_config(command: &str) -> BuildConfig
+    {
+        BuildConfig
+        {
+            command: command.to_string(),
+            path: None,
+            working_directory: None,
+            env: Some(HashMap::from([("GREETING".to_string(), "hello".to_string())])),
+            timeout_seconds: Some(1),
+            output: "public/".to_string()
+        }
+    }
+
+    fn build_git_config(build: BuildConfig) -> GitConfig
+    {
+        GitConfig { build: Some(build), ..git_config("", "main") }
+    }
+
+    #[test]
+    fn test_run_build()
+    {
+        let path = format!("tests/test_run_build-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(format!("{}/site", path)).unwrap();
+
+        let output = run_build(&build_config("mkdir -p public && echo $GREETING > public/index.html && echo built"), &path).unwrap();
+        assert_eq!(output, "built\n");
+        assert_eq!(read_file_utf8(&format!("{}/public/index.html", path)).unwrap(), "hello\n");
+
+        let config = BuildConfig { working_directory: Some("site".to_string()), ..build_config("pwd") };
+        assert!(run_build(&config, &path).unwrap().trim_end().ends_with("/site"));
+
+        let error = run_build(&build_config("echo one; echo two; echo oops >&2; exit 3"), &path).unwrap_err();
+        assert!(error.why.contains("failed"));
+        assert_eq!(error.tail(2), "two\noops");
+
+        let tic = Instant::now();
+        assert!(run_build(&build_config("sleep 5"), &path).unwrap_err().why.contains("timed out"));
+        assert!(tic.elapsed().as_secs() < 5);
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_build_and_publish()
+    {
+        let path = format!("tests/test_build_and_publish-{}", uuid::Uuid::new_v4());
+        let checkout = format!("{}/source", path);
+        std::fs::create_dir_all(&checkout).unwrap();
+        std::fs::create_dir_all(format!("{}/site", path)).unwrap();
+        write_file_bytes(&format!("{}/site/index.html", path), b"old");
+
+        let mut config = Config::load_or_default("tests/config.json");
+        config.content.path = format!("{}/site", path);
+        assert_eq!(checkout_path(&config), config.content.path);
+        config.git = Some(build_git_config(build_config("mkdir -p public/blog && echo new > public/blog/index.html && exit 1")));
+        assert_eq!(checkout_path(&config), "source");
+        assert!(!config.git.as_ref().unwrap().is_watchable());
+        assert_eq!(config.redacted().git.unwrap().build.unwrap().env.unwrap()["GREETING"], REDACTED);
+
+        // the previous output is served until a build succeeds
+        assert!(build_and_publish(&config, &checkout).is_err());
+        assert_eq!(read_file_utf8(&format!("{}/site/index.html", path)).unwrap(), "old");
+
+        config.git = Some(build_git_config(build_config("mkdir -p public/blog && echo new > public/blog/index.html")));
+        build_and_publish(&config, &checkout).unwrap();
+        assert_eq!(read_file_utf8(&format!("{}/site/blog/index.html", path)).unwrap(), "new\n");
+        assert!(!Path::new(&format!("{}/site/index.html", path)).exists());
+        assert!(!Path::new(&format!("{}/site.build", path)).exists());
+        assert!(!Path::new(&format!("{}/site.previous", path)).exists());
+
+        config.git = Some(build_git_config(BuildConfig { output: "missing".to_string(), ..build_config("true") }));
+        assert!(build_and_publish(&config, &checkout).unwrap_err().why.contains("does not exist"));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_built_releases()
+    {
+        let path = format!("tests/test_built_releases-{}", uuid::Uuid::new_v4());
+        let remote = format!("{}/remote", path);
+        let repo = Repository::init(&remote).unwrap();
+        commit(&repo, &[("index.md", Some("built"))], "sources");
+
+        let config = site_config(&path, GitConfig
+        {
+            releases: Some(ReleasesConfig { path: Some(format!("{}/releases", path)), keep: None, check_links: None }),
+            build: Some(build_config("mkdir -p public && cp index.md public/index.html")),
+            ..git_config(&local_remote(&remote), repo.head().unwrap().shorthand().unwrap())
+        });
+
+        let releases = Releases::new(&config).unwrap();
+        assert!(releases.deploy(&config).unwrap().is_some());
+        assert_eq!(read_file_utf8(&config.content.home).unwrap(), "built");
+        assert_eq!(releases.active(), Some(releases.list()[0].name.clone()));
+        assert!(releases.served_path(&releases.list()[0].name).ends_with("/public"));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[tokio::test]
+    async fn test_failed_builds_are_retried()
+    {
+        let path = format!("tests/test_failed_builds_are_retried-{}", uuid::Uuid::new_v4());
+        let remote = format!("{}/remote", path);
+        let repo = Repository::init(&remote).unwrap();
+        commit(&repo, &[("index.md", Some("built"))], "sources");
+
+        let mut config = Config::load_or_default("tests/config.json");
+        config.content.path = format!("{}/site", path);
+        let checkout = format!("{}/source", path);
+        let build = BuildConfig { path: Some(checkout.clone()), ..build_config("mkdir -p public && cp index.md public/index.html && exit 1") };
+        config.git = Some(GitConfig { build: Some(build.clone()), ..git_config(&local_remote(&remote), repo.head().unwrap().shorthand().unwrap()) });
+
+        GitRefreshTask::refresh(&config).await;
+        assert!(!Path::new(&config.content.path).exists());
+        assert!(built_commit(&checkout).is_none());
+        assert!(needs_build(&config, &checkout));
+
+        // the same commit is built again, without a new push
+        config.git.as_mut().unwrap().build = Some(BuildConfig { command: "mkdir -p public && cp index.md public/index.html".to_string(), ..build });
+        GitRefreshTask::refresh(&config).await;
+        assert_eq!(read_file_utf8(&format!("{}/index.html", config.content.path)).unwrap(), "built");
+        assert_eq!(built_commit(&checkout), Some(repo.head().unwrap().target().unwrap().to_string()));
+        assert!(!needs_build(&config, &checkout));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
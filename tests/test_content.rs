@@ -5,7 +5,9 @@ mod test_content
 {
     use std::{collections::HashMap, fs::remove_file, path::Path, thread::sleep, time};
 
-    use busser::{config::{read_config, Config}, content::{error_page::{ErrorPage, DEFAULT_BODY}, filter::ContentFilter, get_content, insert_tag, is_page, mime_type::MIME, Content, HasUir}, filesystem::file::{file_hash, write_file_bytes, Observed}, util::read_bytes};
+    use axum::{body::to_bytes, response::IntoResponse};
+
+    use busser::{config::{read_config, Config}, content::{cache::ContentCache, error_page::{ErrorPage, DEFAULT_BODY}, filter::ContentFilter, get_content, insert_tag, is_page, mime_type::MIME, Content, HasUir}, filesystem::file::{file_hash, write_file_bytes, Observed}, util::read_bytes};
 
     #[test]
     fn test_load_content()
@@ -259,5 +261,78 @@ mod test_content
         assert!(body.contains("404"));
     }
 
-}
+    #[test]
+    fn test_content_cache()
+    {
+        let mut cache = ContentCache::new(10);
+
+        cache.insert("a", vec![0; 4]);
+        cache.insert("b", vec![0; 4]);
+        assert_eq!(cache.resident_bytes(), 8);
+        assert!(cache.get("a").is_some());
+
+        // b is least recently used
+        cache.insert("c", vec![0; 4]);
+        assert_eq!(cache.resident(), vec!["c".to_string(), "a".to_string()]);
+        assert_eq!(cache.resident_bytes(), 8);
+
+        // larger than the budget, never resident
+        assert_eq!(cache.insert("d", vec![1; 11]).len(), 11);
+        assert!(cache.get("d").is_none());
+
+        cache.insert("a", vec![0; 2]);
+        assert_eq!(cache.resident_bytes(), 6);
+        cache.remove("a");
+        assert_eq!(cache.resident(), vec!["c".to_string()]);
+    }
+
+    #[test]
+    fn test_cached_content()
+    {
+        let cache = ContentCache::shared(16);
+
+        let mut a = Content::new("/a.html", "tests/pages/a.html", 60, 3600, false);
+        a.set_cache(Some(cache.clone()));
+        assert!(a.utf8_body().is_ok_and(|b| b == ""));
+        assert!(a.load_from_file().is_ok());
+        assert!(a.utf8_body().is_ok_and(|b| b == "this is /a"));
+        assert_eq!(cache.lock().unwrap().resident(), vec!["tests/pages/a.html".to_string()]);
+
+        let mut b = Content::new("/b.html", "tests/pages/b.html", 60, 3600, false);
+        b.set_cache(Some(cache.clone()));
+        assert!(b.load_from_file().is_ok());
+        assert_eq!(cache.lock().unwrap().resident(), vec!["tests/pages/b.html".to_string()]);
+
+        // evicted, re-read from disk
+        assert!(a.utf8_body().is_ok_and(|b| b == "this is /a"));
+        assert!(!a.is_stale());
+        assert_eq!(cache.lock().unwrap().resident(), vec!["tests/pages/a.html".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_content()
+    {
+        let path = format!("test_streamed_content-{}", uuid::Uuid::new_v4());
+        let data: Vec<u8> = (0..200000).map(|x| (x % 251) as u8).collect();
+        write_file_bytes(&path, &data);
 
+        let mut content = Content::new(&path, &path, 60, 3600, false);
+        content.set_stream_threshold(Some(1024));
+        assert!(content.load_from_file().is_ok());
+        assert!(content.is_streamed());
+        assert!(content.byte_body().is_empty());
+        assert!(!content.is_stale());
+
+        let response = content.clone().into_response();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body.to_vec(), data);
+
+        write_file_bytes(&path, &data[0..1000]);
+        assert!(content.is_stale());
+        content.refresh();
+        assert!(!content.is_streamed());
+        assert_eq!(content.byte_body(), data[0..1000].to_vec());
+
+        let _ = remove_file(path);
+    }
+}
@@ -0,0 +1,109 @@
+mod common;
+
+#[cfg(test)]
+mod divergence
+{
+    use busser::{config::{Config, DivergencePolicy, GitConfig, ReleasesConfig}, filesystem::file::read_file_utf8, integrations::git::{refresh::GitRefreshTask, releases::Releases, Divergence}};
+    use git2::{Oid, Repository};
+
+    use crate::common::{commit_onto, git_config, local_remote, site_config};
+
+    /// A remote with commits one and two, and a config tracking it
+    fn setup(path: &str, policy: Option<DivergencePolicy>) -> (Repository, Oid, Config)
+    {
+        let remote = format!("{}/remote", path);
+        let repo = Repository::init(&remote).unwrap();
+        repo.set_head("refs/heads/main").unwrap();
+        let one = commit_onto(&repo, &[("index.html", Some("one"))], "one", None);
+        commit_onto(&repo, &[("index.html", Some("two"))], "two", Some(one));
+
+        let config = site_config(path, GitConfig { on_divergence: policy, ..git_config(&local_remote(&remote), "main") });
+        (repo, one, config)
+    }
+
+    #[test]
+    fn test_divergence_policies()
+    {
+        for policy in [None, Some(DivergencePolicy::Reset), Some(DivergencePolicy::Reclone)]
+        {
+            let path = format!("tests/test_divergence-{}", uuid::Uuid::new_v4());
+            let (repo, one, config) = setup(&path, policy);
+            GitRefreshTask::pull(&config);
+            assert_eq!(read_file_utf8(&config.content.home).unwrap(), "two");
+            assert_eq!(GitRefreshTask::pull(&config), (None, None));
+
+            // force-push a rewritten history
+            let rewritten = commit_onto(&repo, &[("index.html", Some("rewritten"))], "rewritten", Some(one));
+            let (head, divergence) = GitRefreshTask::pull(&config);
+            let divergence = divergence.unwrap();
+            assert_eq!(divergence.policy, policy.unwrap_or(DivergencePolicy::Refuse));
+            assert_eq!(divergence.remote.as_ref().unwrap().hash, rewritten.to_string()[0..7]);
+            assert_ne!(divergence.local, divergence.remote);
+            assert!(divergence.message("test.domain").contains(&rewritten.to_string()[0..7]));
+
+            match policy
+            {
+                None =>
+                {
+                    assert!(!divergence.resolved());
+                    assert!(head.is_none());
+                    assert!(divergence.message("test.domain").contains("refused"));
+                    assert_eq!(read_file_utf8(&config.content.home).unwrap(), "two");
+
+                    // refused again on every pull, but only notified while new
+                    let site = config.content.path.clone();
+                    assert!(divergence.record(&site));
+                    assert!(!GitRefreshTask::pull(&config).1.unwrap().record(&site));
+                    let again = commit_onto(&repo, &[("index.html", Some("again"))], "again", Some(one));
+                    let divergence = GitRefreshTask::pull(&config).1.unwrap();
+                    assert_eq!(divergence.remote.as_ref().unwrap().hash, again.to_string()[0..7]);
+                    assert!(divergence.record(&site));
+                },
+                Some(_) =>
+                {
+                    assert!(divergence.resolved());
+                    assert_eq!(head, divergence.remote);
+                    assert_eq!(read_file_utf8(&config.content.home).unwrap(), "rewritten");
+                    assert_eq!(GitRefreshTask::pull(&config), (None, None));
+                }
+            }
+
+            let _ = std::fs::remove_dir_all(path);
+        }
+    }
+
+    #[test]
+    fn test_bare_record()
+    {
+        // as cloned to serve objects
+        let path = format!("tests/test_bare_record-{}", uuid::Uuid::new_v4());
+        Repository::init_bare(&path).unwrap();
+        let divergence = Divergence { policy: DivergencePolicy::Refuse, local: None, remote: None, head: None, error: None };
+        assert!(divergence.record(&path));
+        assert!(std::path::Path::new(&format!("{}/busser_diverged", path)).exists());
+        assert!(!divergence.record(&path));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_diverged_releases()
+    {
+        let path = format!("tests/test_diverged_releases-{}", uuid::Uuid::new_v4());
+        let (repo, one, mut config) = setup(&path, None);
+        config.git.as_mut().unwrap().releases = Some(ReleasesConfig { path: Some(format!("{}/releases", path)), keep: None, check_links: None });
+
+        let releases = Releases::new(&config).unwrap();
+        releases.deploy(&config).unwrap();
+        commit_onto(&repo, &[("index.html", Some("rewritten"))], "rewritten", Some(one));
+        assert!(releases.deploy(&config).unwrap_err().why.contains("diverged"));
+        assert_eq!(read_file_utf8(&config.content.home).unwrap(), "two");
+
+        config.git.as_mut().unwrap().on_divergence = Some(DivergencePolicy::Reset);
+        assert!(releases.deploy(&config).unwrap().is_some());
+        assert_eq!(read_file_utf8(&config.content.home).unwrap(), "rewritten");
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
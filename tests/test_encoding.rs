@@ -0,0 +1,102 @@
+mod common;
+
+#[cfg(test)]
+mod encoding
+{
+    use axum::{body::to_bytes, response::IntoResponse};
+    use busser::{config::Config, content::{encoding::{preferred_encoding, sidecar_of, Encoding}, get_content, sitemap::SiteMap, HasUir}, filesystem::file::write_file_bytes};
+
+    #[test]
+    fn test_sidecar_of()
+    {
+        assert_eq!(sidecar_of("a/app.js.br"), Some(("a/app.js".to_string(), Encoding::Brotli)));
+        assert_eq!(sidecar_of("a/app.js.zst"), Some(("a/app.js".to_string(), Encoding::Zstd)));
+        assert_eq!(sidecar_of("a/app.js.gz"), Some(("a/app.js".to_string(), Encoding::Gzip)));
+        assert_eq!(sidecar_of("a/app.js"), None);
+        assert_eq!(sidecar_of("a/.gz"), None);
+    }
+
+    #[test]
+    fn test_preferred_encoding()
+    {
+        let all = vec![Encoding::Gzip, Encoding::Brotli, Encoding::Zstd];
+        assert_eq!(preferred_encoding("gzip, deflate, br, zstd", &all), Some(Encoding::Brotli));
+        assert_eq!(preferred_encoding("gzip, deflate, br", &[Encoding::Gzip]), Some(Encoding::Gzip));
+        assert_eq!(preferred_encoding("gzip;q=1.0, br;q=0.5", &all), Some(Encoding::Gzip));
+        assert_eq!(preferred_encoding("br;q=0, *", &all), Some(Encoding::Zstd));
+        assert_eq!(preferred_encoding("identity", &all), None);
+        assert_eq!(preferred_encoding("*;q=0", &all), None);
+        assert_eq!(preferred_encoding("br", &[]), None);
+    }
+
+    #[tokio::test]
+    async fn test_sidecar_content()
+    {
+        let path = format!("tests/test_sidecar_content-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(&path).unwrap();
+        write_file_bytes(&format!("{}/app.js", path), "let a = 1;".as_bytes());
+        write_file_bytes(&format!("{}/app.js.br", path), "brotli".as_bytes());
+        write_file_bytes(&format!("{}/app.js.gz", path), "gzip".as_bytes());
+        write_file_bytes(&format!("{}/archive.tar.gz", path), "archive".as_bytes());
+        write_file_bytes(&format!("{}/index.html", path), "<p>home</p>".as_bytes());
+        write_file_bytes(&format!("{}/index.html.gz", path), "home gzip".as_bytes());
+
+        let contents = get_content(&path, &path, None, None, Some(false), None);
+        let uris: Vec<String> = contents.iter().map(|c| c.get_uri()).collect();
+        assert!(uris.contains(&"/app.js".to_string()));
+        assert!(uris.contains(&"/archive.tar.gz".to_string()));
+        assert!(!uris.contains(&"/app.js.br".to_string()));
+        assert!(!uris.contains(&"/app.js.gz".to_string()));
+
+        let mut app = contents.into_iter().find(|c| c.get_uri() == "/app.js").unwrap();
+        assert_eq!(app.sidecars(), vec![Encoding::Brotli, Encoding::Gzip]);
+        assert_eq!(app.negotiate_encoding(Some("gzip, br")), Some(Encoding::Brotli));
+        assert_eq!(app.negotiate_encoding(Some("identity")), None);
+        assert_eq!(app.negotiate_encoding(None), None);
+
+        let response = app.encoded_response(Encoding::Gzip);
+        assert_eq!(response.headers()["content-encoding"], "gzip");
+        assert_eq!(response.headers()["content-type"], "text/javascript; charset=utf-8");
+        assert_eq!(response.headers()["vary"], "accept-encoding");
+        assert_eq!(to_bytes(response.into_body(), usize::MAX).await.unwrap(), "gzip".as_bytes());
+
+        app.load_from_file().unwrap();
+        let response = app.clone().into_response();
+        assert!(response.headers().get("content-encoding").is_none());
+        assert_eq!(response.headers()["vary"], "accept-encoding");
+        assert_eq!(to_bytes(response.into_body(), usize::MAX).await.unwrap(), "let a = 1;".as_bytes());
+
+        let mut config = Config::load_or_default("tests/config.json");
+        config.content.path = path.clone();
+        config.content.home = format!("{}/index.html", path);
+
+        // html with a tag inserted is not served precompressed
+        let sitemap = SiteMap::build(&config, true, true);
+        assert_eq!(sitemap.get("/index").unwrap().lock().await.negotiate_encoding(Some("gzip")), None);
+
+        let mut sitemap = SiteMap::build(&config, false, true);
+        let uris = sitemap.collect_uris();
+        assert!(!uris.contains(&"/app.js.br".to_string()));
+        for uri in ["/", "/index", "/index.html"]
+        {
+            assert_eq!(sitemap.get(uri).unwrap().lock().await.negotiate_encoding(Some("gzip")), Some(Encoding::Gzip));
+        }
+        assert!(!String::from_utf8(sitemap.to_xml()).unwrap().contains(".gz<"));
+
+        // watched sidecars
+        write_file_bytes(&format!("{}/app.js.zst", path), "zstd".as_bytes());
+        std::fs::remove_file(format!("{}/app.js.br", path)).unwrap();
+        assert!(!sitemap.apply_changes(vec![format!("{}/app.js.zst", path), format!("{}/app.js.br", path)]).await);
+        assert_eq!(sitemap.get("/app.js").unwrap().lock().await.sidecars(), vec![Encoding::Gzip, Encoding::Zstd]);
+
+        write_file_bytes(&format!("{}/style.css.br", path), "brotli".as_bytes());
+        assert!(sitemap.apply_changes(vec![format!("{}/style.css.br", path)]).await);
+        assert!(sitemap.collect_uris().contains(&"/style.css.br".to_string()));
+        write_file_bytes(&format!("{}/style.css", path), "p {}".as_bytes());
+        assert!(sitemap.apply_changes(vec![format!("{}/style.css", path)]).await);
+        assert!(!sitemap.collect_uris().contains(&"/style.css.br".to_string()));
+        assert_eq!(sitemap.get("/style.css").unwrap().lock().await.sidecars(), vec![Encoding::Brotli]);
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
@@ -0,0 +1,120 @@
+mod common;
+
+#[cfg(test)]
+mod feed
+{
+    use busser::{config::{Config, FeedConfig, FeedFormat}, content::{feed::{feed_entry, html_meta, parse_date, to_xml, FeedEntry, PageMeta}, sitemap::{LastModified, SiteMap}}, filesystem::file::write_file_bytes};
+    use chrono::{TimeZone, Utc};
+
+    fn feed_config(format: FeedFormat) -> FeedConfig
+    {
+        FeedConfig
+        {
+            prefix: "/blog/".to_string(),
+            uri: "/blog/feed.xml".to_string(),
+            format: Some(format),
+            title: Some("Blog".to_string()),
+            description: None,
+            author: None,
+            max_entries: Some(2)
+        }
+    }
+
+    #[test]
+    fn test_html_meta()
+    {
+        let html = r#"<html><head><title> A &amp; B </title>
+            <meta name="description" content="About things">
+            <meta property='article:published_time' content='2024-03-01T10:00:00Z'>
+            </head><body></body></html>"#;
+        assert_eq!
+        (
+            html_meta(html),
+            PageMeta
+            {
+                title: Some("A & B".to_string()),
+                summary: Some("About things".to_string()),
+                date: Some("2024-03-01T10:00:00Z".to_string())
+            }
+        );
+
+        let html = r#"<meta content="OG" property="og:title"><time datetime="2023-01-02">then</time>"#;
+        let meta = html_meta(html);
+        assert_eq!(meta.title, Some("OG".to_string()));
+        assert_eq!(meta.summary, None);
+        assert_eq!(meta.date, Some("2023-01-02".to_string()));
+
+        assert_eq!(parse_date("2024-03-01"), Some(Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()));
+        assert_eq!(parse_date("2024-03-01T10:00:00+01:00"), Some(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap()));
+        assert_eq!(parse_date("Fri, 01 Mar 2024 10:00:00 +0000"), Some(Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap()));
+        assert_eq!(parse_date("yesterday"), None);
+    }
+
+    #[test]
+    fn test_to_xml()
+    {
+        let entries: Vec<FeedEntry> = (1..4).map(|day| FeedEntry
+        {
+            uri: format!("/blog/{}", day),
+            title: format!("Post <{}>", day),
+            summary: if day == 3 { Some("Latest".to_string()) } else { None },
+            date: Utc.with_ymd_and_hms(2024, 3, day, 0, 0, 0).unwrap()
+        }).collect();
+
+        let rss = String::from_utf8(to_xml(&feed_config(FeedFormat::Rss), "https://example.com", &entries)).unwrap();
+        assert!(rss.contains("<rss version=\"2.0\""));
+        assert!(rss.contains("<title>Post &lt;3&gt;</title>"));
+        assert!(rss.contains("<link>https://example.com/blog/3</link>"));
+        assert!(rss.contains("<description>Latest</description>"));
+        assert!(rss.contains("<lastBuildDate>Sun, 3 Mar 2024 00:00:00 +0000</lastBuildDate>"));
+        assert!(rss.find("/blog/3").unwrap() < rss.find("/blog/2</link>").unwrap());
+        // max_entries keeps the newest
+        assert!(!rss.contains("/blog/1"));
+
+        let atom = String::from_utf8(to_xml(&feed_config(FeedFormat::Atom), "https://example.com", &entries)).unwrap();
+        assert!(atom.contains("<feed xmlns=\"http://www.w3.org/2005/Atom\">"));
+        assert!(atom.contains("<link href=\"https://example.com/blog/feed.xml\" rel=\"self\"/>"));
+        assert!(atom.contains("<updated>2024-03-03T00:00:00+00:00</updated>"));
+        assert!(atom.contains("<summary>Latest</summary>"));
+        assert!(!atom.contains("/blog/1"));
+    }
+
+    #[tokio::test]
+    async fn test_sitemap_feeds()
+    {
+        let path = format!("tests/test_sitemap_feeds-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(format!("{}/blog", path)).unwrap();
+        write_file_bytes(&format!("{}/index.html", path), "<title>Home</title>".as_bytes());
+        write_file_bytes(&format!("{}/blog/a.html", path), "<title>A</title><meta name=\"date\" content=\"2024-01-01\">".as_bytes());
+        write_file_bytes(&format!("{}/blog/b.md", path), "---\ntitle: B\nsummary: Bee\ndate: 2024-02-01\n---\nb".as_bytes());
+        write_file_bytes(&format!("{}/blog/c.md", path), "---\ntitle: C\ndraft: true\n---\nc".as_bytes());
+
+        let mut config = Config::load_or_default("tests/config.json");
+        config.domain = "https://example.com".to_string();
+        config.content.path = path.clone();
+        config.content.home = format!("{}/index.html", path);
+        config.content.generate_sitemap = Some(false);
+        config.content.render_markdown = Some(true);
+        config.content.feeds = Some(vec![FeedConfig { max_entries: None, ..feed_config(FeedFormat::Rss) }]);
+
+        let mut sitemap = SiteMap::build(&config, false, true);
+
+        let entries = sitemap.feed_entries(config.content.feeds.as_ref().unwrap().first().unwrap());
+        assert_eq!(entries.iter().map(|e| e.uri.as_str()).collect::<Vec<&str>>(), vec!["/blog/a", "/blog/b"]);
+        assert_eq!(entries[1].summary, Some("Bee".to_string()));
+
+        let feed = String::from_utf8(sitemap.generated().get("/blog/feed.xml").unwrap()).unwrap();
+        assert!(feed.contains("<title>B</title>"));
+        assert!(!feed.contains("<title>C</title>"));
+        assert!(!feed.contains("Home"));
+        assert!(!std::path::Path::new(&format!("{}/blog/feed.xml", path)).exists());
+
+        write_file_bytes(&format!("{}/blog/d.html", path), "<title>D</title>".as_bytes());
+        sitemap.apply_changes(vec![format!("{}/blog/d.html", path)]).await;
+        let feed = String::from_utf8(sitemap.generated().get("/blog/feed.xml").unwrap()).unwrap();
+        assert!(feed.contains("<title>D</title>"));
+        assert_eq!(feed_entry("/blog/d", &format!("{}/blog/d.html", path), "<title>D</title>", &LastModified::new(&path)).unwrap().title, "D");
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -0,0 +1,94 @@
+mod common;
+
+#[cfg(test)]
+mod forge
+{
+    use std::{sync::Arc, time::SystemTime};
+
+    use axum::{body::{Body, Bytes}, http::{HeaderMap, HeaderName, HeaderValue, Request}};
+    use busser::{integrations::{bitbucket::Bitbucket, forge::{handle_push, ForgeWebhook}, gitea::Gitea}, util::dump_bytes};
+    use openssl::{hash::MessageDigest, pkey::PKey, sign::Signer};
+    use reqwest::StatusCode;
+    use tokio::sync::Mutex;
+
+    const GITEA_PUSH: &str = r#"{"ref": "refs/heads/main", "repository": {"html_url": "https://forge.example/jerboa/site", "clone_url": "https://forge.example/jerboa/site.git", "ssh_url": "git@forge.example:jerboa/site.git"}}"#;
+    const CLOUD_PUSH: &str = r#"{"push": {"changes": [{"new": {"type": "branch", "name": "main"}}]}, "repository": {"links": {"html": {"href": "https://bitbucket.org/jerboa/site"}}}}"#;
+    const SERVER_PUSH: &str = r#"{"changes": [{"ref": {"id": "refs/heads/main", "type": "BRANCH"}}], "repository": {"links": {"clone": [{"href": "ssh://git@bitbucket.example:7999/jer/site.git", "name": "ssh"}], "self": [{"href": "https://bitbucket.example/projects/JER/repos/site/browse"}]}}}"#;
+
+    fn header(key: &'static str, value: &str) -> HeaderMap
+    {
+        let mut headers = HeaderMap::new();
+        headers.insert(HeaderName::from_static(key), HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn sign(token: &str, body: &str) -> String
+    {
+        let key = PKey::hmac(token.as_bytes()).unwrap();
+        let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+        signer.update(body.as_bytes()).unwrap();
+        dump_bytes(&signer.sign_to_vec().unwrap()).to_lowercase()
+    }
+
+    #[tokio::test]
+    async fn test_gitea()
+    {
+        assert_eq!(Gitea::is_push(&HeaderMap::new()).await, StatusCode::CONTINUE);
+        assert_eq!(Gitea::is_push(&header("x-gitea-event", "push")).await, StatusCode::OK);
+        assert_eq!(Gitea::is_push(&header("x-forgejo-event", "push")).await, StatusCode::OK);
+        assert_eq!(Gitea::is_push(&header("x-forgejo-event", "issues")).await, StatusCode::CONTINUE);
+
+        let body = Bytes::from(GITEA_PUSH);
+        assert!(Gitea::is_watched_repo(&body, "https://forge.example/jerboa/site.git"));
+        assert!(Gitea::is_watched_repo(&body, "git@forge.example:jerboa/site.git"));
+        assert!(!Gitea::is_watched_repo(&body, "https://forge.example/jerboa/other.git"));
+        assert!(Gitea::is_tracked_branch(&body, "main"));
+        assert!(!Gitea::is_tracked_branch(&body, "dev"));
+
+        let signature = sign("secret", GITEA_PUSH);
+        assert_eq!(Gitea::is_authentic(&header("x-forgejo-signature", &signature), "secret".to_string(), &body), StatusCode::ACCEPTED);
+        assert_eq!(Gitea::is_authentic(&header("x-gitea-signature", &signature), "secret".to_string(), &body), StatusCode::ACCEPTED);
+        assert_eq!(Gitea::is_authentic(&header("x-gitea-signature", &signature), "wrong".to_string(), &body), StatusCode::UNAUTHORIZED);
+        assert_eq!(Gitea::is_authentic(&HeaderMap::new(), "secret".to_string(), &body), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_bitbucket()
+    {
+        assert_eq!(Bitbucket::is_push(&HeaderMap::new()).await, StatusCode::CONTINUE);
+        assert_eq!(Bitbucket::is_push(&header("x-event-key", "repo:push")).await, StatusCode::OK);
+        assert_eq!(Bitbucket::is_push(&header("x-event-key", "repo:refs_changed")).await, StatusCode::OK);
+        assert_eq!(Bitbucket::is_push(&header("x-event-key", "pullrequest:created")).await, StatusCode::CONTINUE);
+
+        let cloud = Bytes::from(CLOUD_PUSH);
+        assert!(Bitbucket::is_watched_repo(&cloud, "https://bitbucket.org/jerboa/site.git"));
+        assert!(Bitbucket::is_watched_repo(&cloud, "https://bitbucket.org/jerboa/site"));
+        assert!(!Bitbucket::is_watched_repo(&cloud, "https://bitbucket.org/jerboa/other.git"));
+        assert!(Bitbucket::is_tracked_branch(&cloud, "main"));
+        assert!(!Bitbucket::is_tracked_branch(&cloud, "dev"));
+
+        let server = Bytes::from(SERVER_PUSH);
+        assert!(Bitbucket::is_watched_repo(&server, "ssh://git@bitbucket.example:7999/jer/site.git"));
+        assert!(!Bitbucket::is_watched_repo(&server, "https://bitbucket.org/jerboa/site"));
+        assert!(Bitbucket::is_tracked_branch(&server, "main"));
+        assert!(!Bitbucket::is_tracked_branch(&server, "mai"));
+
+        let signature = format!("sha256={}", sign("secret", CLOUD_PUSH));
+        assert_eq!(Bitbucket::is_authentic(&header("x-hub-signature", &signature), "secret".to_string(), &cloud), StatusCode::ACCEPTED);
+        assert_eq!(Bitbucket::is_authentic(&header("x-hub-signature", &signature), "secret".to_string(), &server), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_handle_push()
+    {
+        let push = |body: &str| Request::builder().method("POST").uri("/").body(Body::from(body.to_string())).unwrap();
+        let lock = Arc::new(Mutex::new(SystemTime::now()));
+        let remote = "https://forge.example/jerboa/site.git";
+
+        assert_eq!(handle_push::<Gitea>(lock.clone(), HeaderMap::new(), push(GITEA_PUSH), "not_a_repo", "main", "secret".to_string()).await, StatusCode::OK);
+        assert_eq!(handle_push::<Gitea>(lock.clone(), HeaderMap::new(), push(GITEA_PUSH), remote, "dev", "secret".to_string()).await, StatusCode::OK);
+        assert_eq!(handle_push::<Gitea>(lock.clone(), HeaderMap::new(), push(GITEA_PUSH), remote, "main", "secret".to_string()).await, StatusCode::UNAUTHORIZED);
+        let headers = header("x-gitea-signature", &sign("wrong", GITEA_PUSH));
+        assert_eq!(handle_push::<Gitea>(lock, headers, push(GITEA_PUSH), remote, "main", "secret".to_string()).await, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -15,9 +15,7 @@ mod git
         {
             remote: "https://github.com/JerboaBurrow/Busser".into(),
             branch: "main".into(),
-            checkout_schedule: None,
-            auth: None,
-            remote_webhook_token: None
+            ..Default::default()
         };
 
         let path = "tests/test_clone";
@@ -40,9 +38,7 @@ mod git
         {
             remote: "https://github.com/JerboaBurrow/Busser".into(),
             branch: "main".into(),
-            checkout_schedule: None,
-            auth: None,
-            remote_webhook_token: None
+            ..Default::default()
         };
 
         let path = "tests/test_clean_and_clone";
@@ -77,14 +73,13 @@ mod git
         {
             remote: "git@github.com:JerboaBurrow/test.git".into(),
             branch: "main".into(),
-            checkout_schedule: None,
-            remote_webhook_token: None,
             auth: Some(GitAuthConfig
             {
                 key_path: Some("not_a_key".into()),
                 user: "not_a_user".into(),
                 passphrase: "not_a_passphrase".into(),
             }),
+            ..Default::default()
         };
 
         let path = "tests/test_key_authed_clone";
@@ -109,14 +104,13 @@ mod git
         {
             remote: "https://github.com/JerboaBurrow/test".into(),
             branch: "main".into(),
-            checkout_schedule: None,
-            remote_webhook_token: None,
             auth: Some(GitAuthConfig
             {
                 key_path: None,
                 user: "not_a_user".into(),
                 passphrase: "not_a_passphrase".into(),
             }),
+            ..Default::default()
         };
 
         let path = "tests/test_pass_authed_clone";
@@ -137,9 +131,7 @@ mod git
         {
             remote: "https://github.com/JerboaBurrow/Busser".into(),
             branch: "main".into(),
-            checkout_schedule: None,
-            remote_webhook_token: None,
-            auth: None,
+            ..Default::default()
         };
 
         let path = "tests/test_fast_forward_pull";
@@ -164,9 +156,7 @@ mod git
         {
             remote: "https://github.com/JerboaBurrow/Busser".into(),
             branch: "main".into(),
-            checkout_schedule: None,
-            remote_webhook_token: None,
-            auth: None,
+            ..Default::default()
         };
 
         let mut config = Config::default();
@@ -197,6 +187,7 @@ mod git
             author_name: "name".to_owned(),
             author_email: "name@domain.com".to_owned(),
             datetime: "yesterday".to_string(),
+            ..Default::default()
         };
         let config = Config::default();
 
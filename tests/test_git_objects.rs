@@ -0,0 +1,150 @@
+mod common;
+
+#[cfg(test)]
+mod git_objects
+{
+    use axum::{body::Bytes, http::HeaderMap, Router};
+    use busser::{config::{Config, FeedConfig, GitConfig, SearchConfig}, content::sitemap::{LastModified, SiteMap}, filesystem::file::write_file_bytes, integrations::git::{last_commit_times, objects::{GitSource, PIN_REF}, short_oid}, server::api::{pin::{PinCommit, PinStatus}, ApiRequest}};
+    use git2::Repository;
+    use reqwest::StatusCode;
+
+    use crate::common::{commit, git_config};
+
+    fn objects_config(path: &str, branch: &str) -> Config
+    {
+        let mut config = Config::load_or_default("tests/config.json");
+        config.content.path = path.to_string();
+        config.content.home = format!("{}/index.html", path);
+        config.content.generate_sitemap = Some(false);
+        config.git = Some(GitConfig { serve_objects: Some(true), ..git_config("", branch) });
+        config
+    }
+
+    #[test]
+    fn test_git_source()
+    {
+        let path = format!("tests/test_git_source-{}", uuid::Uuid::new_v4());
+        let repo = Repository::init(&path).unwrap();
+        let first = commit(&repo, &[("index.html", Some("first")), ("a/b.html", Some("b"))], "first");
+        let second = commit(&repo, &[("index.html", Some("second"))], "second");
+        let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+
+        let source = GitSource::open(&path, &branch).unwrap();
+        assert_eq!(source.commit(), second);
+        assert_eq!(source.pinned(), None);
+        let mut files = source.files().unwrap();
+        files.sort();
+        assert_eq!(files, vec!["a/b.html", "index.html"]);
+        assert_eq!(source.tree_path(&format!("{}/a/b.html", path)), "a/b.html");
+        assert_eq!(source.read("index.html"), Some(b"second".to_vec()));
+        assert_eq!(source.read("missing.html"), None);
+
+        // clones share the served commit, the pin survives reopening
+        let clone = source.clone();
+        assert_eq!(source.pin(&short_oid(first)).unwrap(), first);
+        assert_eq!(clone.read("index.html"), Some(b"first".to_vec()));
+        assert_eq!(clone.pinned(), Some(short_oid(first)));
+        assert_eq!(GitSource::open(&path, &branch).unwrap().commit(), first);
+        assert!(repo.find_reference(PIN_REF).is_ok());
+        assert!(source.pin("not-a-commit").is_err());
+
+        assert_eq!(clone.unpin().unwrap(), second);
+        assert_eq!(source.read("index.html"), Some(b"second".to_vec()));
+        assert_eq!(source.pinned(), None);
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[tokio::test]
+    async fn test_pin_served_commit()
+    {
+        let path = format!("tests/test_pin_served_commit-{}", uuid::Uuid::new_v4());
+        let repo = Repository::init(&path).unwrap();
+        let first = commit(&repo, &[("index.html", Some("first")), ("page.html", Some("page one"))], "first");
+        commit(&repo, &[("page.html", Some("page two"))], "second");
+        let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+
+        // the working copy is not served
+        write_file_bytes(&format!("{}/page.html", path), b"uncommitted");
+        write_file_bytes(&format!("{}/untracked.html", path), b"untracked");
+
+        let sitemap = SiteMap::build(&objects_config(&path, &branch), false, true);
+        let git = sitemap.git_source().cloned().unwrap();
+        let router: Router = sitemap.into();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap(); });
+
+        let get = |uri: &str| reqwest::get(format!("http://{}{}", address, uri));
+        assert_eq!(get("/page.html").await.unwrap().text().await.unwrap(), "page two");
+        assert_eq!(get("/untracked.html").await.unwrap().status(), StatusCode::NOT_FOUND);
+
+        let mut request = PinCommit::new();
+        assert_eq!(request.deserialise_payload(HeaderMap::new(), Bytes::from(r#"{"commit": 1}"#)), StatusCode::BAD_REQUEST);
+        assert_eq!(request.deserialise_payload(HeaderMap::new(), Bytes::from(r#"{"commit": "not-a-commit"}"#)), StatusCode::OK);
+        assert_eq!(request.respond(git.clone()).await.1, StatusCode::BAD_REQUEST);
+
+        assert_eq!(request.deserialise_payload(HeaderMap::new(), Bytes::from(format!(r#"{{"commit": "{}"}}"#, short_oid(first)))), StatusCode::OK);
+        let (body, status) = request.respond(git.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        let pinned: PinStatus = serde_json::from_str(&body.unwrap()).unwrap();
+        assert_eq!(pinned.commit, short_oid(first));
+        assert_eq!(pinned.pinned, Some(short_oid(first)));
+        assert_eq!(get("/page.html").await.unwrap().text().await.unwrap(), "page one");
+
+        assert_eq!(request.deserialise_payload(HeaderMap::new(), Bytes::from("{}")), StatusCode::OK);
+        let (body, _) = request.respond(git).await;
+        let released: PinStatus = serde_json::from_str(&body.unwrap()).unwrap();
+        assert_eq!(released.pinned, None);
+        assert_eq!(get("/page.html").await.unwrap().text().await.unwrap(), "page two");
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_generated_from_objects()
+    {
+        let path = format!("tests/test_generated_from_objects-{}", uuid::Uuid::new_v4());
+        let repo = Repository::init(&path).unwrap();
+        commit(&repo, &[("index.html", Some("<title>Home</title>Jerboas <a href=\"/gone.html\">gone</a>")), ("blog/a.html", Some("<title>A</title>"))], "first");
+        let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+
+        // pages are read from the commit, not the working copy
+        std::fs::remove_file(format!("{}/index.html", path)).unwrap();
+        std::fs::remove_file(format!("{}/blog/a.html", path)).unwrap();
+
+        let mut config = objects_config(&path, &branch);
+        config.content.search = Some(SearchConfig::default());
+        config.content.check_links = Some(true);
+        config.content.feeds = Some(vec![FeedConfig { prefix: "/blog".to_string(), uri: "/blog/feed.xml".to_string(), format: None, title: None, description: None, author: None, max_entries: None }]);
+        let sitemap = SiteMap::build(&config, false, true);
+
+        assert_eq!(sitemap.search_index().read().unwrap().search("jerboas", false, 10)[0].title, "Home");
+        assert_eq!(sitemap.link_report().read().unwrap().broken.len(), 1);
+        let feed = String::from_utf8(sitemap.generated().get("/blog/feed.xml").unwrap()).unwrap();
+        assert!(feed.contains("<title>A</title>"));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_last_commit_times()
+    {
+        let path = format!("tests/test_last_commit_times-{}", uuid::Uuid::new_v4());
+        let repo = Repository::init(&path).unwrap();
+        commit(&repo, &[("index.html", Some("first")), ("old.html", Some("old"))], "first");
+        let second = commit(&repo, &[("a/b.html", Some("b")), ("old.html", None)], "second");
+
+        // only files in HEAD have times
+        let times = last_commit_times(&repo);
+        let mut files: Vec<&str> = times.keys().map(|p| p.to_str().unwrap()).collect();
+        files.sort();
+        assert_eq!(files, vec!["a/b.html", "index.html"]);
+
+        let last_modified = LastModified::new(&path);
+        assert_eq!(last_modified.head, Some(second));
+        assert!(last_modified.get(&format!("{}/a/b.html", path)).is_some());
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
@@ -0,0 +1,79 @@
+mod common;
+
+#[cfg(test)]
+mod gitlab
+{
+    use std::{sync::Arc, time::SystemTime};
+
+    use axum::{body::{Body, Bytes}, http::{HeaderMap, HeaderValue, Request}};
+    use busser::integrations::gitlab::{handle_push, is_authentic, is_push, is_tracked_branch, is_watched_repo};
+    use reqwest::StatusCode;
+    use tokio::sync::Mutex;
+
+    use crate::common::git_config;
+
+    const PUSH: &str = r#"{"object_kind": "push", "ref": "refs/heads/main", "project": {"git_http_url": "https://gitlab.com/jerboa/site.git", "git_ssh_url": "git@gitlab.com:jerboa/site.git"}}"#;
+
+    fn token(token: &str) -> HeaderMap
+    {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-gitlab-token", HeaderValue::from_str(token).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_is_push()
+    {
+        assert_eq!(is_push(&HeaderMap::new()), StatusCode::CONTINUE);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-gitlab-event", HeaderValue::from_static("Tag Push Hook"));
+        assert_eq!(is_push(&headers), StatusCode::CONTINUE);
+
+        headers.insert("x-gitlab-event", HeaderValue::from_static("Push Hook"));
+        assert_eq!(is_push(&headers), StatusCode::OK);
+
+        headers.insert("x-gitlab-event", HeaderValue::from_bytes(&[0xff]).unwrap());
+        assert_eq!(is_push(&headers), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_is_watched_repo_and_branch()
+    {
+        let body = Bytes::from(PUSH);
+        assert!(is_watched_repo(&body, "https://gitlab.com/jerboa/site.git"));
+        assert!(is_watched_repo(&body, "git@gitlab.com:jerboa/site.git"));
+        assert!(!is_watched_repo(&body, "https://gitlab.com/jerboa/other.git"));
+        assert!(!is_watched_repo(&Bytes::from(r#"{"repository": {"html_url": "something"}}"#), "something"));
+        assert!(!is_watched_repo(&Bytes::from(""), ""));
+
+        assert!(is_tracked_branch(&body, "main"));
+        assert!(!is_tracked_branch(&body, "mai"));
+        assert!(!is_tracked_branch(&Bytes::from(r#"{"ref": "refs/tags/main"}"#), "main"));
+    }
+
+    #[test]
+    fn test_is_authentic()
+    {
+        assert_eq!(is_authentic(&token("secret"), "secret"), StatusCode::ACCEPTED);
+        assert_eq!(is_authentic(&token("secret!"), "secret"), StatusCode::UNAUTHORIZED);
+        assert_eq!(is_authentic(&token("terces"), "secret"), StatusCode::UNAUTHORIZED);
+        assert_eq!(is_authentic(&token(""), ""), StatusCode::UNAUTHORIZED);
+        assert_eq!(is_authentic(&HeaderMap::new(), "secret"), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_handle_push()
+    {
+        let push = |body: &str| Request::builder().method("POST").uri("/").body(Body::from(body.to_string())).unwrap();
+        let lock = Arc::new(Mutex::new(SystemTime::now()));
+        let git = git_config("https://gitlab.com/jerboa/site.git", "main");
+
+        // other projects and branches are ignored without authenticating
+        assert_eq!(handle_push(lock.clone(), HeaderMap::new(), push(PUSH), git_config("not_a_repo", "main"), "secret".to_string()).await, StatusCode::OK);
+        assert_eq!(handle_push(lock.clone(), HeaderMap::new(), push(&PUSH.replace("refs/heads/main", "refs/heads/dev")), git.clone(), "secret".to_string()).await, StatusCode::OK);
+
+        assert_eq!(handle_push(lock.clone(), HeaderMap::new(), push(PUSH), git.clone(), "secret".to_string()).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(handle_push(lock, token("wrong!"), push(PUSH), git, "secret".to_string()).await, StatusCode::UNAUTHORIZED);
+    }
+}
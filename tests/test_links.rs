@@ -0,0 +1,152 @@
+mod common;
+
+#[cfg(test)]
+mod links
+{
+    use busser::{config::Config, content::{links::{link_references, percent_decode, BrokenLink, LinkReport}, sitemap::SiteMap}, filesystem::file::write_file_bytes, integrations::git::HeadInfo};
+
+    #[test]
+    fn test_link_references()
+    {
+        let html = r#"<a href="/a.html">a</a><!-- <a href="/commented.html"> --><img class="x" src='img/b.png'><link rel="stylesheet" HREF = "/c.css"><a data-href="/not-a-link">"#;
+        assert_eq!(link_references(html), vec!["/a.html", "img/b.png", "/c.css"]);
+        assert_eq!(percent_decode("/my%20page.html"), "/my page.html");
+        assert_eq!(percent_decode("/100%"), "/100%");
+        assert_eq!(percent_decode("/%zz"), "/%zz");
+    }
+
+    #[test]
+    fn test_message()
+    {
+        let mut report = LinkReport::default();
+        assert!(report.message("test.domain", 1).is_none());
+
+        report.commit = Some(HeadInfo { hash: "abc123".to_string(), author_name: "jerboa".to_string(), author_email: "j@b".to_string(), datetime: "today".to_string(), ..Default::default() });
+        report.broken = vec!
+        [
+            BrokenLink { page: "/".to_string(), reference: "gone.html".to_string(), target: "/gone.html".to_string() },
+            BrokenLink { page: "/b".to_string(), reference: "/x.png".to_string(), target: "/x.png".to_string() }
+        ];
+        assert_eq!
+        (
+            report.message("test.domain", 1).unwrap(),
+            "Found 2 broken links on test.domain at commit abc123 by jerboa at today:\n```\n / -> gone.html\n and 1 more\n```"
+        );
+    }
+
+    #[test]
+    fn test_check_links()
+    {
+        let path = format!("tests/test_check_links-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(format!("{}/posts", path)).unwrap();
+        write_file_bytes(&format!("{}/index.html", path), r#"<a href="posts/first">first</a><a href="/posts/missing.html#top">missing</a><a href="https://test.domain/my%20page.html">space</a>"#.as_bytes());
+        write_file_bytes(&format!("{}/my page.html", path), r##"<a href="mailto:a@b.c">mail</a><a href="https://elsewhere.com/gone">external</a><a href="#top">top</a><a href="/sitemap.xml">sitemap</a>"##.as_bytes());
+        write_file_bytes(&format!("{}/posts/first.md", path), "# First\n\n![missing](../img/missing.png) [home](/)".as_bytes());
+
+        let mut config = Config::load_or_default("tests/config.json");
+        config.domain = "https://test.domain".to_string();
+        config.content.path = path.clone();
+        config.content.home = format!("{}/index.html", path);
+        config.content.allow_without_extension = true;
+        config.content.render_markdown = Some(true);
+        config.content.generate_sitemap = Some(true);
+
+        // not checked unless configured
+        assert_eq!(*SiteMap::build(&config, false, true).link_report().read().unwrap(), LinkReport::default());
+
+        config.content.check_links = Some(true);
+        let sitemap = SiteMap::build(&config, false, true);
+        let report = sitemap.link_report().read().unwrap().clone();
+
+        assert_eq!(report.pages, 3);
+        assert_eq!(report.links, 6);
+        assert!(!report.checked_utc.is_empty());
+        assert_eq!
+        (
+            report.broken,
+            vec!
+            [
+                BrokenLink { page: "/".to_string(), reference: "/posts/missing.html#top".to_string(), target: "/posts/missing.html".to_string() },
+                BrokenLink { page: "/posts/first".to_string(), reference: "../img/missing.png".to_string(), target: "/img/missing.png".to_string() }
+            ]
+        );
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_check_included_links()
+    {
+        let path = format!("tests/test_check_included_links-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(format!("{}/partials", path)).unwrap();
+        write_file_bytes(&format!("{}/partials/nav.html", path), r#"<a href="/gone.html">gone</a>"#.as_bytes());
+        write_file_bytes(&format!("{}/index.html", path), r#"<!--#include "partials/nav.html"--><p>home</p>"#.as_bytes());
+
+        let mut config = Config::load_or_default("tests/config.json");
+        config.domain = "https://test.domain".to_string();
+        config.content.path = path.clone();
+        config.content.home = format!("{}/index.html", path);
+        config.content.server_side_includes = Some(true);
+        config.content.check_links = Some(true);
+
+        // links are checked in the page as served
+        let report = SiteMap::build(&config, false, true).link_report().read().unwrap().clone();
+        assert!(report.broken.contains(&BrokenLink { page: "/".to_string(), reference: "/gone.html".to_string(), target: "/gone.html".to_string() }));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[tokio::test]
+    async fn test_incremental_links()
+    {
+        let path = format!("tests/test_incremental_links-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(format!("{}/partials", path)).unwrap();
+        write_file_bytes(&format!("{}/partials/nav.html", path), r#"<a href="/">home</a>"#.as_bytes());
+        write_file_bytes(&format!("{}/index.html", path), r#"<!--#include "partials/nav.html"--><a href="/b.html">b</a>"#.as_bytes());
+        write_file_bytes(&format!("{}/b.html", path), r#"<a href="/gone.html">gone</a>"#.as_bytes());
+
+        let mut config = Config::load_or_default("tests/config.json");
+        config.domain = "https://test.domain".to_string();
+        config.content.path = path.clone();
+        config.content.home = format!("{}/index.html", path);
+        config.content.server_side_includes = Some(true);
+        config.content.check_links = Some(true);
+
+        let mut sitemap = SiteMap::build(&config, false, true);
+        let broken = |sitemap: &SiteMap| sitemap.link_report().read().unwrap().broken.iter().map(|b| (b.page.clone(), b.target.clone())).collect::<Vec<_>>();
+        assert_eq!(broken(&sitemap), vec![("/b".to_string(), "/gone.html".to_string())]);
+
+        // only changed pages are rendered again
+        write_file_bytes(&format!("{}/b.html", path), r#"<a href="/">home</a>"#.as_bytes());
+        write_file_bytes(&format!("{}/index.html", path), r#"<!--#include "partials/nav.html"--><a href="/c.html">c</a>"#.as_bytes());
+        sitemap.apply_changes(vec![format!("{}/index.html", path)]).await;
+        assert_eq!
+        (
+            broken(&sitemap),
+            vec![("/".to_string(), "/c.html".to_string()), ("/b".to_string(), "/gone.html".to_string())]
+        );
+        sitemap.apply_changes(vec![format!("{}/b.html", path)]).await;
+        assert_eq!(broken(&sitemap), vec![("/".to_string(), "/c.html".to_string())]);
+
+        // as are pages including changed files
+        write_file_bytes(&format!("{}/partials/nav.html", path), r#"<a href="/nav.html">nav</a>"#.as_bytes());
+        sitemap.apply_changes(vec![format!("{}/partials/nav.html", path)]).await;
+        assert_eq!
+        (
+            broken(&sitemap),
+            vec!
+            [
+                ("/".to_string(), "/c.html".to_string()),
+                ("/".to_string(), "/nav.html".to_string()),
+                ("/partials/nav".to_string(), "/nav.html".to_string())
+            ]
+        );
+
+        // new content is linked to without rendering pages again
+        write_file_bytes(&format!("{}/c.html", path), "c".as_bytes());
+        sitemap.apply_changes(vec![format!("{}/c.html", path)]).await;
+        assert_eq!(broken(&sitemap), vec![("/".to_string(), "/nav.html".to_string()), ("/partials/nav".to_string(), "/nav.html".to_string())]);
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
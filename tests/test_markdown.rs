@@ -0,0 +1,148 @@
+mod common;
+
+#[cfg(test)]
+mod markdown
+{
+    use busser::{config::Config, content::{is_page, markdown::{apply_layout, is_markdown, render_markdown, render_page, split_front_matter, FrontMatter, MarkdownOptions}, mime_type::MIME, sitemap::SiteMap, Content}, filesystem::file::write_file_bytes};
+
+    #[test]
+    fn test_front_matter()
+    {
+        let (front_matter, body) = split_front_matter("---\ntitle: A post\ndate: 2024-03-01\ndraft: true\ntemplate: layouts/post.html\n---\n# Heading\n");
+        assert_eq!
+        (
+            front_matter,
+            FrontMatter
+            {
+                title: Some("A post".to_string()),
+                date: Some("2024-03-01".to_string()),
+                summary: None,
+                draft: Some(true),
+                template: Some("layouts/post.html".to_string())
+            }
+        );
+        assert!(front_matter.is_draft());
+        assert_eq!(body, "# Heading\n");
+
+        let (front_matter, body) = split_front_matter("# No front-matter\n---\n");
+        assert_eq!(front_matter, FrontMatter::default());
+        assert_eq!(body, "# No front-matter\n---\n");
+
+        assert!(is_markdown("a/b.md"));
+        assert!(is_markdown("a/b.MARKDOWN"));
+        assert!(!is_markdown("a/b.html"));
+    }
+
+    #[test]
+    fn test_render()
+    {
+        assert_eq!(render_markdown("# Title\n\n~~old~~ *new*"), "<h1>Title</h1>\n<p><del>old</del> <em>new</em></p>\n");
+        assert!(render_markdown("|a|b|\n|-|-|\n|1|2|").contains("<table>"));
+
+        assert_eq!
+        (
+            apply_layout("<title>{{title}}</title><time>{{ date }}</time>{{ content }}{{ other }}", "<p>x</p>", "<A & B>", "2024"),
+            "<title>&lt;A &amp; B&gt;</title><time>2024</time><p>x</p>{{ other }}"
+        );
+    }
+
+    #[test]
+    fn test_render_page()
+    {
+        let path = format!("tests/test_render_page-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(format!("{}/layouts", path)).unwrap();
+        write_file_bytes(&format!("{}/layouts/default.html", path), "<main>{{ content }}</main>".as_bytes());
+        write_file_bytes(&format!("{}/layouts/post.html", path), "<h1>{{ title }}</h1><article>{{ content }}</article>".as_bytes());
+        write_file_bytes(&format!("{}/a.md", path), "hello".as_bytes());
+        write_file_bytes(&format!("{}/b.md", path), "---\ntitle: B\ntemplate: layouts/post.html\n---\nworld".as_bytes());
+
+        let options = MarkdownOptions { content_path: path.clone(), layout: Some(format!("{}/layouts/default.html", path)) };
+
+        let page = render_page(&format!("{}/a.md", path), &options).unwrap();
+        assert_eq!(page.html, "<main><p>hello</p>\n</main>");
+        assert_eq!(page.dependencies, vec![format!("{}/layouts/default.html", path)]);
+
+        let page = render_page(&format!("{}/b.md", path), &options).unwrap();
+        assert_eq!(page.html, "<h1>B</h1><article><p>world</p>\n</article>");
+        assert_eq!(page.front_matter.title, Some("B".to_string()));
+        assert_eq!(page.dependencies, vec![format!("{}/layouts/post.html", path)]);
+
+        let page = render_page(&format!("{}/a.md", path), &MarkdownOptions { content_path: path.clone(), layout: None }).unwrap();
+        assert!(page.html.contains("<title>a</title>"));
+        assert!(page.html.contains("<p>hello</p>"));
+        assert!(page.dependencies.is_empty());
+
+        assert!(render_page(&format!("{}/missing.md", path), &options).is_err());
+
+        // layouts cannot be read from outside the content path
+        write_file_bytes(&format!("{}/secret.html", path), "<secret>{{ content }}</secret>".as_bytes());
+        write_file_bytes(&format!("{}/c.md", path), "---\ntemplate: ../secret.html\n---\nhidden".as_bytes());
+        write_file_bytes(&format!("{}/d.md", path), "---\ntemplate: /../layouts/../secret.html\n---\nhidden".as_bytes());
+        let layouts = MarkdownOptions { content_path: format!("{}/layouts", path), layout: None };
+        for page in ["c.md", "d.md"]
+        {
+            let page = render_page(&format!("{}/{}", path, page), &layouts).unwrap();
+            assert!(!page.html.contains("<secret>"));
+            assert!(page.html.contains("<p>hidden</p>"));
+            assert!(page.dependencies.is_empty());
+        }
+
+        let mut content = Content::new("/a", &format!("{}/a.md", path), 3600, 3600, false);
+        content.set_markdown(Some(options));
+        assert_eq!(content.get_content_type(), MIME::TextHtml);
+        content.load_from_file().unwrap();
+        assert_eq!(content.utf8_body().unwrap(), "<main><p>hello</p>\n</main>");
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[tokio::test]
+    async fn test_markdown_sitemap()
+    {
+        let path = format!("tests/test_markdown_sitemap-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(format!("{}/posts", path)).unwrap();
+        write_file_bytes(&format!("{}/index.html", path), "home".as_bytes());
+        write_file_bytes(&format!("{}/posts/first.md", path), "---\ntitle: First\n---\n# First".as_bytes());
+        write_file_bytes(&format!("{}/posts/second.md", path), "---\ndraft: true\n---\n# Second".as_bytes());
+
+        let mut config = Config::load_or_default("tests/config.json");
+        config.content.path = path.clone();
+        config.content.home = format!("{}/index.html", path);
+        config.content.render_markdown = Some(true);
+
+        let mut sitemap = SiteMap::build(&config, false, true);
+        let uris = sitemap.collect_uris();
+        assert!(uris.contains(&"/posts/first".to_string()));
+        assert!(!uris.contains(&"/posts/first.md".to_string()));
+        assert!(!uris.contains(&"/posts/second".to_string()));
+        assert!(String::from_utf8(sitemap.to_xml()).unwrap().contains("/posts/first</loc>"));
+        assert!(is_page("https://test.domain/posts/first", "https://test.domain"));
+
+        // publishing a draft
+        write_file_bytes(&format!("{}/posts/second.md", path), "# Second".as_bytes());
+        assert!(sitemap.apply_changes(vec![format!("{}/posts/second.md", path)]).await);
+        assert!(sitemap.collect_uris().contains(&"/posts/second".to_string()));
+
+        // and un-publishing it
+        write_file_bytes(&format!("{}/posts/second.md", path), "---\ndraft: true\n---\n# Second".as_bytes());
+        assert!(sitemap.apply_changes(vec![format!("{}/posts/second.md", path)]).await);
+        assert!(!sitemap.collect_uris().contains(&"/posts/second".to_string()));
+
+        // editing the layout re-renders pages
+        write_file_bytes(&format!("{}/layout.html", path), "<main>{{ content }}</main>".as_bytes());
+        config.content.markdown_layout = Some(format!("{}/layout.html", path));
+        let mut sitemap = SiteMap::build(&config, false, true);
+        sitemap.refresh_all().await;
+        assert_eq!(sitemap.get("/posts/first").unwrap().lock().await.utf8_body().unwrap(), "<main><h1>First</h1>\n</main>");
+        write_file_bytes(&format!("{}/layout.html", path), "<article>{{ content }}</article>".as_bytes());
+        sitemap.apply_changes(vec![format!("{}/layout.html", path)]).await;
+        assert_eq!(sitemap.get("/posts/first").unwrap().lock().await.utf8_body().unwrap(), "<article><h1>First</h1>\n</article>");
+
+        // without rendering Markdown is served as-is
+        config.content.render_markdown = None;
+        let sitemap = SiteMap::build(&config, false, true);
+        assert!(sitemap.collect_uris().contains(&"/posts/first.md".to_string()));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
@@ -0,0 +1,94 @@
+mod common;
+
+#[cfg(test)]
+mod media
+{
+    use busser::{config::Config, content::{media::{media_references, resolve_reference}, sitemap::SiteMap}, filesystem::file::write_file_bytes};
+
+    #[test]
+    fn test_media_references()
+    {
+        let html = r#"<head><meta property="og:image" content="/og.png"><meta name="description" content="not.png"></head>
+            <img src="a.jpg" alt="a"><img data-x='1' srcset="b.webp 1x, c.webp 2x">
+            <video poster="poster.jpg"><source src="clip.mp4?v=2" type="video/mp4"></video>"#;
+        assert_eq!
+        (
+            media_references(html),
+            vec!["/og.png", "a.jpg", "b.webp", "c.webp", "poster.jpg", "clip.mp4?v=2"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_reference()
+    {
+        let domain = "https://test.domain";
+        assert_eq!(resolve_reference("/blog/post", "img/a.jpg", domain), Some("/blog/img/a.jpg".to_string()));
+        assert_eq!(resolve_reference("/blog/post.html", "../a.jpg#x", domain), Some("/a.jpg".to_string()));
+        assert_eq!(resolve_reference("/", "./a.jpg?v=1", domain), Some("/a.jpg".to_string()));
+        assert_eq!(resolve_reference("/blog/post", "/a.jpg", domain), Some("/a.jpg".to_string()));
+        assert_eq!(resolve_reference("/blog/post", "https://test.domain/a.jpg", domain), Some("/a.jpg".to_string()));
+        assert_eq!(resolve_reference("/blog/post", "//test.domain/a.jpg", domain), Some("/a.jpg".to_string()));
+        assert_eq!(resolve_reference("/blog/post", "https://cdn.domain/a.jpg", domain), None);
+        assert_eq!(resolve_reference("/blog/post", "data:image/png;base64,AAAA", domain), None);
+    }
+
+    #[test]
+    fn test_sitemap_media()
+    {
+        let path = format!("tests/test_sitemap_media-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(format!("{}/blog/img", path)).unwrap();
+        write_file_bytes(&format!("{}/index.html", path), "<h1>Home</h1>".as_bytes());
+        write_file_bytes(&format!("{}/blog/post.html", path), "<img src=\"img/a.png\"><video src=\"/clip.mp4\"></video><img src=\"missing.png\">".as_bytes());
+        write_file_bytes(&format!("{}/blog/img/a.png", path), "".as_bytes());
+        write_file_bytes(&format!("{}/clip.mp4", path), "".as_bytes());
+        write_file_bytes(&format!("{}/unused.jpg", path), "".as_bytes());
+
+        let mut config = Config::load_or_default("tests/config.json");
+        config.domain = "https://test.domain".to_string();
+        config.content.path = path.clone();
+        config.content.home = format!("{}/index.html", path);
+
+        let sitemap = SiteMap::build(&config, false, true);
+        let media = sitemap.page_media();
+        let uris = |page: &str| media.get(page).map(|m| m.iter().map(|m| m.uri.as_str()).collect::<Vec<&str>>());
+        assert_eq!(uris("/blog/post"), Some(vec!["/blog/img/a.png", "/clip.mp4"]));
+        assert_eq!(uris("/blog/post.html"), Some(vec!["/blog/img/a.png", "/clip.mp4"]));
+        assert_eq!(uris("/"), Some(vec!["/unused.jpg"]));
+
+        let xml = String::from_utf8(sitemap.to_xml()).unwrap();
+        assert!(xml.contains("<loc>https://test.domain/blog/post</loc>"));
+        assert!(xml.contains("<image:loc>https://test.domain/blog/img/a.png</image:loc>"));
+        assert!(xml.contains("<video:content_loc>https://test.domain/clip.mp4</video:content_loc>"));
+        // every url has a loc
+        assert_eq!(xml.matches("<url>").count(), xml.matches("<loc>").count());
+
+        config.content.sitemap_unreferenced_media = Some(false);
+        let sitemap = SiteMap::build(&config, false, true);
+        assert!(!sitemap.page_media().contains_key("/"));
+        assert!(!String::from_utf8(sitemap.to_xml()).unwrap().contains("unused.jpg"));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_included_media()
+    {
+        let path = format!("tests/test_included_media-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(format!("{}/partials", path)).unwrap();
+        write_file_bytes(&format!("{}/partials/banner.html", path), "<img src=\"/banner.png\">".as_bytes());
+        write_file_bytes(&format!("{}/index.html", path), "<!--#include \"partials/banner.html\"--><h1>Home</h1>".as_bytes());
+        write_file_bytes(&format!("{}/banner.png", path), "".as_bytes());
+
+        let mut config = Config::load_or_default("tests/config.json");
+        config.domain = "https://test.domain".to_string();
+        config.content.path = path.clone();
+        config.content.home = format!("{}/index.html", path);
+        config.content.server_side_includes = Some(true);
+        config.content.sitemap_unreferenced_media = Some(false);
+
+        let media = SiteMap::build(&config, false, true).page_media();
+        assert_eq!(media.get("/").map(|m| m[0].uri.as_str()), Some("/banner.png"));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
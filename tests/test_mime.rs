@@ -3,7 +3,9 @@ mod common;
 #[cfg(test)]
 mod mime
 {
-    use busser::content::mime_type::{Mime, MIME};
+    use std::collections::HashMap;
+
+    use busser::content::mime_type::{register_mime_types, Mime, MIME};
 
 
     #[test]
@@ -142,4 +144,58 @@ mod mime
         assert!(!MIME::Unknown.in_sitemap());
     }
 
+    #[test]
+    fn test_infer_mime_type()
+    {
+        assert_eq!(<MIME as Mime>::infer_mime_type("tests/pages/a.html"), MIME::TextHtml);
+        assert_eq!(<MIME as Mime>::infer_mime_type("tests/pages/A.HTM"), MIME::TextHtml);
+        assert_eq!(<MIME as Mime>::infer_mime_type("app.mjs"), MIME::TextJavascript);
+        assert_eq!(<MIME as Mime>::infer_mime_type("site.webmanifest"), MIME::ApplicationManifest);
+        assert_eq!(<MIME as Mime>::infer_mime_type("font.woff2"), MIME::FontWOFF2);
+        assert_eq!(<MIME as Mime>::infer_mime_type("song.mp3"), MIME::AudioMPEG);
+        assert_eq!(<MIME as Mime>::infer_mime_type("archive.tar.gz"), MIME::ApplicationGZIP);
+        assert_eq!(<MIME as Mime>::infer_mime_type("no_extension"), MIME::Unknown);
+        assert_eq!(<MIME as Mime>::infer_mime_type("unknown.xyz"), MIME::Unknown);
+
+        assert!(MIME::AudioOGG.is_audio());
+        assert!(!MIME::AudioOGG.in_sitemap());
+        assert!(MIME::ApplicationJSON.is_text());
+        assert!(!MIME::FontWOFF.is_text());
+        assert_eq!(MIME::TextHtml.header_value(), "text/html; charset=utf-8");
+        assert_eq!(MIME::ApplicationManifest.header_value(), "application/manifest+json; charset=utf-8");
+        assert_eq!(MIME::ImagePNG.header_value(), "image/png");
+    }
+
+    #[test]
+    fn test_register_mime_types()
+    {
+        let mut mappings = HashMap::new();
+        mappings.insert("glb".to_string(), "model/gltf-binary".to_string());
+        mappings.insert(".GLTF".to_string(), "model/gltf+json".to_string());
+        mappings.insert("vtt".to_string(), "text/vtt".to_string());
+        mappings.insert("jsonld".to_string(), "application/json".to_string());
+        register_mime_types(&mappings);
+
+        let glb = <MIME as Mime>::infer_mime_type("model.glb");
+        assert!(matches!(glb, MIME::Custom(_)));
+        assert_eq!(glb.as_str(), "model/gltf-binary");
+        assert_eq!(glb.header_value(), "model/gltf-binary");
+        assert!(!glb.is_text());
+
+        let gltf = <MIME as Mime>::infer_mime_type("model.gltf");
+        assert_eq!(gltf.as_str(), "model/gltf+json");
+        assert!(gltf.is_text());
+
+        let vtt = <MIME as Mime>::infer_mime_type("captions.vtt");
+        assert!(vtt.is_text());
+        assert!(!vtt.is_html());
+
+        assert_eq!(<MIME as Mime>::infer_mime_type("data.jsonld"), MIME::ApplicationJSON);
+        assert_eq!(<MIME as Mime>::infer_mime_type("index.html"), MIME::TextHtml);
+
+        // re-registering the same type reuses it
+        register_mime_types(&mappings);
+        assert_eq!(<MIME as Mime>::infer_mime_type("model.glb"), glb);
+    }
+
 }
\ No newline at end of file
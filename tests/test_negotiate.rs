@@ -0,0 +1,112 @@
+mod common;
+
+#[cfg(test)]
+mod negotiate
+{
+    use axum::Router;
+    use busser::{config::{Config, NegotiationConfig}, content::{mime_type::MIME, negotiate::{cookie_value, language_variant, parse_quality_list, preferred_image, preferred_language}, sitemap::SiteMap}, filesystem::file::write_file_bytes};
+
+    #[test]
+    fn test_headers()
+    {
+        assert_eq!(language_variant("/a/page.fr.html"), Some(("/a/page".to_string(), "fr".to_string())));
+        assert_eq!(language_variant("/page.en-GB.html"), Some(("/page".to_string(), "en-gb".to_string())));
+        assert_eq!(language_variant("/page.html"), None);
+        assert_eq!(language_variant("/page.fr.css"), None);
+
+        assert_eq!
+        (
+            parse_quality_list("fr;q=0.5, en-GB, de;q=0.9"),
+            vec![("en-gb".to_string(), 1.0), ("de".to_string(), 0.9), ("fr".to_string(), 0.5)]
+        );
+
+        let images = vec![MIME::ImageWEBP, MIME::ImageAVIF];
+        assert_eq!(preferred_image("image/avif,image/webp,image/apng,*/*;q=0.8", &images), Some(MIME::ImageAVIF));
+        assert_eq!(preferred_image("image/webp,*/*", &images), Some(MIME::ImageWEBP));
+        assert_eq!(preferred_image("image/avif;q=0.5,image/webp", &images), Some(MIME::ImageWEBP));
+        assert_eq!(preferred_image("*/*", &images), None);
+        assert_eq!(preferred_image("image/avif", &[MIME::ImageWEBP]), None);
+
+        let languages = vec!["en".to_string(), "fr".to_string(), "pt-br".to_string()];
+        assert_eq!(preferred_language("fr-CH, fr;q=0.9, en;q=0.8", &languages), Some("fr".to_string()));
+        assert_eq!(preferred_language("de, en-US;q=0.5", &languages), Some("en".to_string()));
+        assert_eq!(preferred_language("pt", &languages), Some("pt-br".to_string()));
+        assert_eq!(preferred_language("de, *", &languages), None);
+        assert_eq!(preferred_language("fr;q=0", &languages), None);
+
+        assert_eq!(cookie_value("a=1; lang=fr; b=2", "lang"), Some("fr".to_string()));
+        assert_eq!(cookie_value("a=1", "lang"), None);
+    }
+
+    #[tokio::test]
+    async fn test_negotiated_router()
+    {
+        let path = format!("tests/test_negotiated_router-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(&path).unwrap();
+        write_file_bytes(&format!("{}/photo.jpg", path), "jpeg".as_bytes());
+        write_file_bytes(&format!("{}/photo.webp", path), "webp".as_bytes());
+        write_file_bytes(&format!("{}/photo.avif", path), "avif".as_bytes());
+        write_file_bytes(&format!("{}/page.en.html", path), "english".as_bytes());
+        write_file_bytes(&format!("{}/page.fr.html", path), "français".as_bytes());
+        write_file_bytes(&format!("{}/index.html", path), "home".as_bytes());
+
+        let mut config = Config::load_or_default("tests/config.json");
+        config.domain = "https://test.domain".to_string();
+        config.content.path = path.clone();
+        config.content.home = format!("{}/index.html", path);
+        config.content.negotiation = Some
+        (
+            NegotiationConfig
+            {
+                images: Some(true),
+                languages: Some(true),
+                default_language: Some("en".to_string()),
+                language_cookie: Some("lang".to_string()),
+                language_prefix: Some(true)
+            }
+        );
+
+        let sitemap = SiteMap::build(&config, false, true);
+
+        let xml = String::from_utf8(sitemap.to_xml()).unwrap();
+        assert!(xml.contains("xmlns:xhtml=\"http://www.w3.org/1999/xhtml\""));
+        assert!(xml.contains("<xhtml:link rel=\"alternate\" hreflang=\"fr\" href=\"https://test.domain/fr/page\"/>"));
+        assert!(xml.contains("<xhtml:link rel=\"alternate\" hreflang=\"x-default\" href=\"https://test.domain/page\"/>"));
+
+        let router: Router = sitemap.into();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap(); });
+
+        let client = reqwest::Client::new();
+        let get = |uri: &str, headers: Vec<(&'static str, &'static str)>|
+        {
+            let mut request = client.get(format!("http://{}{}", address, uri));
+            for (name, value) in headers { request = request.header(name, value); }
+            request.send()
+        };
+
+        let response = get("/photo.jpg", vec![("accept", "image/avif,image/webp,*/*;q=0.8")]).await.unwrap();
+        assert_eq!(response.headers()["content-type"], "image/avif");
+        assert_eq!(response.headers()["vary"], "accept");
+        assert_eq!(response.text().await.unwrap(), "avif");
+
+        let response = get("/photo.jpg", vec![("accept", "*/*")]).await.unwrap();
+        assert_eq!(response.headers()["content-type"], "image/jpeg");
+        assert_eq!(response.text().await.unwrap(), "jpeg");
+
+        assert_eq!(get("/photo.webp", vec![("accept", "image/avif")]).await.unwrap().text().await.unwrap(), "webp");
+
+        assert_eq!(get("/page", vec![("accept-language", "fr-FR, en;q=0.5")]).await.unwrap().text().await.unwrap(), "français");
+        assert_eq!(get("/page.html", vec![("accept-language", "fr")]).await.unwrap().text().await.unwrap(), "français");
+        assert_eq!(get("/page", vec![("accept-language", "de")]).await.unwrap().text().await.unwrap(), "english");
+        assert_eq!(get("/page", vec![("accept-language", "fr"), ("cookie", "lang=en")]).await.unwrap().text().await.unwrap(), "english");
+        assert_eq!(get("/fr/page", vec![("accept-language", "en")]).await.unwrap().text().await.unwrap(), "français");
+        assert_eq!(get("/page.en.html", vec![("accept-language", "fr")]).await.unwrap().text().await.unwrap(), "english");
+
+        let response = get("/page", vec![]).await.unwrap();
+        assert_eq!(response.headers()["vary"], "accept-language, cookie");
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
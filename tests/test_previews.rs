@@ -0,0 +1,161 @@
+mod common;
+
+#[cfg(test)]
+mod previews
+{
+    use std::path::Path;
+
+    use axum::{body::Bytes, http::{HeaderMap, HeaderValue}, Router};
+    use busser::{config::{Config, PreviewConfig}, filesystem::file::write_file_bytes, integrations::github::{github_event, preview_event}, server::previews::{is_authorised, preview_name, PreviewEvent, Previews}};
+    use git2::{Repository, Signature};
+    use openssl::base64::encode_block;
+    use reqwest::StatusCode;
+
+    use crate::common::{git_config, local_remote};
+
+    fn preview_config(path: Option<String>) -> PreviewConfig
+    {
+        PreviewConfig
+        {
+            path,
+            prefix: None,
+            token: "preview-token".to_string(),
+            branches: Some(vec!["^feature/".to_string()]),
+            pull_requests: Some(true)
+        }
+    }
+
+    fn basic(password: &str) -> HeaderMap
+    {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_str(&format!("Basic {}", encode_block(format!("reviewer:{}", password).as_bytes()))).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_preview_event()
+    {
+        let git = git_config("https://github.com/JerboaBurrow/Busser", "main");
+        let config = preview_config(None);
+        let event = |event: &str, body: &str| preview_event(event, &Bytes::from(body.to_string()), &git, &config);
+
+        assert_eq!(event("push", r#"{"ref": "refs/heads/main"}"#), None);
+        assert_eq!(event("push", r#"{"ref": "refs/heads/fix"}"#), None);
+        assert_eq!(event("push", r#"{"ref": "refs/tags/feature/v1"}"#), None);
+        assert_eq!
+        (
+            event("push", r#"{"ref": "refs/heads/feature/new page", "deleted": false}"#),
+            Some(PreviewEvent::Update { name: preview_name("feature/new page"), refspec: "refs/heads/feature/new page".to_string() })
+        );
+        assert_eq!(event("push", r#"{"ref": "refs/heads/feature/x", "deleted": true}"#), Some(PreviewEvent::Remove { name: preview_name("feature/x") }));
+
+        assert_eq!
+        (
+            event("pull_request", r#"{"action": "synchronize", "number": 5}"#),
+            Some(PreviewEvent::Update { name: "pr-5".to_string(), refspec: "refs/pull/5/head".to_string() })
+        );
+        assert_eq!(event("pull_request", r#"{"action": "closed", "number": 5}"#), Some(PreviewEvent::Remove { name: "pr-5".to_string() }));
+        assert_eq!(event("pull_request", r#"{"action": "labeled", "number": 5}"#), None);
+        assert_eq!(event("issues", r#"{"action": "opened", "number": 5}"#), None);
+
+        let config = PreviewConfig { pull_requests: None, ..preview_config(None) };
+        assert_eq!(preview_event("pull_request", &Bytes::from(r#"{"action": "opened", "number": 5}"#), &git, &config), None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-github-event", HeaderValue::from_static("pull_request"));
+        assert_eq!(github_event(&headers), None);
+        headers.insert("user-agent", HeaderValue::from_static("GitHub-Hookshot/abc"));
+        assert_eq!(github_event(&headers), Some("pull_request".to_string()));
+    }
+
+    #[test]
+    fn test_authorisation()
+    {
+        assert!(preview_name("feature/a.b_c-d").starts_with("feature-a-b_c-d-"));
+        assert_eq!(preview_name("feature/a.b_c-d").len(), "feature-a-b_c-d-".len() + 8);
+        assert_eq!(preview_name("feature/x"), preview_name("feature/x"));
+        assert_ne!(preview_name("feature/x"), preview_name("feature-x"));
+        assert!(is_authorised(&basic("token"), "token"));
+        assert!(!is_authorised(&basic("tokens"), "token"));
+        assert!(!is_authorised(&basic(""), ""));
+        assert!(!is_authorised(&HeaderMap::new(), "token"));
+    }
+
+    #[tokio::test]
+    async fn test_previews()
+    {
+        let id = uuid::Uuid::new_v4();
+        let remote = format!("tests/test_previews_remote-{}", id);
+        let path = format!("tests/test_previews-{}", id);
+
+        let repo = Repository::init(&remote).unwrap();
+        write_file_bytes(&format!("{}/index.html", remote), b"main");
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("index.html")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("jerboa", "j@b").unwrap();
+        let main = repo.commit(Some("HEAD"), &signature, &signature, "main", &tree, &[]).unwrap();
+
+        write_file_bytes(&format!("{}/index.html", remote), b"feature");
+        index.add_path(Path::new("index.html")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.find_commit(main).unwrap();
+        repo.commit(Some("refs/heads/feature/a"), &signature, &signature, "feature", &tree, &[&parent]).unwrap();
+
+        let git = git_config(&local_remote(&remote), "main");
+        let mut config = Config::load_or_default("tests/config.json");
+        config.domain = "test.domain".to_string();
+        config.content.path = "pages".to_string();
+        config.content.home = "pages/index.html".to_string();
+        config.previews = Some(preview_config(Some(path.clone())));
+
+        let previews = Previews::new(&config).unwrap();
+        assert!(previews.names().is_empty());
+        assert_eq!(previews.url("feature-a"), "https://test.domain/_preview/feature-a/");
+        assert!(previews.update("feature-a", &git, "refs/heads/feature/a").unwrap().0);
+        assert!(!previews.update("feature-a", &git, "refs/heads/feature/a").unwrap().0);
+        assert_eq!(previews.names(), vec!["feature-a"]);
+
+        // checked out previews are served after a restart
+        assert_eq!(Previews::new(&config).unwrap().names(), vec!["feature-a"]);
+
+        let router: Router = Router::new().route(&previews.uri_pattern(), previews.route("not found".to_string()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap(); });
+
+        let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap();
+        let get = |uri: &str, password: &str| client.get(format!("http://{}{}", address, uri)).basic_auth("reviewer", Some(password)).send();
+
+        let response = get("/_preview/feature-a/", "wrong").await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers().contains_key("www-authenticate"));
+        let response = get("/_preview/feature-a/", "preview-token").await.unwrap();
+        assert_eq!(response.headers()["cache-control"], "private, no-store");
+        assert_eq!(response.headers()["x-robots-tag"], "noindex");
+        assert_eq!(response.text().await.unwrap(), "feature");
+        assert_eq!(get("/_preview/feature-a/index.html", "preview-token").await.unwrap().text().await.unwrap(), "feature");
+        assert_eq!(get("/_preview/feature-a", "preview-token").await.unwrap().headers()["location"], "/_preview/feature-a/");
+        assert_eq!(get("/_preview/missing/", "preview-token").await.unwrap().status(), StatusCode::NOT_FOUND);
+
+        assert!(previews.remove("feature-a").unwrap());
+        assert!(!previews.remove("feature-a").unwrap());
+        assert!(!Path::new(&format!("{}/feature-a", path)).exists());
+        assert_eq!(get("/_preview/feature-a/", "preview-token").await.unwrap().status(), StatusCode::NOT_FOUND);
+
+        // as from a webhook
+        let event = PreviewEvent::Update { name: "feature-b".to_string(), refspec: "refs/heads/feature/a".to_string() };
+        assert!(previews.handle(event.clone(), &config).await.is_err());
+        config.git = Some(git.clone());
+        config.notification_endpoint = None;
+        previews.handle(event, &config).await.unwrap();
+        assert_eq!(previews.names(), vec!["feature-b"]);
+        assert_eq!(get("/_preview/feature-b/", "preview-token").await.unwrap().text().await.unwrap(), "feature");
+        previews.handle(PreviewEvent::Remove { name: "feature-b".to_string() }, &config).await.unwrap();
+        assert!(previews.names().is_empty());
+        assert!(!Path::new(&format!("{}/feature-b", path)).exists());
+
+        let _ = std::fs::remove_dir_all(remote);
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
@@ -0,0 +1,102 @@
+mod common;
+
+#[cfg(test)]
+mod releases
+{
+    use axum::{body::Bytes, http::HeaderMap};
+    use busser::{config::{Config, GitConfig, ReleasesConfig}, filesystem::file::{read_file_utf8, write_file_bytes}, integrations::git::releases::{check_release, Release, Releases}, server::api::{releases::Rollback, ApiRequest}};
+    use git2::Repository;
+    use reqwest::StatusCode;
+
+    use crate::common::{commit, git_config, local_remote, site_config};
+
+    fn releases_config(path: &str, remote: &str, branch: &str) -> Config
+    {
+        let releases = ReleasesConfig { path: Some(format!("{}/releases", path)), keep: Some(2), check_links: None };
+        site_config(path, GitConfig { releases: Some(releases), ..git_config(&local_remote(remote), branch) })
+    }
+
+    #[test]
+    fn test_check_release()
+    {
+        let path = format!("tests/test_check_release-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(&path).unwrap();
+        let mut config = Config::load_or_default("tests/config.json");
+        config.content.path = "pages".to_string();
+        config.content.home = "pages/index.html".to_string();
+        config.content.generate_sitemap = Some(false);
+
+        assert!(check_release(&config, &path, false).unwrap_err().contains("home page"));
+
+        write_file_bytes(&format!("{}/index.html", path), r#"<a href="/missing.html">missing</a>"#.as_bytes());
+        assert!(check_release(&config, &path, false).is_ok());
+        assert!(check_release(&config, &path, true).unwrap_err().contains("broken links"));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_deploy_and_rollback()
+    {
+        let path = format!("tests/test_releases-{}", uuid::Uuid::new_v4());
+        let remote = format!("{}/remote", path);
+        let repo = Repository::init(&remote).unwrap();
+        commit(&repo, &[("index.html", Some("one"))], "one");
+        let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+
+        let config = releases_config(&path, &remote, &branch);
+        assert!(!config.git.as_ref().unwrap().is_watchable());
+        let site = config.content.path.clone();
+        let releases = Releases::new(&config).unwrap();
+        let active = |releases: &Releases| releases.list().into_iter().filter(|r| r.active).collect::<Vec<Release>>();
+
+        // a clone at the content path is replaced by the first release
+        std::fs::create_dir_all(&site).unwrap();
+        assert!(releases.deploy(&config).unwrap().is_some());
+        assert!(std::fs::symlink_metadata(&site).unwrap().is_symlink());
+        assert_eq!(read_file_utf8(&config.content.home).unwrap(), "one");
+        assert!(releases.deploy(&config).unwrap().is_none());
+
+        let two = commit(&repo, &[("index.html", Some("two"))], "two");
+        assert_eq!(releases.deploy(&config).unwrap().unwrap().hash, two.to_string()[0..7]);
+        assert_eq!(read_file_utf8(&config.content.home).unwrap(), "two");
+        assert_eq!(releases.list().len(), 2);
+
+        // failing checks leave the active release served
+        let failed = commit(&repo, &[("index.html", None), ("other.html", Some("other"))], "no home");
+        assert!(releases.deploy(&config).unwrap_err().why.contains("failed checks"));
+        // the failed head is not retried
+        assert_eq!(releases.failed(), Some(failed.to_string()[0..7].to_string()));
+        assert!(releases.deploy(&config).unwrap().is_none());
+        assert_eq!(releases.list().len(), 2);
+        assert_eq!(active(&releases)[0].commit, two.to_string()[0..7]);
+        assert_eq!(read_file_utf8(&config.content.home).unwrap(), "two");
+
+        let first = releases.list()[0].name.clone();
+        assert_eq!(releases.rollback().unwrap(), first);
+        assert_eq!(read_file_utf8(&config.content.home).unwrap(), "one");
+        assert!(releases.rollback().is_err());
+        assert!(releases.activate("20000101T000000000-abc1234").is_err());
+
+        let mut request = Rollback::new();
+        let latest = releases.list()[1].name.clone();
+        assert_eq!(request.deserialise_payload(HeaderMap::new(), Bytes::from(format!(r#"{{"action": "activate", "name": "{}"}}"#, latest))), StatusCode::OK);
+        let (body, status) = request.respond(releases.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        let listed: Vec<Release> = serde_json::from_str(&body.unwrap()).unwrap();
+        assert!(listed[1].active);
+        assert_eq!(read_file_utf8(&config.content.home).unwrap(), "two");
+        assert_eq!(request.deserialise_payload(HeaderMap::new(), Bytes::from(r#"{"action": "undo"}"#)), StatusCode::BAD_REQUEST);
+
+        // the oldest inactive release is pruned
+        let four = commit(&repo, &[("index.html", Some("four"))], "four");
+        releases.deploy(&config).unwrap();
+        assert!(releases.failed().is_none());
+        let kept: Vec<String> = releases.list().into_iter().map(|r| r.commit).collect();
+        assert_eq!(kept, vec![two.to_string()[0..7].to_string(), four.to_string()[0..7].to_string()]);
+        assert_eq!(read_file_utf8(&config.content.home).unwrap(), "four");
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
@@ -0,0 +1,102 @@
+mod common;
+
+#[cfg(test)]
+mod robots
+{
+    use axum::Router;
+    use busser::{config::{Config, RobotsConfig, RobotsGroupConfig}, content::{robots::{Robots, RobotsGroup}, sitemap::SiteMap}, filesystem::file::{read_file_utf8, write_file_bytes}};
+
+    const ROBOTS: &str = "# hand written\nUser-agent: *\nUser-agent: Bingbot\nDisallow: /private/ # secret\nCrawl-delay: 5\n\nUser-agent: GPTBot\nDisallow: /\n\nSitemap: https://test.domain/sitemap.xml\n";
+
+    fn group(user_agents: &[&str], allow: &[&str], disallow: &[&str], crawl_delay: Option<u64>) -> RobotsGroupConfig
+    {
+        RobotsGroupConfig
+        {
+            user_agents: user_agents.iter().map(|u| u.to_string()).collect(),
+            allow: Some(allow.iter().map(|a| a.to_string()).collect()),
+            disallow: Some(disallow.iter().map(|d| d.to_string()).collect()),
+            crawl_delay
+        }
+    }
+
+    #[test]
+    fn test_parse()
+    {
+        let robots = Robots::parse(ROBOTS);
+        assert_eq!(robots.header, vec!["# hand written"]);
+        assert_eq!(robots.sitemaps, vec!["https://test.domain/sitemap.xml"]);
+        assert_eq!
+        (
+            robots.groups,
+            vec!
+            [
+                RobotsGroup
+                {
+                    user_agents: vec!["*".to_string(), "Bingbot".to_string()],
+                    rules: vec![("Disallow".to_string(), "/private/".to_string()), ("Crawl-delay".to_string(), "5".to_string())]
+                },
+                RobotsGroup { user_agents: vec!["GPTBot".to_string()], rules: vec![("Disallow".to_string(), "/".to_string())] }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge()
+    {
+        let mut robots = Robots::parse(ROBOTS);
+        robots.merge(&group(&["bingbot", "*"], &["/private/public.html"], &["/private/"], Some(10)));
+        robots.merge(&group(&["CCBot"], &[], &["/"], None));
+        robots.add_sitemap("https://test.domain/sitemap.xml");
+
+        assert_eq!
+        (
+            robots.to_string(),
+            "# hand written\n\nUser-agent: *\nUser-agent: Bingbot\nDisallow: /private/\nAllow: /private/public.html\nCrawl-delay: 10\n\nUser-agent: GPTBot\nDisallow: /\n\nUser-agent: CCBot\nDisallow: /\n\nSitemap: https://test.domain/sitemap.xml"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sitemap_robots()
+    {
+        let path = format!("tests/test_sitemap_robots-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(&path).unwrap();
+        write_file_bytes(&format!("{}/index.html", path), "home".as_bytes());
+        write_file_bytes(&format!("{}/robots.txt", path), "User-agent: *\nDisallow: /drafts/".as_bytes());
+
+        let mut config = Config::load_or_default("tests/config.json");
+        config.domain = "https://test.domain".to_string();
+        config.content.path = path.clone();
+        config.content.home = format!("{}/index.html", path);
+        config.content.generate_sitemap = Some(false);
+
+        // nothing to generate
+        assert!(SiteMap::build(&config, false, true).robots().is_none());
+
+        config.content.robots = Some(RobotsConfig { groups: Some(vec![group(&["*"], &[], &["/admin/"], None)]), merge: None });
+        assert_eq!(SiteMap::build(&config, false, true).robots().unwrap().to_string(), "User-agent: *\nDisallow: /drafts/\nDisallow: /admin/");
+
+        config.content.robots = Some(RobotsConfig { groups: Some(vec![group(&["*"], &[], &["/admin/"], None)]), merge: Some(false) });
+        config.content.generate_sitemap = Some(true);
+        let sitemap = SiteMap::build(&config, false, true);
+        assert_eq!(sitemap.robots().unwrap().to_string(), "User-agent: *\nDisallow: /admin/\n\nSitemap: https://test.domain/sitemap.xml");
+
+        // the hand written robots.txt is untouched, but the generated one is served
+        assert_eq!(read_file_utf8(&format!("{}/robots.txt", path)).unwrap(), "User-agent: *\nDisallow: /drafts/");
+        assert!(!std::path::Path::new(&format!("{}/sitemap.xml", path)).exists());
+
+        let router: Router = sitemap.into();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap(); });
+
+        let response = reqwest::get(format!("http://{}/robots.txt", address)).await.unwrap();
+        assert_eq!(response.headers()["content-type"], "text/plain; charset=utf-8");
+        assert!(response.text().await.unwrap().contains("Disallow: /admin/"));
+
+        let response = reqwest::get(format!("http://{}/sitemap.xml", address)).await.unwrap();
+        assert!(response.status().is_success());
+        assert!(response.text().await.unwrap().contains("<loc>https://test.domain/</loc>"));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
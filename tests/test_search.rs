@@ -0,0 +1,125 @@
+mod common;
+
+#[cfg(test)]
+mod search
+{
+    use axum::Router;
+    use busser::{config::{Config, SearchConfig}, content::{search::{extract_text, snippet, stem, Document, SearchIndex, SearchResponse}, sitemap::SiteMap}, filesystem::file::write_file_bytes};
+
+    fn document(uri: &str, title: &str, text: &str) -> Document
+    {
+        Document { uri: uri.to_string(), title: title.to_string(), text: text.to_string() }
+    }
+
+    #[test]
+    fn test_extract_text()
+    {
+        let html = "<html><head><title>Ignored</title></head><body><style>p {}</style><h1>Hello &amp; welcome</h1>\n<!-- hidden --><script>var x = 1;</script><p>to the\n  burrow</p></body></html>";
+        assert_eq!(extract_text(html), "Hello & welcome to the burrow");
+    }
+
+    #[test]
+    fn test_stem_and_snippet()
+    {
+        assert_eq!(stem("running"), "run");
+        assert_eq!(stem("runs"), "run");
+        assert_eq!(stem("pages"), "page");
+        assert_eq!(stem("libraries"), "library");
+        assert_eq!(stem("boxes"), "box");
+        assert_eq!(stem("class"), "class");
+        assert_eq!(stem("bus"), "bus");
+
+        let text = format!("{} jerboa {}", "a".repeat(100), "b".repeat(200));
+        let s = snippet(&text, &["jerboa".to_string()]);
+        assert!(s.starts_with('…') && s.ends_with('…'));
+        assert!(s.contains("jerboa"));
+        assert_eq!(snippet("short text", &["missing".to_string()]), "short text");
+        // multi-byte text is cut on character boundaries
+        assert!(snippet(&"é".repeat(200), &["x".to_string()]).ends_with('…'));
+    }
+
+    #[test]
+    fn test_search_index()
+    {
+        let index = SearchIndex::new
+        (
+            vec!
+            [
+                document("/a", "Jerboas", "Jerboas are running rodents of the desert"),
+                document("/b", "Deserts", "A desert burrow, where a jerboa runs at night"),
+                document("/c", "Servers", "Busser serves static sites")
+            ],
+            true
+        );
+        assert_eq!(index.len(), 3);
+
+        // title words rank higher
+        let results = index.search("jerboa", false, 10);
+        assert_eq!(results.iter().map(|r| r.uri.as_str()).collect::<Vec<&str>>(), vec!["/a", "/b"]);
+        assert!(results[0].score > results[1].score);
+        assert_eq!(results[0].title, "Jerboas");
+
+        // stemmed, and pages matching all words first
+        let results = index.search("run desert night", false, 10);
+        assert_eq!(results[0].uri, "/b");
+        assert_eq!(index.search("run desert night", false, 1).len(), 1);
+
+        assert!(index.search("serv", false, 10).is_empty());
+        assert_eq!(index.search("serv", true, 10)[0].uri, "/c");
+        // prefixes match words before stemming, running is indexed as run
+        assert!(index.search("runni", false, 10).is_empty());
+        assert!(index.search("runni", true, 10).iter().any(|r| r.uri == "/a"));
+        assert!(index.search("", true, 10).is_empty());
+
+        let unstemmed = SearchIndex::new(vec![document("/a", "", "running")], false);
+        assert!(unstemmed.search("run", false, 10).is_empty());
+        assert_eq!(unstemmed.search("run", true, 10).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_route()
+    {
+        let path = format!("tests/test_search_route-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(&path).unwrap();
+        write_file_bytes(&format!("{}/index.html", path), "<html><head><title>Home</title></head><body>Welcome to the burrow</body></html>".as_bytes());
+        write_file_bytes(&format!("{}/post.md", path), "---\ntitle: Jerboa facts\n---\n# Facts\n\nA jerboa can jump <far>.".as_bytes());
+
+        let mut config = Config::load_or_default("tests/config.json");
+        config.domain = "https://test.domain".to_string();
+        config.content.path = path.clone();
+        config.content.home = format!("{}/index.html", path);
+        config.content.generate_sitemap = Some(false);
+        config.content.render_markdown = Some(true);
+        config.content.search = Some(SearchConfig { results_page: Some(true), ..Default::default() });
+
+        let sitemap = SiteMap::build(&config, false, true);
+        assert_eq!(sitemap.search_index().read().unwrap().len(), 2);
+
+        let router: Router = sitemap.into();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap(); });
+
+        let response = reqwest::get(format!("http://{}/search?q=jump", address)).await.unwrap();
+        assert_eq!(response.headers()["content-type"], "application/json");
+        let response: SearchResponse = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+        assert_eq!(response.query, "jump");
+        assert_eq!(response.results.len(), 1);
+        assert_eq!(response.results[0].uri, "/post");
+        assert_eq!(response.results[0].title, "Jerboa facts");
+
+        let response = reqwest::Client::new().get(format!("http://{}/search?q=burrow&limit=0", address)).send().await.unwrap();
+        let response: SearchResponse = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+        assert!(response.results.is_empty());
+
+        let response = reqwest::Client::new()
+            .get(format!("http://{}/search?q=%3Cburrow%3E", address))
+            .header("accept", "text/html")
+            .send().await.unwrap();
+        let body = response.text().await.unwrap();
+        assert!(body.contains("<a href=\"/\">Home</a>"));
+        assert!(body.contains("value=\"&lt;burrow&gt;\""));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
@@ -0,0 +1,159 @@
+mod common;
+
+#[cfg(test)]
+mod short_links
+{
+    use std::collections::HashMap;
+
+    use axum::{body::Bytes, http::HeaderMap, Router};
+    use busser::{config::{Config, ShortLinkConfig, ShortLinksConfig}, filesystem::file::read_file_utf8, server::{api::{require_token, short_links::ShortLink, ApiRequest}, short_links::{is_expired, ShortLinks}, stats::{digest::{digest_message, process_hits}, hits::{Hit, HitStats}}}};
+    use chrono::{DateTime, Utc};
+    use reqwest::StatusCode;
+
+    fn link(target: &str, expires_utc: Option<&str>) -> ShortLinkConfig
+    {
+        ShortLinkConfig { target: target.to_string(), expires_utc: expires_utc.map(|e| e.to_string()) }
+    }
+
+    fn links_config(path: Option<String>) -> ShortLinksConfig
+    {
+        ShortLinksConfig
+        {
+            path,
+            prefix: Some("go".to_string()),
+            links: Some(HashMap::from([("docs".to_string(), link("https://docs.rs/busser", None))]))
+        }
+    }
+
+    #[test]
+    fn test_require_token()
+    {
+        // without an api token links cannot be changed
+        assert_eq!(require_token(None, &HeaderMap::new(), &Bytes::from("{}")), StatusCode::UNAUTHORIZED);
+        assert_eq!(require_token(Some("token".to_string()), &HeaderMap::new(), &Bytes::from("{}")), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_expiry()
+    {
+        let now: DateTime<Utc> = DateTime::parse_from_rfc3339("2024-06-01T00:00:00Z").unwrap().to_utc();
+        assert!(!is_expired(&link("/", None), now));
+        assert!(!is_expired(&link("/", Some("2024-06-02T00:00:00Z")), now));
+        assert!(is_expired(&link("/", Some("2024-05-31T00:00:00+00:00")), now));
+        assert!(is_expired(&link("/", Some("tomorrow")), now));
+    }
+
+    #[test]
+    fn test_short_links()
+    {
+        let path = format!("tests/test_short_links-{}.json", uuid::Uuid::new_v4());
+
+        let read_only = ShortLinks::new(&links_config(None));
+        assert_eq!(read_only.prefix, "/go/");
+        assert_eq!(read_only.uri_pattern(), "/go/:name");
+        assert_eq!(read_only.get("docs"), Some(link("https://docs.rs/busser", None)));
+        assert!(read_only.insert("abc", link("/", None)).is_err());
+
+        let links = ShortLinks::new(&links_config(Some(path.clone())));
+        assert!(links.insert("docs", link("/", None)).is_err());
+        assert!(links.remove("docs").is_err());
+        assert!(links.insert("a/b", link("/", None)).is_err());
+        assert!(links.insert("abc", link("/", Some("soon"))).is_err());
+
+        assert!(links.insert("abc", link("/blog/a-long-title.html", None)).is_ok());
+        assert!(links.insert("old", link("/", Some("2000-01-01T00:00:00Z"))).is_ok());
+        assert_eq!(links.get("abc"), Some(link("/blog/a-long-title.html", None)));
+        assert_eq!(links.get("old"), None);
+        assert_eq!(links.all().len(), 3);
+
+        // saved, and shared by clones
+        let reloaded = ShortLinks::new(&links_config(Some(path.clone())));
+        assert_eq!(reloaded.get("abc"), Some(link("/blog/a-long-title.html", None)));
+        let clone = links.clone();
+        assert!(clone.remove("abc").unwrap());
+        assert!(!links.remove("abc").unwrap());
+        assert!(!read_file_utf8(&path).unwrap().contains("abc"));
+
+        let unwritable = ShortLinks::new(&links_config(Some(format!("tests/missing-{}/links.json", uuid::Uuid::new_v4()))));
+        assert!(unwritable.insert("abc", link("/", None)).unwrap_err().why.contains("saving short links"));
+        assert_eq!(unwritable.get("abc"), None);
+
+        // links are unchanged when they cannot be saved
+        let dir = format!("tests/test_short_links_removed-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(&dir).unwrap();
+        let removed = ShortLinks::new(&links_config(Some(format!("{}/links.json", dir))));
+        assert!(removed.insert("abc", link("/", None)).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(removed.remove("abc").is_err());
+        assert_eq!(removed.get("abc"), Some(link("/", None)));
+        assert!(removed.insert("def", link("/", None)).is_err());
+        assert_eq!(removed.get("def"), None);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_api_request()
+    {
+        let path = format!("tests/test_short_links_api-{}.json", uuid::Uuid::new_v4());
+        let links = ShortLinks::new(&links_config(Some(path.clone())));
+
+        let mut request = ShortLink::new();
+        assert_eq!(request.deserialise_payload(HeaderMap::new(), Bytes::from(r#"{"action": "rename"}"#)), StatusCode::BAD_REQUEST);
+
+        assert_eq!(request.deserialise_payload(HeaderMap::new(), Bytes::from(r#"{"action": "create", "name": "abc", "target": "https://jerboa.app"}"#)), StatusCode::OK);
+        let (body, status) = request.respond(links.clone()).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let all: HashMap<String, ShortLinkConfig> = serde_json::from_str(&body.unwrap()).unwrap();
+        assert_eq!(all["abc"], link("https://jerboa.app", None));
+        assert_eq!(all.len(), 2);
+
+        assert_eq!(request.deserialise_payload(HeaderMap::new(), Bytes::from(r#"{"action": "delete", "name": "docs"}"#)), StatusCode::OK);
+        assert_eq!(request.respond(links.clone()).await.1, StatusCode::BAD_REQUEST);
+
+        assert_eq!(request.deserialise_payload(HeaderMap::new(), Bytes::from(r#"{"action": "delete", "name": "abc"}"#)), StatusCode::OK);
+        assert_eq!(request.respond(links.clone()).await.1, StatusCode::OK);
+        assert_eq!(request.respond(links.clone()).await.1, StatusCode::NOT_FOUND);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_redirect()
+    {
+        let links = ShortLinks::new(&links_config(None));
+        let router: Router = Router::new().route(&links.uri_pattern(), links.route("not found".to_string()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap(); });
+
+        let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap();
+        let response = client.get(format!("http://{}/go/docs", address)).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(response.headers()["location"], "https://docs.rs/busser");
+
+        let response = client.get(format!("http://{}/go/missing", address)).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.text().await.unwrap(), "not found");
+    }
+
+    #[test]
+    fn test_digest_clicks()
+    {
+        let mut config = Config::load_or_default("tests/config.json");
+        config.stats.ignore_invalid_paths = Some(true);
+        config.short_links = Some(links_config(None));
+
+        let mut stats = HitStats::new();
+        let now = Utc::now().to_rfc3339();
+        for (i, path) in ["/go/abc", "/go/abc?ref=mail", "/go/xyz"].iter().enumerate()
+        {
+            stats.hits.insert([i as u8; 64], Hit { times: vec![now.clone()], path: path.to_string(), ip_hash: format!("{}", i) });
+        }
+
+        let digest = process_hits(None, None, &config, Some(stats));
+        assert_eq!(digest.short_link_clicks, vec![("abc".to_string(), 2), ("xyz".to_string(), 1)]);
+        assert!(!digest.top_resources.iter().any(|(uri, _)| uri.starts_with("/go/")));
+        assert!(digest_message(&digest, None, None).contains("Short link clicks:\n  abc : 2\n  xyz : 1\n"));
+    }
+}
@@ -3,10 +3,9 @@ mod common;
 #[cfg(test)]
 mod sitemap
 {
-    use std::{fs::remove_file, path::Path, time::SystemTime};
+    use std::{path::Path, time::{Duration, SystemTime}};
 
-    use busser::{config::Config, content::sitemap::{lastmod, SiteMap}, filesystem::file::read_file_utf8};
-    use chrono::{DateTime, Datelike, Utc};
+    use busser::{config::{ChangeFrequency, Config, SitemapRuleConfig}, content::{sitemap::{lastmod, split_urls, LastModified, SiteMap}, watcher::ContentWatcher}, filesystem::file::{read_file_utf8, write_file_bytes}};
 
     #[test]
     fn test_lastmod()
@@ -17,25 +16,15 @@ mod sitemap
     #[test]
     fn test_build()
     {
-
-        for file in vec!["tests/pages/robots.txt", "tests/pages/sitemap.xml"]
-        {
-            let path = Path::new(file);
-            if path.exists()
-            {
-                let _ = remove_file(file);
-            }
-        }
-
         let mut config = Config::load_or_default("tests/config.json");
         config.domain = "https://test.domain".to_string();
+        config.content.generate_sitemap = Some(true);
 
         let sitemap = SiteMap::build(&config, false, false);
-        
-        sitemap.write_robots();
-        assert!(Path::new("tests/pages/robots.txt").exists());
-        sitemap.write_sitemap_xml();
-        assert!(Path::new("tests/pages/sitemap.xml").exists());
+
+        // served from memory, the content path is untouched
+        assert!(!Path::new("tests/pages/robots.txt").exists());
+        assert!(!Path::new("tests/pages/sitemap.xml").exists());
 
         let uris = sitemap.collect_uris();
         assert!(uris.contains(&"/a".to_string()));
@@ -45,28 +34,140 @@ mod sitemap
         assert!(uris.contains(&"/b.html".to_string()));
         assert!(uris.contains(&"/c/d.html".to_string()));
 
-        let sitemap_disk = read_file_utf8("tests/pages/sitemap.xml").unwrap();
-        let robots_disk = read_file_utf8("tests/pages/robots.txt").unwrap();
-
-        for file in vec!["tests/pages/robots.txt", "tests/pages/sitemap.xml"]
-        {
-            let path = Path::new(file);
-            if path.exists()
-            {
-                let _ = remove_file(file);
-            }
-        }
+        let generated_sitemap = String::from_utf8(sitemap.generated().get("/sitemap.xml").unwrap()).unwrap();
+        let generated_robots = String::from_utf8(sitemap.generated().get("/robots.txt").unwrap()).unwrap();
 
         let mut expected_sitemap = read_file_utf8("tests/common/sitemap.xml").unwrap();
         // for windows...
         expected_sitemap = expected_sitemap.replace("\r", "");
 
-        let date: DateTime<Utc> = SystemTime::now().into();
-        let today = format!("{}-{:0>2}-{:0>2}",date.year(), date.month(), date.day());
-        expected_sitemap = expected_sitemap.replace("TODAY", &today);
+        // tests/pages are committed together, so share a lastmod
+        let modified = LastModified::new("tests/pages").get("tests/pages/a.html").unwrap();
+        expected_sitemap = expected_sitemap.replace("LASTMOD", &lastmod(modified.into()));
+
+        assert_eq!(generated_sitemap, expected_sitemap);
+        assert_eq!("Sitemap: https://test.domain/sitemap.xml", generated_robots);
+    }
+
+    #[test]
+    fn test_last_modified()
+    {
+        let path = format!("tests/test_last_modified-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(&path).unwrap();
+        let page = format!("{}/a.html", path);
+        write_file_bytes(&page, "untracked".as_bytes());
+
+        let last_modified = LastModified::new(&path);
+        // not committed, so the file's modified time
+        assert_eq!(last_modified.get(&page), Some(std::fs::metadata(&page).unwrap().modified().unwrap().into()));
+        assert_eq!(last_modified.get(&format!("{}/missing.html", path)), None);
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_sitemap_rules()
+    {
+        let mut config = Config::load_or_default("tests/config.json");
+        config.domain = "https://test.domain".to_string();
+        config.content.sitemap_rules = Some(vec!
+        [
+            SitemapRuleConfig { paths: vec!["^/c/".to_string()], changefreq: Some(ChangeFrequency::Weekly), priority: Some(0.25) },
+            SitemapRuleConfig { paths: vec!["^/a".to_string(), "^/c/".to_string()], changefreq: Some(ChangeFrequency::Daily), priority: Some(2.0) }
+        ]);
+
+        let xml = String::from_utf8(SiteMap::build(&config, false, true).to_xml()).unwrap();
+        assert!(xml.contains("<loc>https://test.domain/a</loc>\n        <lastmod>"));
+        assert!(xml.contains("</lastmod>\n        <changefreq>daily</changefreq>\n        <priority>1.0</priority>\n    </url>\n    <url>\n        <loc>https://test.domain/a.html</loc>"));
+        assert!(xml.contains("<changefreq>weekly</changefreq>\n        <priority>0.2</priority>"));
+        assert_eq!(xml.matches("<changefreq>").count(), 4);
+    }
+
+    #[test]
+    fn test_split_urls()
+    {
+        let urls: Vec<String> = (0..5).map(|i| format!("<url>{}</url>", i)).collect();
+        assert_eq!(split_urls(urls.clone(), 10, 1000), vec![urls.clone()]);
+        assert_eq!(split_urls(urls.clone(), 2, 1000).iter().map(|s| s.len()).collect::<Vec<usize>>(), vec![2, 2, 1]);
+        // 13 bytes and a line break each
+        assert_eq!(split_urls(urls.clone(), 10, 28).iter().map(|s| s.len()).collect::<Vec<usize>>(), vec![2, 2, 1]);
+        assert!(split_urls(vec![], 10, 1000).is_empty());
+
+        let path = format!("tests/test_split_urls-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(&path).unwrap();
+        write_file_bytes(&format!("{}/a.html", path), "this is /a".as_bytes());
+        let mut config = Config::load_or_default("tests/config.json");
+        config.domain = "https://test.domain".to_string();
+        config.content.path = path.clone();
+        config.content.home = format!("{}/a.html", path);
+
+        let sitemaps = SiteMap::build(&config, false, true).sitemaps();
+        assert_eq!(sitemaps.len(), 1);
+        assert_eq!(sitemaps[0].0, "sitemap.xml");
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[tokio::test]
+    async fn test_apply_changes()
+    {
+        let path = format!("tests/test_apply_changes-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(format!("{}/c", path)).unwrap();
+        write_file_bytes(&format!("{}/a.html", path), "this is /a".as_bytes());
+        write_file_bytes(&format!("{}/c/d.html", path), "this is /c/d".as_bytes());
+
+        let mut config = Config::load_or_default("tests/config.json");
+        config.content.path = path.clone();
+        config.content.home = format!("{}/a.html", path);
+
+        let mut sitemap = SiteMap::build(&config, false, true);
+        let hash = sitemap.get_hash();
+        assert!(sitemap.collect_uris().contains(&"/c/d".to_string()));
+
+        // modification is refreshed in place
+        write_file_bytes(&format!("{}/a.html", path), "this is /a, modified".as_bytes());
+        assert!(!sitemap.apply_changes(vec![format!("{}/a.html", path)]).await);
+        assert_eq!(sitemap.get_hash(), hash);
+
+        // additions
+        write_file_bytes(&format!("{}/b.html", path), "this is /b".as_bytes());
+        write_file_bytes(&format!("{}/b.css", path), "".as_bytes());
+        assert!(sitemap.apply_changes(vec![format!("{}/b.html", path), format!("{}/b.css", path)]).await);
+        let uris = sitemap.collect_uris();
+        assert!(uris.contains(&"/b".to_string()));
+        assert!(uris.contains(&"/b.html".to_string()));
+        assert!(uris.contains(&"/b.css".to_string()));
+
+        // removing b.css does not remove the page /b
+        std::fs::remove_file(format!("{}/b.css", path)).unwrap();
+        assert!(sitemap.apply_changes(vec![format!("{}/b.css", path)]).await);
+        let uris = sitemap.collect_uris();
+        assert!(uris.contains(&"/b".to_string()));
+        assert!(!uris.contains(&"/b.css".to_string()));
+
+        // directory removal
+        std::fs::remove_dir_all(format!("{}/c", path)).unwrap();
+        assert!(sitemap.apply_changes(vec![format!("{}/c", path)]).await);
+        let uris = sitemap.collect_uris();
+        assert!(!uris.contains(&"/c/d".to_string()));
+        assert!(!uris.contains(&"/c/d.html".to_string()));
+        assert!(uris.contains(&"/".to_string()));
 
-        assert_eq!(sitemap_disk, expected_sitemap);
-        assert_eq!("Sitemap: https://test.domain/sitemap.xml", robots_disk);
+        let _ = std::fs::remove_dir_all(path);
     }
 
-}
\ No newline at end of file
+    #[tokio::test]
+    async fn test_content_watcher()
+    {
+        let path = format!("tests/test_content_watcher-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(&path).unwrap();
+
+        let mut watcher = ContentWatcher::new(&path, Duration::from_millis(100)).unwrap();
+        write_file_bytes(&format!("{}/a.html", path), "this is /a".as_bytes());
+
+        let changes = tokio::time::timeout(Duration::from_secs(5), watcher.next()).await.unwrap().unwrap();
+        assert!(changes.contains(&format!("{}/a.html", path)));
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
@@ -0,0 +1,107 @@
+mod common;
+
+#[cfg(test)]
+mod snippet
+{
+    use busser::{config::{SnippetConfig, SnippetPosition}, content::{insert_tag, snippet::{applies_to, inject, inject_after_doctype}, template::IncludeOptions, Content}, filesystem::file::{write_file_bytes, Observed}};
+
+    const PAGE: &str = "<!DOCTYPE html>\n<html>\n<HEAD lang=\"en\">\n<title>a</title>\n</head>\n<body>\n<p>a</p>\n</body>\n</html>";
+
+    #[test]
+    fn test_inject()
+    {
+        assert_eq!
+        (
+            inject(PAGE, SnippetPosition::HeadStart, "<s>"),
+            "<!DOCTYPE html>\n<html>\n<HEAD lang=\"en\"><s>\n<title>a</title>\n</head>\n<body>\n<p>a</p>\n</body>\n</html>"
+        );
+        assert_eq!
+        (
+            inject(PAGE, SnippetPosition::HeadEnd, "<s>"),
+            "<!DOCTYPE html>\n<html>\n<HEAD lang=\"en\">\n<title>a</title>\n<s></head>\n<body>\n<p>a</p>\n</body>\n</html>"
+        );
+        assert_eq!
+        (
+            inject(PAGE, SnippetPosition::BodyEnd, "<s>"),
+            "<!DOCTYPE html>\n<html>\n<HEAD lang=\"en\">\n<title>a</title>\n</head>\n<body>\n<p>a</p>\n<s></body>\n</html>"
+        );
+
+        // <header> is not <head>
+        assert_eq!(inject("<header></header>", SnippetPosition::HeadStart, "<s>"), "<header></header>");
+        assert_eq!(inject("<p>a</p>", SnippetPosition::HeadEnd, "<s>"), "<p>a</p>");
+        assert_eq!(inject("<p>a</p>", SnippetPosition::BodyEnd, "<s>"), "<p>a</p><s>");
+
+        assert_eq!(inject_after_doctype("<!doctype html><p>a</p>", "<s>"), "<!doctype html><s><p>a</p>");
+        assert_eq!(inject_after_doctype("<p>a</p>", "<s>"), "<s><p>a</p>");
+
+        let tag = format!("<!--Hosted by Busser {}, https://github.com/JerboaBurrow/Busser-->\n", busser::program_version());
+        assert_eq!(insert_tag(PAGE.to_string()), PAGE.replacen("<!DOCTYPE html>\n", &format!("<!DOCTYPE html>\n{}", tag), 1));
+    }
+
+    #[test]
+    fn test_snippet_content()
+    {
+        let path = format!("tests/test_snippet_content-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(&path).unwrap();
+        write_file_bytes(&format!("{}/a.html", path), PAGE.as_bytes());
+        write_file_bytes(&format!("{}/ribbon.html", path), "<div>staging</div>".as_bytes());
+
+        let analytics = SnippetConfig { position: SnippetPosition::HeadEnd, html: Some("<script></script>".to_string()), file: None, paths: None };
+        let ribbon = SnippetConfig { position: SnippetPosition::BodyEnd, html: None, file: Some(format!("{}/ribbon.html", path)), paths: Some(vec!["^/a".to_string()]) };
+        let other = SnippetConfig { position: SnippetPosition::HeadStart, html: Some("<meta>".to_string()), file: None, paths: Some(vec!["^/blog/".to_string()]) };
+
+        assert!(applies_to(&analytics, "/b"));
+        assert!(applies_to(&ribbon, "/a.html"));
+        assert!(!applies_to(&other, "/a.html"));
+
+        let snippets = vec![analytics, ribbon, other];
+        let mut content = Content::new("/a.html", &format!("{}/a.html", path), 3600, 3600, false);
+        content.set_snippets(&snippets);
+        content.load_from_file().unwrap();
+        let body = content.utf8_body().unwrap();
+        assert!(body.contains("<script></script></head>"));
+        assert!(body.contains("<div>staging</div></body>"));
+        assert!(!body.contains("<meta>"));
+        assert!(content.depends_on(&format!("{}/ribbon.html", path)));
+
+        write_file_bytes(&format!("{}/ribbon.html", path), "<div>preview</div>".as_bytes());
+        assert!(content.is_stale());
+        content.refresh();
+        assert!(content.utf8_body().unwrap().contains("<div>preview</div></body>"));
+
+        // not html
+        let mut css = Content::new("/a.css", &format!("{}/a.css", path), 3600, 3600, false);
+        css.set_snippets(&snippets);
+        assert!(css.dependencies().is_empty());
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_snippets_in_included_partials()
+    {
+        let path = format!("tests/test_snippets_in_included_partials-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(format!("{}/partials", path)).unwrap();
+        write_file_bytes(&format!("{}/partials/head.html", path), "<head><title>a</title></head>".as_bytes());
+        write_file_bytes(&format!("{}/a.html", path), "<html><!--#include \"partials/head.html\"--><body></body></html>".as_bytes());
+
+        let snippets = vec!
+        [
+            SnippetConfig { position: SnippetPosition::HeadEnd, html: Some("<script></script>".to_string()), file: None, paths: None },
+            SnippetConfig { position: SnippetPosition::BodyEnd, html: Some("<!--#include \"partials/head.html\"-->".to_string()), file: None, paths: None }
+        ];
+        let mut content = Content::new("/a.html", &format!("{}/a.html", path), 3600, 3600, false);
+        content.set_server_side_includes(Some(IncludeOptions { content_path: path.clone(), domain: "https://test.domain".to_string() }));
+        content.set_snippets(&snippets);
+        content.load_from_file().unwrap();
+
+        // snippets are injected into the expanded page, and are not expanded themselves
+        assert_eq!
+        (
+            content.utf8_body().unwrap(),
+            "<html><head><title>a</title><script></script></head><body><!--#include \"partials/head.html\"--></body></html>"
+        );
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
@@ -0,0 +1,121 @@
+mod common;
+
+#[cfg(test)]
+mod tags
+{
+    use axum::http::{HeaderMap, HeaderValue};
+    use busser::{config::{Config, GitConfig, ReleasesConfig, TagsConfig}, filesystem::file::read_file_utf8, integrations::{forge::ForgeWebhook, git::{refresh::GitRefreshTask, releases::Releases, tags::{highest_tag, tag_version}}, github::Github}};
+    use git2::Repository;
+    use reqwest::StatusCode;
+    use semver::Version;
+
+    use crate::common::{commit, git_config, local_remote, site_config};
+
+    fn tag(repo: &Repository, name: &str, data: &str)
+    {
+        let oid = commit(repo, &[("index.html", Some(data))], data);
+        repo.tag_lightweight(name, &repo.find_object(oid, None).unwrap(), false).unwrap();
+    }
+
+    fn tags_config(version: Option<&str>, pattern: Option<&str>) -> TagsConfig
+    {
+        TagsConfig { version: version.map(|v| v.to_string()), pattern: pattern.map(|p| p.to_string()) }
+    }
+
+    fn config(path: &str, remote: &str, branch: &str, tags: TagsConfig) -> Config
+    {
+        let mut config = site_config(path, GitConfig { tags: Some(tags), ..git_config(&local_remote(remote), branch) });
+        config.domain = "test.domain".to_string();
+        config
+    }
+
+    #[test]
+    fn test_tag_version()
+    {
+        let any = TagsConfig::default();
+        assert_eq!(tag_version("v1.2.3", &any), Some(Version::new(1, 2, 3)));
+        assert_eq!(tag_version("1.2.3", &any), Some(Version::new(1, 2, 3)));
+        assert_eq!(tag_version("v1.2", &any), None);
+        assert_eq!(tag_version("release", &any), None);
+        assert_eq!(tag_version("v2.0.0-rc.1", &any), None);
+
+        let site = tags_config(Some(">=1.2, <2"), Some("^site-v"));
+        assert_eq!(tag_version("site-v1.4.0", &site), Some(Version::new(1, 4, 0)));
+        assert_eq!(tag_version("site-v2.0.0", &site), None);
+        assert_eq!(tag_version("site-v1.1.9", &site), None);
+        assert_eq!(tag_version("v1.4.0", &site), None);
+
+        assert_eq!(tag_version("v1.2.3", &tags_config(Some("not a requirement"), None)), None);
+        assert_eq!(tag_version("v1.2.3", &tags_config(None, Some("("))), None);
+    }
+
+    #[tokio::test]
+    async fn test_github_tag_events()
+    {
+        let mut headers = HeaderMap::new();
+        headers.insert("user-agent", HeaderValue::from_static("GitHub-Hookshot/abc"));
+        for (event, status) in [("push", StatusCode::OK), ("create", StatusCode::OK), ("release", StatusCode::OK), ("issues", StatusCode::CONTINUE)]
+        {
+            headers.insert("x-github-event", HeaderValue::from_static(event));
+            assert_eq!(Github::is_push(&headers).await, status);
+        }
+    }
+
+    #[test]
+    fn test_deploy_tags()
+    {
+        let path = format!("tests/test_deploy_tags-{}", uuid::Uuid::new_v4());
+        let remote = format!("{}/remote", path);
+        let repo = Repository::init(&remote).unwrap();
+        tag(&repo, "v1.0.0", "one");
+        tag(&repo, "v1.1.0", "one point one");
+        tag(&repo, "v2.0.0", "two");
+        commit(&repo, &[("index.html", Some("untagged"))], "untagged");
+        let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+        assert_eq!(highest_tag(&repo, &TagsConfig::default()).unwrap().0, "v2.0.0");
+
+        let config = config(&path, &remote, &branch, tags_config(Some("^1"), None));
+        GitRefreshTask::pull(&config);
+        assert_eq!(read_file_utf8(&config.content.home).unwrap(), "one point one");
+        assert!(Repository::open(&config.content.path).unwrap().head_detached().unwrap());
+        assert_eq!(GitRefreshTask::pull(&config), (None, None));
+
+        tag(&repo, "v1.2.0", "one point two");
+        let (info, _) = GitRefreshTask::pull(&config);
+        assert_eq!(info.as_ref().unwrap().tag, Some("v1.2.0".to_string()));
+        assert!(GitRefreshTask::head_info_to_message(info, &config).unwrap().starts_with("Deployed version v1.2.0 for test.domain"));
+        assert_eq!(read_file_utf8(&config.content.home).unwrap(), "one point two");
+
+        // a withdrawn version is rolled back
+        repo.tag_delete("v1.2.0").unwrap();
+        let (info, _) = GitRefreshTask::pull(&config);
+        assert_eq!(info.unwrap().tag, Some("v1.1.0".to_string()));
+        assert_eq!(read_file_utf8(&config.content.home).unwrap(), "one point one");
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_release_tags()
+    {
+        let path = format!("tests/test_release_tags-{}", uuid::Uuid::new_v4());
+        let remote = format!("{}/remote", path);
+        let repo = Repository::init(&remote).unwrap();
+        tag(&repo, "v1.0.0", "one");
+        commit(&repo, &[("index.html", Some("untagged"))], "untagged");
+        let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+
+        let mut config = config(&path, &remote, &branch, TagsConfig::default());
+        config.git.as_mut().unwrap().releases = Some(ReleasesConfig { path: Some(format!("{}/releases", path)), keep: None, check_links: None });
+        let releases = Releases::new(&config).unwrap();
+        assert!(releases.deploy(&config).unwrap().is_some());
+        assert_eq!(read_file_utf8(&config.content.home).unwrap(), "one");
+
+        tag(&repo, "v1.0.1", "one point zero point one");
+        assert_eq!(releases.deploy(&config).unwrap().unwrap().tag, Some("v1.0.1".to_string()));
+        assert_eq!(read_file_utf8(&config.content.home).unwrap(), "one point zero point one");
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
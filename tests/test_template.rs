@@ -0,0 +1,123 @@
+mod common;
+
+#[cfg(test)]
+mod template
+{
+    use busser::{config::Config, content::{sitemap::{lastmod, SiteMap}, template::{commit, content_file, expand, IncludeOptions}, Content}, filesystem::file::{write_file_bytes, Observed}};
+
+    #[test]
+    fn test_expand()
+    {
+        let path = format!("tests/test_expand-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(format!("{}/partials", path)).unwrap();
+        write_file_bytes(&format!("{}/partials/nav.html", path), "<nav><!--#include \"partials/logo.html\"--></nav>".as_bytes());
+        write_file_bytes(&format!("{}/partials/logo.html", path), "<img src=\"logo.png\">".as_bytes());
+        write_file_bytes(&format!("{}/partials/cycle.html", path), "<!--#include file=\"/partials/cycle.html\"-->".as_bytes());
+        write_file_bytes(&format!("{}/a.html", path), "".as_bytes());
+
+        let options = IncludeOptions { content_path: path.clone(), domain: "https://test.domain".to_string() };
+        let page = format!("{}/a.html", path);
+
+        let expanded = expand("<body><!--#include \"partials/nav.html\" --></body>", "/a", &page, &options);
+        assert_eq!(expanded.html, "<body><nav><img src=\"logo.png\"></nav></body>");
+        assert_eq!(expanded.dependencies, vec![format!("{}/partials/nav.html", path), format!("{}/partials/logo.html", path)]);
+
+        let expanded = expand("<!--#include virtual=\"partials/missing.html\"-->", "/a", &page, &options);
+        assert_eq!(expanded.html, "<!-- error including partials/missing.html -->");
+        assert_eq!(expanded.dependencies, vec![format!("{}/partials/missing.html", path)]);
+
+        let expanded = expand("<!--#include \"partials/cycle.html\"-->", "/a", &page, &options);
+        assert_eq!(expanded.html, "<!-- error including /partials/cycle.html -->");
+
+        let modified = lastmod(std::fs::metadata(&page).unwrap().modified().unwrap());
+        let expanded = expand("<!--#echo var=\"domain\"--><!--#echo var=\"uri\" --> <!--#echo var=\"lastmod\"--><!--#echo var=\"other\"-->", "/a", &page, &options);
+        assert_eq!(expanded.html, format!("https://test.domain/a {}<!--#echo var=\"other\"-->", modified));
+        assert!(expanded.dependencies.is_empty());
+
+        // this repository
+        let hash = commit(&path).unwrap();
+        assert_eq!(expand("<!--#echo var=\"commit\"-->", "/a", &page, &options).html, hash);
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_include_traversal()
+    {
+        let root = format!("tests/test_include_traversal-{}", uuid::Uuid::new_v4());
+        let path = format!("{}/site", root);
+        std::fs::create_dir_all(format!("{}/partials", path)).unwrap();
+        write_file_bytes(&format!("{}/secret.txt", root), "secret".as_bytes());
+        write_file_bytes(&format!("{}/config.json", path), "{\"api_token\": \"secret\"}".as_bytes());
+        write_file_bytes(&format!("{}/partials/nav.html", path), "<nav></nav>".as_bytes());
+        write_file_bytes(&format!("{}/a.html", path), "".as_bytes());
+
+        let options = IncludeOptions { content_path: path.clone(), domain: "https://test.domain".to_string() };
+        let page = format!("{}/a.html", path);
+
+        for include in ["../secret.txt", "/../secret.txt", "partials/../../secret.txt", "config.json", "../missing.html"]
+        {
+            let expanded = expand(&format!("<!--#include \"{}\"-->", include), "/a", &page, &options);
+            assert_eq!(expanded.html, format!("<!-- error including {} -->", include));
+            assert!(expanded.dependencies.is_empty());
+        }
+
+        assert_eq!(content_file(&path, "partials/../partials/nav.html"), Some(format!("{}/partials/../partials/nav.html", path)));
+        assert_eq!(content_file(&path, "partials/new.html"), Some(format!("{}/partials/new.html", path)));
+        assert_eq!(content_file(&path, "../secret.txt"), None);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_include_dependencies()
+    {
+        let path = format!("tests/test_include_dependencies-{}", uuid::Uuid::new_v4());
+        std::fs::create_dir_all(format!("{}/partials", path)).unwrap();
+        write_file_bytes(&format!("{}/partials/footer.html", path), "<footer>1</footer>".as_bytes());
+        write_file_bytes(&format!("{}/a.html", path), "<p>a</p><!--#include \"partials/footer.html\"-->".as_bytes());
+        write_file_bytes(&format!("{}/b.html", path), "<p>b</p><!--#include \"partials/footer.html\"-->".as_bytes());
+
+        let options = IncludeOptions { content_path: path.clone(), domain: "https://test.domain".to_string() };
+        let mut content = Content::new("/a.html", &format!("{}/a.html", path), 3600, 3600, false);
+        content.set_server_side_includes(Some(options.clone()));
+        content.load_from_file().unwrap();
+        assert_eq!(content.utf8_body().unwrap(), "<p>a</p><footer>1</footer>");
+        assert!(content.depends_on(&format!("{}/partials/footer.html", path)));
+        assert!(!content.is_stale());
+
+        write_file_bytes(&format!("{}/partials/footer.html", path), "<footer>2</footer>".as_bytes());
+        assert!(content.is_stale());
+
+        // only html is expanded
+        write_file_bytes(&format!("{}/a.css", path), "/*<!--#include \"partials/footer.html\"-->*/".as_bytes());
+        let mut css = Content::new("/a.css", &format!("{}/a.css", path), 3600, 3600, false);
+        css.set_server_side_includes(Some(options));
+        css.load_from_file().unwrap();
+        assert_eq!(css.utf8_body().unwrap(), "/*<!--#include \"partials/footer.html\"-->*/");
+        assert!(css.dependencies().is_empty());
+
+        let mut config = Config::load_or_default("tests/config.json");
+        config.content.path = path.clone();
+        config.content.home = format!("{}/a.html", path);
+        config.content.server_side_includes = Some(true);
+        config.content.cache_budget_bytes = Some(1024);
+
+        let mut sitemap = SiteMap::build(&config, false, true);
+        sitemap.refresh_all().await;
+        assert_eq!(sitemap.get("/b").unwrap().lock().await.utf8_body().unwrap(), "<p>b</p><footer>2</footer>");
+
+        assert_eq!(sitemap.dependents(&format!("{}/partials/footer.html", path)), vec!["/", "/a", "/a.html", "/b", "/b.html"]);
+        assert!(sitemap.dependents(&format!("{}/a.html", path)).is_empty());
+
+        // editing a partial refreshes the pages using it
+        write_file_bytes(&format!("{}/partials/footer.html", path), "<footer>3</footer>".as_bytes());
+        assert!(!sitemap.apply_changes(vec![format!("{}/partials/footer.html", path)]).await);
+        for uri in ["/", "/a", "/a.html", "/b", "/b.html"]
+        {
+            assert!(sitemap.get(uri).unwrap().lock().await.utf8_body().unwrap().ends_with("<footer>3</footer>"));
+        }
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}